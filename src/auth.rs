@@ -0,0 +1,117 @@
+pub mod hmac_sha256;
+pub mod oauth1;
+pub mod oauth2;
+pub mod sigv4;
+
+use hmac::{Hmac, Mac};
+use log::{debug, warn};
+use reqwest::{
+    Request as Reqwest,
+    header::{HeaderName, HeaderValue, AUTHORIZATION}
+};
+use sha2::{Digest, Sha256};
+
+use std::{error::Error, str::FromStr};
+
+use crate::model::Auth;
+
+//Applies the auth on the final request, so that signatures cover the final url, headers and body
+pub fn apply(auth: &Auth, reqwest: &mut Reqwest) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match auth {
+        Auth::Basic(basic) => {
+            let credentials = base64::encode(format!("{}:{}", basic.username, basic.password));
+            set_header(reqwest, AUTHORIZATION.as_str(), &format!("Basic {}", credentials))
+        },
+        Auth::Bearer(bearer) => set_header(reqwest, AUTHORIZATION.as_str(), &format!("Bearer {}", bearer.token)),
+        Auth::Hmac(hmac) => hmac_sha256::sign(hmac, reqwest, chrono::Utc::now().timestamp(), &get_nonce()),
+        Auth::AwsSigV4(aws) => sigv4::sign(aws, reqwest, chrono::Utc::now()),
+        Auth::OAuth1(oauth1) => oauth1::sign(oauth1, reqwest, chrono::Utc::now().timestamp(), &get_nonce())
+    }
+}
+
+fn get_nonce() -> String {
+    uuid::Uuid::new_v4().to_simple().to_string()
+}
+
+fn set_header(reqwest: &mut Reqwest, name: &str, value: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    debug!("Setting auth header {}", name);
+    reqwest.headers_mut().insert(HeaderName::from_str(name)?, HeaderValue::from_str(value)?);
+    Ok(())
+}
+
+//Body bytes to be signed, streaming bodies like multipart forms cannot be signed
+fn get_body(reqwest: &Reqwest) -> Option<&[u8]> {
+    match reqwest.body() {
+        Some(body) => {
+            let bytes = body.as_bytes();
+            if bytes.is_none() {
+                warn!("Body of request {} is a stream and cannot be signed", reqwest.url());
+            }
+
+            bytes
+        },
+        None => Some(&[])
+    }
+}
+
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn sha256_hex(message: &[u8]) -> String {
+    hex::encode(Sha256::digest(message))
+}
+
+//Percent encodes everything apart from unreserved characters as per RFC 3986
+fn encode_rfc3986(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte))
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Method, Url};
+
+    use crate::auth::*;
+
+    #[test]
+    fn test_encode_rfc3986() {
+        assert_eq!(encode_rfc3986("Ladies + Gentlemen"), "Ladies%20%2B%20Gentlemen");
+        assert_eq!(encode_rfc3986("a-b.c_d~e"), "a-b.c_d~e");
+        assert_eq!(encode_rfc3986("é!"), "%C3%A9%21");
+    }
+
+    #[test]
+    fn test_basic_auth() {
+        let auth: Auth = serde_yaml::from_str("type: Basic\nusername: user\npassword: pass").unwrap();
+        let mut reqwest = Reqwest::new(Method::GET, Url::parse("https://google.com/").unwrap());
+        apply(&auth, &mut reqwest).unwrap();
+
+        assert!(!auth.is_dynamic());
+        assert_eq!(reqwest.headers().get(AUTHORIZATION).unwrap(), "Basic dXNlcjpwYXNz");
+    }
+
+    #[test]
+    fn test_bearer_auth() {
+        let auth: Auth = serde_yaml::from_str("type: Bearer\ntoken: some_token").unwrap();
+        let mut reqwest = Reqwest::new(Method::GET, Url::parse("https://google.com/").unwrap());
+        apply(&auth, &mut reqwest).unwrap();
+
+        assert_eq!(reqwest.headers().get(AUTHORIZATION).unwrap(), "Bearer some_token");
+    }
+
+    #[test]
+    fn test_error_for_invalid_auth_type() {
+        let auth = serde_yaml::from_str::<Auth>("type: Digest\nusername: user");
+        assert!(auth.err().unwrap().to_string().contains("unknown variant `Digest`"));
+    }
+}
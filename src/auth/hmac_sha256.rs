@@ -0,0 +1,131 @@
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use regex::Regex;
+use reqwest::Request as Reqwest;
+
+use std::error::Error;
+
+use crate::{
+    auth::{get_body, hmac_sha256, set_header, sha256_hex},
+    model::{Encoding, HmacAuth}
+};
+
+//Signs the request with HMAC-SHA256 computed over the configured canonical string
+pub fn sign(auth: &HmacAuth, reqwest: &mut Reqwest, timestamp: i64, nonce: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let timestamp = timestamp.to_string();
+
+    //Headers are set before signing so that they can be part of canonical string
+    if !auth.timestamp_header.is_empty() {
+        set_header(reqwest, &auth.timestamp_header, &timestamp)?;
+    }
+
+    if !auth.nonce_header.is_empty() {
+        set_header(reqwest, &auth.nonce_header, nonce)?;
+    }
+
+    let canonical_string = get_canonical_string(&auth.canonical_string, reqwest, &timestamp, nonce);
+    debug!("HMAC canonical string for {} : {:?}", reqwest.url(), canonical_string);
+
+    let signature = hmac_sha256(auth.key.as_bytes(), canonical_string.as_bytes());
+    let signature = match auth.encoding {
+        Encoding::Hex => hex::encode(signature),
+        Encoding::Base64 => base64::encode(signature)
+    };
+
+    set_header(reqwest, &auth.header, &format!("{}{}", auth.prefix, signature))
+}
+
+//Replaces placeholders like {method}, {path} or {header.content-type} in the template
+fn get_canonical_string(template: &str, reqwest: &Reqwest, timestamp: &str, nonce: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"\{(\w+)(\.[\w-]+)?\}").unwrap();
+    }
+
+    let body = get_body(reqwest).unwrap_or_default();
+
+    RE.replace_all(template, |caps: &regex::Captures| {
+        let url = reqwest.url();
+        match (&caps[1], caps.get(2).map(|m| &m.as_str()[1..])) {
+            ("method", None) => reqwest.method().to_string(),
+            ("url", None) => url.to_string(),
+            ("host", None) => url.host_str().unwrap_or_default().to_owned(),
+            ("path", None) => url.path().to_owned(),
+            ("query", None) => url.query().unwrap_or_default().to_owned(),
+            ("body", None) => String::from_utf8_lossy(body).to_string(),
+            ("bodySha256", None) => sha256_hex(body),
+            ("bodyBase64", None) => base64::encode(body),
+            ("timestamp", None) => timestamp.to_owned(),
+            ("nonce", None) => nonce.to_owned(),
+            ("header", Some(name)) => reqwest.headers().get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_owned(),
+            _ => {
+                warn!("Unknown placeholder {} in HMAC canonical string", &caps[0]);
+                caps[0].to_owned()
+            }
+        }
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Method, Url};
+
+    use crate::auth::hmac_sha256::*;
+
+    fn get_auth(yaml: &str) -> HmacAuth {
+        match serde_yaml::from_str(yaml).unwrap() {
+            crate::model::Auth::Hmac(hmac) => hmac,
+            _ => panic!("auth should be of type Hmac")
+        }
+    }
+
+    fn get_reqwest() -> Reqwest {
+        let mut reqwest = Reqwest::new(Method::POST, Url::parse("https://google.com/orders?id=1").unwrap());
+        reqwest.headers_mut().insert("content-type", "application/json".parse().unwrap());
+        *reqwest.body_mut() = Some(reqwest::Body::from(r#"{"item":"book"}"#));
+        reqwest
+    }
+
+    #[test]
+    fn test_canonical_string() {
+        let reqwest = get_reqwest();
+        let template = "{method}\n{host}{path}?{query}\n{header.content-type}\n{timestamp}:{nonce}\n{body}\n{unknown}";
+        assert_eq!(get_canonical_string(template, &reqwest, "1600000000", "abc"),
+            "POST\ngoogle.com/orders?id=1\napplication/json\n1600000000:abc\n{\"item\":\"book\"}\n{unknown}");
+    }
+
+    #[test]
+    fn test_hmac_sign_with_defaults() {
+        let auth = get_auth("type: Hmac\nkey: secret");
+        let mut reqwest = get_reqwest();
+        sign(&auth, &mut reqwest, 1600000000, "abc").unwrap();
+
+        let expected = hex::encode(hmac_sha256(b"secret", "POST\n/orders\nid=1\n1600000000\n{\"item\":\"book\"}".as_bytes()));
+        assert_eq!(reqwest.headers().get("authorization").unwrap().to_str().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_hmac_sign_with_custom_header() {
+        let auth = get_auth(r#"
+        type: Hmac
+        key: secret
+        header: x-signature
+        prefix: 'HMAC '
+        encoding: Base64
+        canonicalString: "{method}:{header.x-timestamp}:{bodySha256}"
+        timestampHeader: x-timestamp
+        nonceHeader: x-nonce"#);
+
+        let mut reqwest = get_reqwest();
+        sign(&auth, &mut reqwest, 1600000000, "abc").unwrap();
+
+        let canonical_string = format!("POST:1600000000:{}", sha256_hex(br#"{"item":"book"}"#));
+        let expected = format!("HMAC {}", base64::encode(hmac_sha256(b"secret", canonical_string.as_bytes())));
+
+        assert_eq!(reqwest.headers().get("x-timestamp").unwrap(), "1600000000");
+        assert_eq!(reqwest.headers().get("x-nonce").unwrap(), "abc");
+        assert_eq!(reqwest.headers().get("x-signature").unwrap().to_str().unwrap(), expected);
+    }
+}
@@ -0,0 +1,157 @@
+use hmac::{Hmac, Mac};
+use log::debug;
+use reqwest::{
+    Request as Reqwest,
+    header::{AUTHORIZATION, CONTENT_TYPE}
+};
+use sha1::Sha1;
+
+use std::error::Error;
+
+use crate::{
+    auth::{encode_rfc3986, get_body, hmac_sha256, set_header},
+    model::{OAuth1Auth, OAuth1SignatureMethod}
+};
+
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+
+//Signs the request as per OAuth 1.0a (RFC 5849) and sets the Authorization header
+pub fn sign(auth: &OAuth1Auth, reqwest: &mut Reqwest, timestamp: i64, nonce: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let signature_method = match auth.signature_method {
+        OAuth1SignatureMethod::HmacSha1 => "HMAC-SHA1",
+        OAuth1SignatureMethod::HmacSha256 => "HMAC-SHA256",
+        OAuth1SignatureMethod::Plaintext => "PLAINTEXT"
+    };
+
+    let mut oauth_params = vec![
+        (String::from("oauth_consumer_key"), auth.consumer_key.clone()),
+        (String::from("oauth_nonce"), nonce.to_owned()),
+        (String::from("oauth_signature_method"), String::from(signature_method)),
+        (String::from("oauth_timestamp"), timestamp.to_string()),
+        (String::from("oauth_version"), String::from("1.0"))
+    ];
+
+    if !auth.token.is_empty() {
+        oauth_params.push((String::from("oauth_token"), auth.token.clone()));
+    }
+
+    let key = format!("{}&{}", encode_rfc3986(&auth.consumer_secret), encode_rfc3986(&auth.token_secret));
+    let signature = match auth.signature_method {
+        OAuth1SignatureMethod::Plaintext => key,
+        _ => {
+            let base_string = get_base_string(reqwest, &oauth_params);
+            debug!("OAuth1 base string for {} : {:?}", reqwest.url(), base_string);
+
+            let signature = match auth.signature_method {
+                OAuth1SignatureMethod::HmacSha1 => hmac_sha1(key.as_bytes(), base_string.as_bytes()),
+                _ => hmac_sha256(key.as_bytes(), base_string.as_bytes())
+            };
+
+            base64::encode(signature)
+        }
+    };
+
+    oauth_params.push((String::from("oauth_signature"), signature));
+
+    let mut params = oauth_params.iter()
+        .map(|(k, v)| format!("{}=\"{}\"", encode_rfc3986(k), encode_rfc3986(v)))
+        .collect::<Vec<String>>();
+
+    if !auth.realm.is_empty() {
+        params.insert(0, format!("realm=\"{}\"", auth.realm));
+    }
+
+    set_header(reqwest, AUTHORIZATION.as_str(), &format!("OAuth {}", params.join(", ")))
+}
+
+//Base string is METHOD&url&params, where params include query, oauth and form body parameters
+fn get_base_string(reqwest: &Reqwest, oauth_params: &[(String, String)]) -> String {
+    let url = reqwest.url();
+    let mut params = url.query_pairs()
+        .map(|(k, v)| (encode_rfc3986(&k), encode_rfc3986(&v)))
+        .collect::<Vec<(String, String)>>();
+
+    params.extend(oauth_params.iter().map(|(k, v)| (encode_rfc3986(k), encode_rfc3986(v))));
+
+    if is_form_body(reqwest) {
+        if let Some(body) = get_body(reqwest) {
+            params.extend(url::form_urlencoded::parse(body).map(|(k, v)| (encode_rfc3986(&k), encode_rfc3986(&v))));
+        }
+    }
+
+    params.sort();
+    let params = params.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<String>>()
+        .join("&");
+
+    let mut base_url = url.clone();
+    base_url.set_query(None);
+    base_url.set_fragment(None);
+
+    format!("{}&{}&{}", reqwest.method().as_str(), encode_rfc3986(base_url.as_str()), encode_rfc3986(&params))
+}
+
+fn is_form_body(reqwest: &Reqwest) -> bool {
+    reqwest.headers().get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(FORM_CONTENT_TYPE))
+}
+
+fn hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Method, Url};
+
+    use crate::auth::oauth1::*;
+
+    fn get_auth(signature_method: OAuth1SignatureMethod) -> OAuth1Auth {
+        OAuth1Auth {
+            consumer_key: String::from("xvz1evFS4wEEPTGEFPHBog"),
+            consumer_secret: String::from("kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw"),
+            token: String::from("370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb"),
+            token_secret: String::from("LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE"),
+            signature_method,
+            realm: String::new()
+        }
+    }
+
+    fn get_reqwest() -> Reqwest {
+        let url = Url::parse("https://api.twitter.com/1.1/statuses/update.json?include_entities=true").unwrap();
+        let mut reqwest = Reqwest::new(Method::POST, url);
+        reqwest.headers_mut().insert(CONTENT_TYPE, FORM_CONTENT_TYPE.parse().unwrap());
+        *reqwest.body_mut() = Some(reqwest::Body::from("status=Hello%20Ladies%20%2b%20Gentlemen%2c%20a%20signed%20OAuth%20request%21"));
+        reqwest
+    }
+
+    #[test]
+    fn test_oauth1_hmac_sha1_signature() {
+        //Example from Twitter's documentation on creating a signature
+        let mut reqwest = get_reqwest();
+        sign(&get_auth(OAuth1SignatureMethod::HmacSha1), &mut reqwest, 1318622958, "kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg").unwrap();
+
+        let header = reqwest.headers().get(AUTHORIZATION).unwrap().to_str().unwrap();
+        assert!(header.starts_with("OAuth oauth_consumer_key=\"xvz1evFS4wEEPTGEFPHBog\", "));
+        assert!(header.contains("oauth_token=\"370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb\""));
+        assert!(header.contains("oauth_signature=\"hCtSmYh%2BiHYCEqBWrE7C7hYmtUk%3D\""));
+    }
+
+    #[test]
+    fn test_oauth1_plaintext_signature_with_realm() {
+        let mut auth = get_auth(OAuth1SignatureMethod::Plaintext);
+        auth.realm = String::from("Example");
+
+        let mut reqwest = get_reqwest();
+        sign(&auth, &mut reqwest, 1318622958, "abc").unwrap();
+
+        let header = reqwest.headers().get(AUTHORIZATION).unwrap().to_str().unwrap();
+        assert!(header.starts_with("OAuth realm=\"Example\", "));
+        assert!(header.contains("oauth_signature_method=\"PLAINTEXT\""));
+        assert!(header.contains("oauth_signature=\"kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw%26LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE\""));
+    }
+}
@@ -0,0 +1,352 @@
+use log::{debug, info, warn};
+use reqwest::{
+    Request as Reqwest,
+    header::{HeaderValue, AUTHORIZATION}
+};
+use rustc_hash::FxHashMap as HashMap;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use std::{
+    error::Error,
+    sync::Arc,
+    time::{Duration, Instant}
+};
+
+use crate::{
+    model::{OAuth2, OAuth2Grant, TokenCache},
+    parse::preprocessor,
+    protocol::http::{self, HttpClient},
+    report::stats::{Stats, TOKEN_REQUEST_PREFIX}
+};
+
+pub type TokenSlot = Arc<Mutex<Option<Token>>>;
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    access_token: String,
+    token_type: String,
+    refresh_token: String,
+    expires_at: Option<Instant>
+}
+
+impl Token {
+    //Token is refreshed `refreshBefore` secs before it actually expires
+    fn is_valid(&self, refresh_before: u64) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Instant::now() + Duration::from_secs(refresh_before) < expires_at,
+            None => true
+        }
+    }
+
+    fn header_value(&self) -> String {
+        let token_type = match self.token_type.is_empty() || self.token_type.eq_ignore_ascii_case("bearer") {
+            true => "Bearer",
+            false => &self.token_type
+        };
+
+        format!("{} {}", token_type, self.access_token)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+
+    #[serde(default)]
+    token_type: String,
+
+    #[serde(default)]
+    expires_in: Option<u64>,
+
+    #[serde(default)]
+    refresh_token: Option<String>
+}
+
+//Fetches tokens as per the oauth2 config and keeps them cached till they are about to expire
+pub struct TokenProvider {
+    oauth2: OAuth2,
+    global_slot: TokenSlot
+}
+
+impl TokenProvider {
+    pub fn new(oauth2: &OAuth2) -> Self {
+        TokenProvider {
+            oauth2: oauth2.to_owned(),
+            global_slot: Arc::new(Mutex::new(None))
+        }
+    }
+
+    //Every thread gets the same slot when tokens are cached globally, else a slot of its own
+    pub fn get_slot(&self) -> TokenSlot {
+        match self.oauth2.cache {
+            TokenCache::Global => self.global_slot.clone(),
+            TokenCache::Thread => Arc::new(Mutex::new(None))
+        }
+    }
+
+    //Adds the token to requests which do not have an authorization header already
+    pub async fn authorize(&self, mut reqwest: Reqwest, http_client: &HttpClient, slot: &TokenSlot,
+        env_map: &HashMap<String, String>, vec_stats: &mut Vec<Stats>, thread_count: u16)
+    -> Result<Reqwest, Box<dyn Error + Send + Sync>> {
+        if reqwest.headers().contains_key(AUTHORIZATION) {
+            return Ok(reqwest)
+        }
+
+        let header_value = self.get_token(http_client, slot, env_map, vec_stats, thread_count).await?;
+        reqwest.headers_mut().insert(AUTHORIZATION, HeaderValue::from_str(&header_value)?);
+        Ok(reqwest)
+    }
+
+    async fn get_token(&self, http_client: &HttpClient, slot: &TokenSlot, env_map: &HashMap<String, String>,
+        vec_stats: &mut Vec<Stats>, thread_count: u16) -> Result<String, Box<dyn Error + Send + Sync>> {
+        //Lock is held while fetching so that threads sharing the slot do not fetch the token simultaneously
+        let mut token = slot.lock().await;
+        if let Some(token) = token.as_ref().filter(|t| t.is_valid(self.oauth2.refresh_before)) {
+            return Ok(token.header_value())
+        }
+
+        let oauth2 = get_processed_oauth2(&self.oauth2, env_map);
+        let refresh_token = token.as_ref()
+            .map(|t| t.refresh_token.to_owned())
+            .filter(|t| !t.is_empty());
+
+        let new_token = match refresh_token {
+            Some(refresh_token) => {
+                debug!("Refreshing oauth2 token");
+                match fetch_token(http_client, &oauth2, get_refresh_params(&refresh_token), "refresh", vec_stats, thread_count).await {
+                    Ok(new_token) => Ok(new_token),
+                    Err(err) => {
+                        warn!("Refreshing oauth2 token failed, fetching a new one: {}", err);
+                        fetch_token(http_client, &oauth2, get_grant_params(&oauth2), "token", vec_stats, thread_count).await
+                    }
+                }
+            },
+            None => fetch_token(http_client, &oauth2, get_grant_params(&oauth2), "token", vec_stats, thread_count).await
+        }?;
+
+        //Refresh token is retained if the token endpoint did not issue a new one
+        let new_token = match (new_token.refresh_token.is_empty(), token.as_ref()) {
+            (true, Some(old_token)) => Token { refresh_token: old_token.refresh_token.to_owned(), ..new_token },
+            _ => new_token
+        };
+
+        let header_value = new_token.header_value();
+        *token = Some(new_token);
+        Ok(header_value)
+    }
+}
+
+async fn fetch_token(http_client: &HttpClient, oauth2: &OAuth2, mut params: Vec<(&str, String)>, call: &str,
+    vec_stats: &mut Vec<Stats>, thread_count: u16) -> Result<Token, Box<dyn Error + Send + Sync>> {
+    if !oauth2.scope.is_empty() {
+        params.push(("scope", oauth2.scope.to_owned()));
+    }
+
+    let mut builder = http_client.get_client().post(&oauth2.token_url);
+
+    if oauth2.basic_auth {
+        builder = builder.basic_auth(&oauth2.client_id, Some(&oauth2.client_secret));
+    } else {
+        params.push(("client_id", oauth2.client_id.to_owned()));
+        if !oauth2.client_secret.is_empty() {
+            params.push(("client_secret", oauth2.client_secret.to_owned()));
+        }
+    }
+
+    let reqwest = builder.form(&params).build()?;
+
+    //Token calls are reported under their own name so that they are not mixed up with the load.
+    //Calls failing without a response are recorded with status 0 and the kind of error, like the requests
+    let name = format!("{}{}", TOKEN_REQUEST_PREFIX, call);
+    let start_time = Instant::now();
+    let (response, latency) = match http_client.execute(reqwest).await {
+        Ok(result) => result,
+        Err(err) => {
+            let mut stats = Stats::new(&name, 0, start_time.elapsed().as_micros() as u64, thread_count);
+            stats.error_kind = Some(http::get_error_kind(err.as_ref()));
+            stats.error_message = err.to_string();
+            vec_stats.push(stats);
+            return Err(err)
+        }
+    };
+
+    let status = response.status();
+    vec_stats.push(Stats::new(&name, status.as_u16(), latency, thread_count));
+
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(format!("Token request to {} failed with status {}: {}", oauth2.token_url, status, body).into())
+    }
+
+    let token_response: TokenResponse = serde_json::from_str(&body)?;
+    info!("Fetched oauth2 token expiring in {:?} secs", token_response.expires_in);
+
+    Ok(Token {
+        access_token: token_response.access_token,
+        token_type: token_response.token_type,
+        refresh_token: token_response.refresh_token.unwrap_or_default(),
+        expires_at: token_response.expires_in.map(|secs| Instant::now() + Duration::from_secs(secs))
+    })
+}
+
+fn get_grant_params(oauth2: &OAuth2) -> Vec<(&str, String)> {
+    match oauth2.grant {
+        OAuth2Grant::ClientCredentials => vec![("grant_type", String::from("client_credentials"))],
+        OAuth2Grant::Password => vec![
+            ("grant_type", String::from("password")),
+            ("username", oauth2.username.to_owned()),
+            ("password", oauth2.password.to_owned())
+        ],
+        OAuth2Grant::RefreshToken => get_refresh_params(&oauth2.refresh_token)
+    }
+}
+
+fn get_refresh_params(refresh_token: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("grant_type", String::from("refresh_token")),
+        ("refresh_token", refresh_token.to_owned())
+    ]
+}
+
+//Config values can refer to env and data variables, e.g. a different user per thread for password grant
+fn get_processed_oauth2(oauth2: &OAuth2, env_map: &HashMap<String, String>) -> OAuth2 {
+    let s_oauth2 = serde_json::to_string(oauth2).expect("OAuth2 config cannot be serialized");
+    if !s_oauth2.contains("{{") {
+        return oauth2.to_owned()
+    }
+
+    match serde_json::from_str(&preprocessor::param_substitution(&s_oauth2, env_map)) {
+        Ok(processed) => processed,
+        Err(err) => {
+            warn!("Unable to deserialize oauth2 config after parameter replacement, using original config: {}", err);
+            oauth2.to_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::Filter;
+
+    use std::sync::atomic::{AtomicU16, Ordering};
+
+    use crate::{auth::oauth2::*, model::ErrorKind};
+
+    fn get_oauth2(yaml: &str) -> OAuth2 {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn get_token(expires_in: Option<u64>) -> Token {
+        Token {
+            access_token: String::from("abc"),
+            token_type: String::from("bearer"),
+            refresh_token: String::new(),
+            expires_at: expires_in.map(|secs| Instant::now() + Duration::from_secs(secs))
+        }
+    }
+
+    #[test]
+    fn test_token_validity() {
+        assert!(get_token(None).is_valid(30));
+        assert!(get_token(Some(60)).is_valid(30));
+        assert!(!get_token(Some(20)).is_valid(30));
+    }
+
+    #[test]
+    fn test_token_header_value() {
+        assert_eq!(get_token(None).header_value(), "Bearer abc");
+
+        let token = Token { token_type: String::from("MAC"), ..get_token(None) };
+        assert_eq!(token.header_value(), "MAC abc");
+    }
+
+    #[test]
+    fn test_oauth2_defaults() {
+        let oauth2 = get_oauth2("tokenUrl: https://google.com/token\nclientId: client");
+        assert_eq!(oauth2.grant, OAuth2Grant::ClientCredentials);
+        assert_eq!(oauth2.cache, TokenCache::Global);
+        assert_eq!(oauth2.refresh_before, 30);
+    }
+
+    #[test]
+    fn test_grant_params() {
+        let oauth2 = get_oauth2("tokenUrl: https://google.com/token\nclientId: client\ngrant: password\nusername: user\npassword: pass");
+        assert_eq!(get_grant_params(&oauth2), vec![("grant_type", String::from("password")),
+            ("username", String::from("user")), ("password", String::from("pass"))]);
+
+        let oauth2 = get_oauth2("tokenUrl: https://google.com/token\nclientId: client\ngrant: refreshToken\nrefreshToken: xyz");
+        assert_eq!(get_grant_params(&oauth2), vec![("grant_type", String::from("refresh_token")), ("refresh_token", String::from("xyz"))]);
+    }
+
+    #[test]
+    fn test_processed_oauth2() {
+        let oauth2 = get_oauth2("tokenUrl: https://google.com/token\nclientId: client\ngrant: password\nusername: '{{user}}'\npassword: pass");
+        let mut env_map = HashMap::default();
+        env_map.insert(String::from("user"), String::from("user1"));
+
+        assert_eq!(get_processed_oauth2(&oauth2, &env_map).username, "user1");
+    }
+
+    #[tokio::test]
+    async fn test_token_is_cached_and_refreshed() {
+        let calls = Arc::new(AtomicU16::new(0));
+        let calls_clone = calls.clone();
+
+        let route = warp::post()
+            .and(warp::path("token"))
+            .and(warp::body::form())
+            .map(move |form: std::collections::HashMap<String, String>| {
+                let call = calls_clone.fetch_add(1, Ordering::SeqCst);
+                warp::reply::json(&serde_json::json!({
+                    "access_token": format!("token{}-{}", call, form["grant_type"]),
+                    "token_type": "bearer",
+                    "expires_in": 10,
+                    "refresh_token": "refresh"
+                }))
+            });
+
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let oauth2 = get_oauth2(&format!("tokenUrl: http://{}/token\nclientId: client\nrefreshBefore: 5", addr));
+        let token_provider = TokenProvider::new(&oauth2);
+        let slot = token_provider.get_slot();
+        let client = HttpClient::get_default_async_client().unwrap();
+        let mut vec_stats = vec![];
+
+        let token = token_provider.get_token(&client, &slot, &HashMap::default(), &mut vec_stats, 1).await.unwrap();
+        assert_eq!(token, "Bearer token0-client_credentials");
+
+        //Cached token is returned while it is valid
+        let token = token_provider.get_token(&client, &slot, &HashMap::default(), &mut vec_stats, 1).await.unwrap();
+        assert_eq!(token, "Bearer token0-client_credentials");
+
+        //Refresh token is used once the token is about to expire
+        slot.lock().await.as_mut().unwrap().expires_at = Some(Instant::now());
+        let token = token_provider.get_token(&client, &slot, &HashMap::default(), &mut vec_stats, 1).await.unwrap();
+        assert_eq!(token, "Bearer token1-refresh_token");
+
+        assert_eq!(vec_stats.len(), 2);
+        assert_eq!(vec_stats[0].name, "oauth2:token");
+        assert_eq!(vec_stats[1].name, "oauth2:refresh");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_token_url() {
+        //Port of a listener which is closed, so that the connection is refused
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let oauth2 = get_oauth2(&format!("tokenUrl: http://{}/token\nclientId: client", addr));
+        let token_provider = TokenProvider::new(&oauth2);
+        let slot = token_provider.get_slot();
+        let client = HttpClient::get_default_async_client().unwrap();
+        let mut vec_stats = vec![];
+
+        assert!(token_provider.get_token(&client, &slot, &HashMap::default(), &mut vec_stats, 1).await.is_err());
+        assert_eq!(vec_stats.len(), 1);
+        assert_eq!((vec_stats[0].name.as_str(), vec_stats[0].status), ("oauth2:token", 0));
+        assert_eq!(vec_stats[0].error_kind, Some(ErrorKind::Connect));
+        assert!(!vec_stats[0].error_message.is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+use chrono::{DateTime, Utc};
+use log::debug;
+use reqwest::Request as Reqwest;
+
+use std::{collections::BTreeMap, error::Error};
+
+use crate::{
+    auth::{encode_rfc3986, get_body, hmac_sha256, set_header, sha256_hex},
+    model::AwsSigV4Auth
+};
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+//Signs the request as per AWS Signature Version 4
+pub fn sign(auth: &AwsSigV4Auth, reqwest: &mut Reqwest, now: DateTime<Utc>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let is_s3 = auth.service == "s3";
+
+    let payload_hash = match get_body(reqwest) {
+        Some(body) => sha256_hex(body),
+        None => String::from(UNSIGNED_PAYLOAD)
+    };
+
+    //Headers are added before computing canonical request so that they are signed too
+    set_header(reqwest, "x-amz-date", &amz_date)?;
+
+    if is_s3 {
+        set_header(reqwest, "x-amz-content-sha256", &payload_hash)?;
+    }
+
+    if !auth.session_token.is_empty() {
+        set_header(reqwest, "x-amz-security-token", &auth.session_token)?;
+    }
+
+    let (canonical_headers, signed_headers) = get_canonical_headers(reqwest, &auth.signed_headers);
+
+    let canonical_request = format!("{}\n{}\n{}\n{}\n{}\n{}",
+        reqwest.method().as_str(),
+        get_canonical_uri(reqwest.url().path(), is_s3),
+        get_canonical_query(reqwest),
+        canonical_headers,
+        signed_headers,
+        payload_hash);
+
+    debug!("AWS SigV4 canonical request for {} : {:?}", reqwest.url(), canonical_request);
+
+    let scope = format!("{}/{}/{}/aws4_request", date, auth.region, auth.service);
+    let string_to_sign = format!("{}\n{}\n{}\n{}", ALGORITHM, amz_date, scope, sha256_hex(canonical_request.as_bytes()));
+
+    let signing_key = get_signing_key(&auth.secret_key, &date, &auth.region, &auth.service);
+    let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+    set_header(reqwest, "authorization", &format!("{} Credential={}/{}, SignedHeaders={}, Signature={}",
+        ALGORITHM, auth.access_key, scope, signed_headers, signature))
+}
+
+fn get_signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+//Path segments are encoded twice for all services except S3
+fn get_canonical_uri(path: &str, is_s3: bool) -> String {
+    if path.is_empty() {
+        return String::from("/");
+    }
+
+    if is_s3 {
+        return path.to_owned();
+    }
+
+    path.split('/')
+        .map(encode_rfc3986)
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn get_canonical_query(reqwest: &Reqwest) -> String {
+    let mut params = reqwest.url().query_pairs()
+        .map(|(k, v)| (encode_rfc3986(&k), encode_rfc3986(&v)))
+        .collect::<Vec<(String, String)>>();
+
+    params.sort();
+    params.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+//Host header is added by the client while sending, so it is derived from the url.
+//Authorization is never signed, and other headers only when listed, as proxies may change headers like connection on the way
+fn get_canonical_headers(reqwest: &Reqwest, signed_headers: &[String]) -> (String, String) {
+    let mut headers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    let url = reqwest.url();
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_owned()
+    };
+
+    headers.insert(String::from("host"), vec![host]);
+
+    let is_signed = |name: &str| name == "content-type" || name.starts_with("x-amz-") || signed_headers.iter().any(|h| h.eq_ignore_ascii_case(name));
+    for (name, value) in reqwest.headers().iter().filter(|(name, _)| *name != "authorization" && is_signed(name.as_str())) {
+        let value = value.to_str().unwrap_or_default()
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        headers.entry(name.as_str().to_lowercase()).or_default().push(value);
+    }
+
+    let canonical_headers = headers.iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v.join(",")))
+        .collect::<String>();
+
+    let signed_headers = headers.keys()
+        .map(|k| k.as_str())
+        .collect::<Vec<&str>>()
+        .join(";");
+
+    (canonical_headers, signed_headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use reqwest::{Method, Url};
+
+    use crate::auth::sigv4::*;
+
+    fn get_auth(service: &str) -> AwsSigV4Auth {
+        AwsSigV4Auth {
+            access_key: String::from("AKIDEXAMPLE"),
+            secret_key: String::from("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY"),
+            session_token: String::new(),
+            region: String::from("us-east-1"),
+            service: String::from(service),
+            signed_headers: vec![]
+        }
+    }
+
+    #[test]
+    fn test_canonical_uri() {
+        assert_eq!(get_canonical_uri("", false), "/");
+        assert_eq!(get_canonical_uri("/documents%20and%20settings/", false), "/documents%2520and%2520settings/");
+        assert_eq!(get_canonical_uri("/documents%20and%20settings/", true), "/documents%20and%20settings/");
+    }
+
+    #[test]
+    fn test_canonical_query() {
+        let reqwest = Reqwest::new(Method::GET, Url::parse("https://example.com/?b=2&a=c d&a=1").unwrap());
+        assert_eq!(get_canonical_query(&reqwest), "a=1&a=c%20d&b=2");
+    }
+
+    #[test]
+    fn test_sign_get_vanilla() {
+        //Test case from AWS SigV4 test suite
+        let mut reqwest = Reqwest::new(Method::GET, Url::parse("https://example.amazonaws.com/").unwrap());
+        let now = Utc.ymd(2015, 8, 30).and_hms(12, 36, 0);
+
+        sign(&get_auth("service"), &mut reqwest, now).unwrap();
+
+        assert_eq!(reqwest.headers().get("x-amz-date").unwrap(), "20150830T123600Z");
+        assert_eq!(reqwest.headers().get("authorization").unwrap(), "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+            SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31");
+    }
+
+    #[test]
+    fn test_sign_s3_adds_content_hash() {
+        let mut reqwest = Reqwest::new(Method::PUT, Url::parse("https://bucket.s3.amazonaws.com/key").unwrap());
+        *reqwest.body_mut() = Some(reqwest::Body::from("content"));
+
+        let mut auth = get_auth("s3");
+        auth.session_token = String::from("token");
+        sign(&auth, &mut reqwest, Utc::now()).unwrap();
+
+        assert_eq!(reqwest.headers().get("x-amz-content-sha256").unwrap().to_str().unwrap(), sha256_hex(b"content"));
+        assert_eq!(reqwest.headers().get("x-amz-security-token").unwrap(), "token");
+        assert!(reqwest.headers().get("authorization").unwrap().to_str().unwrap()
+            .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-security-token,"));
+    }
+
+    #[test]
+    fn test_signed_headers() {
+        let mut reqwest = Reqwest::new(Method::POST, Url::parse("https://example.amazonaws.com/").unwrap());
+        for (name, value) in [("authorization", "Bearer old"), ("connection", "keep-alive"), ("content-type", "application/json"),
+            ("x-amz-target", "Service.Action"), ("x-api-key", "key"), ("user-agent", "bombardier")] {
+            set_header(&mut reqwest, name, value).unwrap();
+        }
+
+        let mut auth = get_auth("service");
+        auth.signed_headers = vec![String::from("X-Api-Key"), String::from("Authorization")];
+
+        //Existing authorization header is replaced and not signed
+        let (_, signed_headers) = get_canonical_headers(&reqwest, &auth.signed_headers);
+        assert_eq!(signed_headers, "content-type;host;x-amz-target;x-api-key");
+
+        sign(&auth, &mut reqwest, Utc::now()).unwrap();
+        let authorization = reqwest.headers().get("authorization").unwrap().to_str().unwrap();
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential="));
+        assert!(authorization.contains("SignedHeaders=content-type;host;x-amz-date;x-amz-target;x-api-key,"));
+    }
+}
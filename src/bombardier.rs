@@ -1,5 +1,4 @@
 use chrono::{Utc, DateTime};
-use crossbeam::channel;
 use log::{debug, info, error, warn};
 use parking_lot::FairMutex as Mutex;
 use reqwest::Request as Reqwest;
@@ -11,9 +10,10 @@ use tokio::{
     time
 };
 
-use std::{error::Error, sync::{Arc, atomic::{AtomicU16, Ordering}}};
+use std::{collections::BTreeMap, error::Error, sync::{Arc, atomic::{AtomicU16, Ordering}}};
 
 use crate::{
+    auth::oauth2::TokenProvider,
     converter, 
     data::DataProvider, 
     model::*, 
@@ -22,8 +22,9 @@ use crate::{
         preprocessor,
         postprocessor
     }, 
-    protocol::http::{self, HttpClient}, 
-    report::stats
+    protocol::http::{self, Execution, HttpClient}, 
+    report::{run::Run, stats},
+    script::{self, ScriptEngine}
 };
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -31,10 +32,13 @@ pub struct  Bombardier {
     pub config: Config,
     pub env_map: HashMap<String, String>,
     pub requests: Vec<Request>,
+
+    #[serde(default)]
+    pub run: Run,
 }
 
 impl Bombardier {
-    pub fn new(config: Config, env: String, scenarios: String) 
+    pub fn new(config: Config, env: String, scenarios: String, tags: BTreeMap<String, String>) 
      -> Result<Bombardier, Box<dyn Error>>  {
         //Prepare environment map
         let env_map = match parser::parse_env_map(&env) {
@@ -43,13 +47,14 @@ impl Bombardier {
         };
         
         //Prepare bombardier requests
-        let requests = match parser::parse_requests(&scenarios, &env_map) {
+        let requests = match parser::parse_requests(&scenarios, &config.scenarios_file, &env_map) {
             Err(err) => return Err(err),
             Ok(v) => v
         };
 
         //Preparing bombardier
         Ok(Bombardier {
+            run: Run::new(&config, &scenarios, tags),
             config,
             env_map,
             requests,
@@ -58,15 +63,17 @@ impl Bombardier {
 }
 
 impl Bombardier {
-    pub async fn bombard(&self, stats_sender: channel::Sender<Vec<stats::Stats>>)
+    pub async fn bombard(&self, stats_sender: stats::StatsSender)
     -> Result<(), Box<dyn Error + Send + Sync>> {
         //Setting execution config
         let no_of_iterations = self.config.iterations;
         let thread_delay = self.config.rampup_time * 1000 / self.config.thread_count;
         let think_time = self.config.think_time;
+        let pacing = self.config.pacing;
         let continue_on_error = self.config.continue_on_error;
         let execution_time = self.config.execution_time;
         let thread_count = self.config.thread_count;
+        let retry = self.config.retry.clone();
 
         //set up data
         let data_provider = DataProvider::new(&self.config.data_file).await;
@@ -76,10 +83,13 @@ impl Bombardier {
         //Set up client and requests
         let client = Arc::new(http::HttpClient::new(&self.config).await?);
         let requests = Arc::new(self.requests.to_owned());
-       
-        //Initiate Stats sender
-        let stats_sender_arc = Arc::new(stats_sender.clone());
 
+        //Compile request scripts
+        let script_engine = Arc::new(ScriptEngine::new(&self.requests)?);
+
+        //Set up oauth2 token provider
+        let token_provider = self.config.oauth2.as_ref().map(|oauth2| Arc::new(TokenProvider::new(oauth2)));
+       
         //Initialize request cache
         let reqwest_cache = Arc::new(Mutex::new(HashMap::default()));
         
@@ -87,6 +97,7 @@ impl Bombardier {
         let start_time = Utc::now();
         
         let threads_running = Arc::new(AtomicU16::new(0));
+        let node_id = Arc::new(self.config.node_id.clone());
         
         for thread_cnt in 0..thread_count {
             info!("Starting thread: {}", thread_cnt+1);
@@ -94,16 +105,24 @@ impl Bombardier {
 
             let requests = requests.clone();
             let client = client.clone();
+            let script_engine = script_engine.clone();
+            let retry = retry.clone();
+            let token_provider = token_provider.clone();
+            let token_slot = token_provider.as_ref().map(|provider| provider.get_slot());
 
             let mut env_map = self.env_map.clone(); //every thread will mutate this map as per runtime values
             let data_provider = data_provider_arc.clone();
-            let stats_sender = stats_sender_arc.clone();
+            let stats_sender = stats_sender.clone();
             let reqwest_cache = reqwest_cache.clone();
             let threads_running_clone = threads_running.clone();
+            let node_id = node_id.clone();
 
             let mut thread_iteration = 0;
 
             let handle = spawn(async move {
+                //With pacing, every request has an intended send time irrespective of when the previous response was received
+                let mut next_send_time = time::Instant::now();
+
                 loop {
                     if no_of_iterations > 0 { //Iteration Based execution
                         if thread_iteration >= no_of_iterations { 
@@ -124,56 +143,136 @@ impl Bombardier {
                     let mut vec_stats = Vec::with_capacity(requests.len());
                     
                     //looping thru requests
-                    for request in requests.iter() {
-                        let reqwest = match process_request(client.as_ref(), request, &env_map, reqwest_cache.clone()).await {
-                            Ok(reqwest) => Some(reqwest),
-                            Err(err) => {
-                                error!("Error occured while processing request {} : {}", &request.name, err);
-                                None
-                            }
-                        };
+                    'requests: for request in requests.iter() {
+                        let mut script_attempt = 0;
 
-                        if reqwest.is_none() {
-                            if continue_on_error {
-                                continue;
-                            } else {
-                                break;
-                            }
-                        }
+                        loop {
+                            script_attempt += 1;
 
-                        match client.execute(reqwest.unwrap()).await { //can safely unwrap as none is checked
-                            Ok((response, latency)) => {
-                                let status_code = response.status().as_u16();
+                            let reqwest = match process_request(client.as_ref(), request, &mut env_map, reqwest_cache.clone(), &script_engine).await {
+                                //Requests with their own auth block are not authorized with oauth2 token
+                                Ok(reqwest) => match (token_provider.as_ref(), token_slot.as_ref(), request.auth.is_none()) {
+                                    (Some(provider), Some(slot), true) => provider.authorize(reqwest, client.as_ref(), slot, &env_map,
+                                        &mut vec_stats, threads_running_clone.load(Ordering::SeqCst)).await,
+                                    _ => Ok(reqwest)
+                                },
+                                Err(err) => Err(err)
+                            };
 
-                                match postprocessor::process(response, &request.extractors, &mut env_map).await { //process response and update env_map
-                                    Err(err) => error!("Error occurred while post processing response for request {} : {}", &request.name, err),
-                                    Ok(()) => ()
+                            let reqwest = match reqwest {
+                                Ok(reqwest) => reqwest,
+                                Err(err) => {
+                                    error!("Error occured while processing request {} : {}", &request.name, err);
+                                    if continue_on_error {
+                                        continue 'requests;
+                                    } else {
+                                        break 'requests;
+                                    }
                                 }
+                            };
 
-                                let new_stats = stats::Stats::new(&request.name, status_code, latency, threads_running_clone.load(Ordering::SeqCst));
-                                vec_stats.push(new_stats); //Add stats to vector
+                            //Final request is made available to post response script
+                            let request_map = match script_engine.has_post_response_script(&request.id) {
+                                true => Some(script::reqwest_to_map(&request.name, &reqwest)),
+                                false => None
+                            };
 
-                                if status_code > 399 { //check status
-                                    info!("Request {} failed with status {}", &request.name, status_code);
-                                    if !continue_on_error { 
-                                        warn!("Skipping rest of the iteration as continueOnError is set to false");
-                                        break;
+                            let context = StatsContext {
+                                request,
+                                thread_id: thread_cnt + 1,
+                                iteration: thread_iteration,
+                                method: reqwest.method().to_string(),
+                                url: reqwest.url().to_string(),
+                                node_id: &node_id
+                            };
+
+                            //Request level retry policy overrides the global one
+                            let execution = client.execute_with_retry(reqwest, request.retry.as_ref().or(retry.as_ref()), |execution| {
+                                vec_stats.push(get_stats(&context, execution, threads_running_clone.load(Ordering::SeqCst)));
+                            }).await;
+
+                            let mut new_stats = get_stats(&context, &execution, threads_running_clone.load(Ordering::SeqCst));
+                            let bytes_received = execution.bytes_received.clone();
+
+                            match execution.result {
+                                Ok(response) => {
+                                    let status_code = response.status().as_u16();
+                                    let headers = response.headers().clone();
+
+                                    //Body is read here so that its download time is part of latency
+                                    let (body, _, download) = http::read_body(response).await;
+                                    new_stats.download = download;
+                                    new_stats.latency += download;
+                                    new_stats.bytes_received = bytes_received.get();
+                                    let latency = new_stats.latency;
+
+                                    if let Err(err) = postprocessor::process(&headers, &body, &request.extractors, &mut env_map) { //process response and update env_map
+                                        error!("Error occurred while post processing response for request {} : {}", &request.name, err)
+                                    }
+
+                                    if let Some(request_map) = request_map {
+                                        let response = script::ScriptResponse { status: status_code, headers: &headers, body: &body, latency: latency / 1000 };
+                                        match script_engine.post_response(&request.id, &request_map, response, &mut env_map) {
+                                            Ok(true) if script_attempt <= request.scripts.max_retries => {
+                                                info!("Retrying request {} as requested by postResponse script, attempt {}", &request.name, script_attempt + 1);
+                                                vec_stats.push(new_stats);
+                                                time::sleep(time::Duration::from_millis(think_time as u64)).await;
+                                                continue;
+                                            },
+                                            Ok(true) => warn!("Request {} will not be retried as maxRetries {} is exhausted", &request.name, request.scripts.max_retries),
+                                            Ok(false) => (),
+                                            Err(err) => { //failing script is an assertion failure for the request
+                                                error!("{}", err);
+                                                new_stats.error_kind = Some(ErrorKind::Assertion);
+                                                new_stats.error_message = err.to_string();
+                                            }
+                                        }
+                                    }
+
+                                    let is_failed = status_code > 399 || new_stats.error_kind.is_some();
+                                    vec_stats.push(new_stats); //Add stats to vector
+
+                                    if is_failed { //check status
+                                        info!("Request {} failed with status {}", &request.name, status_code);
+                                        if !continue_on_error { 
+                                            warn!("Skipping rest of the iteration as continueOnError is set to false");
+                                            break 'requests;
+                                        }
+                                    }  
+                                },
+                                Err(err) => {
+                                    error!("Error occured while executing request {} : {}", &request.name, err);
+                                    vec_stats.push(new_stats);
+                                    if !continue_on_error {
+                                        warn!("Skipping rest of the iteration as continue on error is set to false");
+                                        break 'requests;
                                     }
-                                }  
-                            },
-                            Err(err) => {
-                                error!("Error occured while executing request {} : {}", &request.name, err);
-                                if !continue_on_error {
-                                    warn!("Skipping rest of the iteration as continue on error is set to false");
-                                    break;
                                 }
                             }
+
+                            break;
                         }
 
-                        time::sleep(time::Duration::from_millis(think_time as u64)).await; //wait per request delay
+                        if pacing > 0 { //wait till the next intended send time, no wait if thread is behind schedule
+                            next_send_time += time::Duration::from_millis(pacing);
+                            time::sleep_until(next_send_time).await;
+                        } else {
+                            time::sleep(time::Duration::from_millis(think_time as u64)).await; //wait per request delay
+                        }
                     };
+
+                    //Expected interval is used to correct the latencies for coordinated omission while aggregating
+                    if pacing > 0 {
+                        vec_stats.iter_mut()
+                            .filter(|s| !s.is_token_request())
+                            .for_each(|s| s.expected_interval = pacing * 1000);
+                    }
                     
-                    stats_sender.try_send(vec_stats).unwrap();
+                    //Waits while the stats consumer is behind, so that stats do not pile up in memory
+                    if stats_sender.send(vec_stats).await.is_err() {
+                        error!("Stats consumer has stopped, stats of thread {} are not recorded", thread_cnt + 1);
+                        break;
+                    }
                 }
             });
 
@@ -188,11 +287,22 @@ impl Bombardier {
     }
 }
 
-async fn process_request(http_client: &HttpClient, request: &Request, env_map: &HashMap<String, String>, cache: Arc<Mutex<HashMap<uuid::Uuid, Reqwest>>>) 
+async fn process_request(http_client: &HttpClient, request: &Request, env_map: &mut HashMap<String, String>, 
+    cache: Arc<Mutex<HashMap<uuid::Uuid, Reqwest>>>, script_engine: &ScriptEngine) 
 -> Result<Reqwest, Box<dyn Error + Send + Sync>> {
+    if script_engine.has_pre_request_script(&request.id) {
+        //Requests with pre request script are never cached as script can change them on every execution
+        let mut processed_request = preprocessor::process(request, env_map);
+        script_engine.pre_request(&request.id, &mut processed_request, env_map)?;
+        return converter::convert_request(http_client, &processed_request).await
+    }
+
     if request.requires_preprocessing {
         let processed_request = preprocessor::process(request, env_map); 
         return converter::convert_request(http_client, &processed_request).await
+    } else if request.auth.as_ref().is_some_and(|auth| auth.is_dynamic()) {
+        //Signatures with timestamp or nonce are computed on every execution, so these are not cached
+        return converter::convert_request(http_client, request).await
     } else {
         //Search the request in cache, if found return
         let reqwest = get_reqwest_from_cache(cache.clone(), &request.id);
@@ -218,6 +328,38 @@ async fn process_request(http_client: &HttpClient, request: &Request, env_map: &
     }
 }
 
+//Where and how a request was sent, recorded in the stats of all its executions
+struct StatsContext<'a> {
+    request: &'a Request,
+    thread_id: u32,
+    iteration: u64,
+    method: String,
+    url: String,
+    node_id: &'a str
+}
+
+//Stats of an execution, errors without response are recorded with status 0 and the kind and message of error.
+//Body download time and bytes are added once the body is read
+fn get_stats(context: &StatsContext, execution: &Execution, thread_count: u16) -> stats::Stats {
+    let mut stats = stats::Stats::new(&context.request.name, execution.status(), execution.latency, thread_count);
+    stats.attempt = execution.attempt;
+    stats.error_kind = execution.error_kind();
+    stats.error_message = execution.result.as_ref().err().map(|err| err.to_string()).unwrap_or_default();
+    stats.ttfb = execution.latency;
+    stats.dns = execution.timings.dns;
+    stats.connect = execution.timings.connect;
+    stats.tls = execution.timings.tls;
+    stats.bytes_sent = execution.bytes_sent;
+    stats.bytes_received = execution.bytes_received.get();
+    stats.scenario = context.request.scenario.clone();
+    stats.thread_id = context.thread_id;
+    stats.iteration = context.iteration;
+    stats.method = context.method.clone();
+    stats.url = context.url.clone();
+    stats.node_id = context.node_id.to_owned();
+    stats
+}
+
 fn get_reqwest_from_cache(cache: Arc<Mutex<HashMap<uuid::Uuid, Reqwest>>>, id: &uuid::Uuid) -> Result<Reqwest, Box<dyn Error + Send + Sync>>{
     let cache_guard = cache.lock();
     if let Some(reqwest) = cache_guard.get(id) {
@@ -1,12 +1,33 @@
 use clap::{Arg, App as ClapApp, ArgMatches, SubCommand};
 use log::error;
 
+use crate::report::{aggregator::GroupBy, run, timeline};
+
 //File Args
 pub const CONFIG_FILE_ARG_NAME: &str = "config yml file";
 pub const SCENARIOS_FILE_ARG_NAME: &str = "scenarios yml file";
 pub const ENVIRONMENT_FILE_ARG_NAME: &str = "environments yml file";
 pub const DATA_FILE_ARG_NAME: &str = "data csv file";
 pub const REPORT_FILE_ARG_NAME: &str = "report file";
+pub const OUTPUT_FILE_ARG_NAME: &str = "output file";
+pub const THRESHOLDS_FILE_ARG_NAME: &str = "thresholds yml file";
+pub const BASELINE_FILE_ARG_NAME: &str = "baseline report file";
+pub const CURRENT_FILE_ARG_NAME: &str = "current report file";
+
+//Bombard
+pub const REPORT_FORMAT_ARG_NAME: &str = "report-format";
+pub const REPORT_FILE_FORMATS: [&str; 3] = ["csv", "binary", "binary-zstd"];
+pub const TAG_ARG_NAME: &str = "tag";
+
+//Report
+pub const FORMAT_ARG_NAME: &str = "format";
+pub const INTERVAL_ARG_NAME: &str = "interval";
+pub const GROUP_BY_ARG_NAME: &str = "group-by";
+pub const REPORT_FORMATS: [&str; 5] = ["text", "html", "json", "junit", "markdown"];
+
+//Compare
+pub const TOLERANCE_ARG_NAME: &str = "tolerance";
+pub const DEFAULT_TOLERANCE: &str = "10";
 
 //Hub
 pub const HUB_ADDRESS_ARG_NAME: &str = "hub adress as <ip>::<port>";
@@ -14,6 +35,11 @@ pub const HUB_ADDRESS_ARG_NAME: &str = "hub adress as <ip>::<port>";
 //Ports
 pub const SERVER_PORT_ARG_NAME: &str = "server port";
 pub const SOCKET_PORT_ARG_NAME: &str = "websocket port";
+pub const METRICS_PORT_ARG_NAME: &str = "metrics-port";
+
+//Flags
+pub const QUIET_ARG_NAME: &str = "quiet";
+pub const FAIL_ON_REGRESSION_ARG_NAME: &str = "fail-on-regression";
 
 pub const DEFAULT_REPORT_FILE: &str = "report.csv";
 
@@ -48,6 +74,20 @@ impl<'a> App<'a> {
         }
     }
 
+    pub fn arg_values(&self, arg: &str) -> Vec<String> {
+        match self.arg_matches.subcommand().1.and_then(|x| x.values_of(arg)) {
+            Some(values) => values.map(String::from).collect(),
+            None => vec![]
+        }
+    }
+
+    pub fn arg_is_present(&self, arg: &str) -> bool {
+        match self.arg_matches.subcommand().1 {
+            Some(x) => x.is_present(arg),
+            None => false
+        }
+    }
+
     pub fn arg_value_as_u16(&self, arg: &str) -> u16 {
         if let Some(arg_matches) = self.arg_matches.subcommand().1 {
             if let Some(value) = arg_matches.value_of(arg) {
@@ -86,19 +126,108 @@ fn create_cmd_app<'a, 'b>() -> ClapApp<'a, 'b> {
                     .validator(is_csv)
                     .display_order(3),
 
-                    get_arg(REPORT_FILE_ARG_NAME, "r", false, "report csv file")
-                    .validator(is_csv)
+                    get_arg(REPORT_FILE_ARG_NAME, "r", false, "report .csv or .bin file, defaults to report.<csv|bin>")
+                    .validator(is_report_file)
                     .display_order(4),
+
+                    Arg::with_name(REPORT_FORMAT_ARG_NAME)
+                    .long(REPORT_FORMAT_ARG_NAME)
+                    .takes_value(true)
+                    .help("format of the report file, binary formats are smaller and faster to write")
+                    .possible_values(&REPORT_FILE_FORMATS)
+                    .default_value("csv")
+                    .display_order(5),
+
+                    Arg::with_name(QUIET_ARG_NAME)
+                    .short("q")
+                    .long(QUIET_ARG_NAME)
+                    .help("Only logs are written to console, live dashboard is not shown")
+                    .display_order(6),
+
+                    get_arg(METRICS_PORT_ARG_NAME, "m", false, "port to serve prometheus metrics on /metrics while bombarding")
+                    .long(METRICS_PORT_ARG_NAME)
+                    .validator(is_u16)
+                    .display_order(7),
+
+                    get_arg(TAG_ARG_NAME, "t", false, "tag of the run as key=value, written to the run file and databases. Can be repeated")
+                    .long(TAG_ARG_NAME)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .validator(is_tag)
+                    .display_order(8),
                 ]))
 
         .subcommand(SubCommand::with_name("report")
                 .about("Generates the report from report file")
-                .arg(get_arg(REPORT_FILE_ARG_NAME, "r", true, "report file")
-                .validator(is_csv)))
+                .args(&[
+                    get_arg(REPORT_FILE_ARG_NAME, "r", true, "report file")
+                    .validator(is_report_file),
+
+                    get_arg(FORMAT_ARG_NAME, "f", false, "report format")
+                    .long(FORMAT_ARG_NAME)
+                    .possible_values(&REPORT_FORMATS)
+                    .default_value("text"),
+
+                    get_arg(OUTPUT_FILE_ARG_NAME, "o", false, "output file, defaults to report.<format>"),
+
+                    get_arg(THRESHOLDS_FILE_ARG_NAME, "t", false, "thresholds yml file, breaches are reported as failures in junit and markdown reports")
+                    .long("thresholds")
+                    .validator(is_yml),
+
+                    get_arg(INTERVAL_ARG_NAME, "i", false, "interval like 10s or 1m, stats are also reported for every interval")
+                    .long(INTERVAL_ARG_NAME)
+                    .validator(|interval| timeline::parse_interval(&interval).map(|_| ())),
+
+                    get_arg(GROUP_BY_ARG_NAME, "g", false, "comma separated fields to group the stats by instead of request name, like scenario,node")
+                    .long(GROUP_BY_ARG_NAME)
+                    .validator(|group_by| GroupBy::parse(&group_by).map(|_| ()))
+                ]))
+
+        .subcommand(SubCommand::with_name("compare")
+                .about("Compares the report file of a run with the report file of a baseline run")
+                .args(&[
+                    Arg::with_name(BASELINE_FILE_ARG_NAME)
+                    .index(1)
+                    .required(true)
+                    .help("baseline report file")
+                    .validator(is_report_file),
+
+                    Arg::with_name(CURRENT_FILE_ARG_NAME)
+                    .index(2)
+                    .required(true)
+                    .help("current report file")
+                    .validator(is_report_file),
+
+                    get_arg(TOLERANCE_ARG_NAME, "t", false, "change in % beyond which a metric worse than baseline is a regression")
+                    .long(TOLERANCE_ARG_NAME)
+                    .default_value(DEFAULT_TOLERANCE)
+                    .validator(is_percentage),
+
+                    Arg::with_name(FAIL_ON_REGRESSION_ARG_NAME)
+                    .long(FAIL_ON_REGRESSION_ARG_NAME)
+                    .help("Exits with non-zero code if any metric has regressed")
+                ]))
+
+        .subcommand(SubCommand::with_name("convert")
+                .about("Converts a binary report file to csv")
+                .args(&[
+                    get_arg(REPORT_FILE_ARG_NAME, "r", true, "binary report file")
+                    .validator(is_report_file),
+
+                    get_arg(OUTPUT_FILE_ARG_NAME, "o", false, "output csv file")
+                    .validator(is_csv)
+                    .default_value(DEFAULT_REPORT_FILE)
+                ]))
 
         .subcommand(SubCommand::with_name("node")
                 .about("Starts bombardier as a node")
-                .arg(get_arg(HUB_ADDRESS_ARG_NAME, "h", true, "hub address <ip>:<port>")))
+                .args(&[
+                    get_arg(HUB_ADDRESS_ARG_NAME, "h", true, "hub address <ip>:<port>"),
+
+                    get_arg(METRICS_PORT_ARG_NAME, "m", false, "port to serve prometheus metrics on /metrics")
+                    .long(METRICS_PORT_ARG_NAME)
+                    .validator(is_u16)
+                ]))
 
         .subcommand(SubCommand::with_name("hub")
                 .about("Starts bombardier as a hub server")
@@ -126,6 +255,18 @@ fn is_csv(file_path: String)-> Result<(),String> {
     }
 }
 
+//Report files can be csv or binary
+fn is_report_file(file_path: String)-> Result<(),String> {
+    match file_path.ends_with(".csv") || file_path.ends_with(".bin") {
+        true => Ok(()),
+        false => Err(String::from("Should be a .csv or .bin file"))
+    }
+}
+
+fn is_tag(value: String) -> Result<(),String> {
+    run::parse_tag(&value).map(|_| ())
+}
+
 fn is_u16(value: String)-> Result<(),String> {
     match value.parse::<u16>() {
         Ok(_) => Ok(()),
@@ -133,6 +274,13 @@ fn is_u16(value: String)-> Result<(),String> {
     }
 }
 
+fn is_percentage(value: String)-> Result<(),String> {
+    match value.parse::<f64>() {
+        Ok(x) if x >= 0.0 => Ok(()),
+        _ => Err(String::from("Should be a non negative number"))
+    }
+}
+
 fn get_arg(name: &'static str, short:&str, required: bool, help: &'static str) -> Arg<'static, 'static> {
     Arg::with_name(name)
         .short(short)
@@ -177,12 +325,27 @@ mod tests {
         assert_eq!(is_csv(String::from("/some/file/path/file.yml")), Err(String::from("Should be a .csv file")));
     }
 
+    #[test]
+    fn test_is_report_file() {
+        assert_eq!(is_report_file(String::from("/some/file/path/report.csv")), Ok(()));
+        assert_eq!(is_report_file(String::from("/some/file/path/report.bin")), Ok(()));
+        assert_eq!(is_report_file(String::from("/some/file/path/report.yml")), Err(String::from("Should be a .csv or .bin file")));
+    }
+
     #[test]
     fn test_is_u16() {
         assert_eq!(is_u16(String::from("0")), Ok(()));
         assert_eq!(is_u16(String::from("abc")), Err(String::from("Should be an integer")));
     }
 
+    #[test]
+    fn test_is_percentage() {
+        assert_eq!(is_percentage(String::from("10")), Ok(()));
+        assert_eq!(is_percentage(String::from("2.5")), Ok(()));
+        assert_eq!(is_percentage(String::from("-1")), Err(String::from("Should be a non negative number")));
+        assert_eq!(is_percentage(String::from("abc")), Err(String::from("Should be a non negative number")));
+    }
+
     #[test]
     fn test_arg_value_as_str() {
         let command = "bombardier";
@@ -204,6 +367,20 @@ mod tests {
         assert_eq!(app.arg_value_as_str(CONFIG_FILE_ARG_NAME), String::from(flag_value));
     }
 
+    #[test]
+    fn test_arg_is_present() {
+        let get_app = |args: Vec<&str>| App {
+            arg_matches: ClapApp::new("Bombardier")
+                .subcommand(SubCommand::with_name("bombard")
+                    .arg(Arg::with_name(QUIET_ARG_NAME).short("q").long(QUIET_ARG_NAME)))
+                    .get_matches_from(args)
+        };
+
+        assert!(get_app(vec!["bombardier", "bombard", "--quiet"]).arg_is_present(QUIET_ARG_NAME));
+        assert!(get_app(vec!["bombardier", "bombard", "-q"]).arg_is_present(QUIET_ARG_NAME));
+        assert!(!get_app(vec!["bombardier", "bombard"]).arg_is_present(QUIET_ARG_NAME));
+    }
+
     #[test]
     fn test_arg_value_as_u16() {
         let command = "bombardier";
@@ -15,9 +15,11 @@ use tokio::fs;
 
 use std::{
     str::FromStr,
+    time::Duration,
     error::Error as StdError
 };
 
+use crate::auth;
 use crate::model::{Request, Body, FormDataFieldType};
 use crate::protocol::http::HttpClient;
 
@@ -36,6 +38,11 @@ pub async fn convert_request(http_client: &HttpClient, request: &Request) -> Res
         .get_client()
         .request(method, request.url.to_owned()).headers(headers);
 
+    //Request timeout overrides the one set on client
+    if request.request_timeout > 0 {
+        builder = builder.timeout(Duration::from_millis(request.request_timeout));
+    }
+
     //Body
     let body = &request.body;
 
@@ -47,7 +54,14 @@ pub async fn convert_request(http_client: &HttpClient, request: &Request) -> Res
         builder = add_url_encoded_data(builder, body).await;
     } 
 
-    Ok(builder.build()?)
+    let mut reqwest = builder.build()?;
+
+    //Auth is applied last as signatures need the final url, headers and body
+    if let Some(auth) = &request.auth {
+        auth::apply(auth, &mut reqwest)?;
+    }
+
+    Ok(reqwest)
 }
 
 async fn get_header_map_from_request(request: &Request) 
@@ -1,14 +1,11 @@
 use csv_async::{
     AsyncReader, 
     AsyncReaderBuilder, 
-    Error, 
     StringRecord, 
     Trim
 };
 
-use futures::StreamExt;
 use log::{info, error};
-use serde::de::DeserializeOwned;
 use rustc_hash::FxHashMap as HashMap;
 use tokio::{
     io::AsyncRead,
@@ -85,8 +82,12 @@ impl DataProvider {
         }
     }
 
-    pub async fn get_records_as<T>(&mut self) -> Result<Vec<T>, Error> 
-    where T: DeserializeOwned {       
+    //Loads every record in memory, report files are aggregated in chunks by report::aggregator instead
+    #[cfg(test)]
+    pub async fn get_records_as<T>(&mut self) -> Result<Vec<T>, csv_async::Error> 
+    where T: serde::de::DeserializeOwned {       
+        use futures::StreamExt;
+
         let file = File::open(&self.file_path).await.unwrap();
         self.reader = create_reader(file).await;
         let record_stream = self.reader.records();
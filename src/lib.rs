@@ -1,6 +1,7 @@
 pub mod cmd;
 pub mod logger;
 
+mod auth;
 mod bombardier;
 mod converter;
 mod data;
@@ -8,31 +9,34 @@ mod model;
 mod parse;
 mod protocol;
 mod report;
+mod script;
 mod server;
 mod storage;
 
 use cmd::App;
 use log::{info, error};
-use std::sync::Arc;
+use std::{io::{self, IsTerminal}, sync::Arc};
 use tokio::{fs,sync::Mutex};
 
 use crate::{
     bombardier::Bombardier, 
     model::Config,
     parse::parser, 
-    report::stats
+    report::{dashboard::Dashboard, metrics::{self, Metrics}, run, stats}
 };
 
 pub async fn process_subcommand(app: App<'_>) {
     let subcommand = app.subcommand();
     if subcommand.is_empty() {
-        error!("No subcommand found. Should either be 'bombard', 'report', 'hub' or 'node'");
+        error!("No subcommand found. Should either be 'bombard', 'report', 'compare', 'convert', 'hub' or 'node'");
         return;
     }
 
     match subcommand.as_str() {
         "bombard" => bombard(app).await,
         "report" => report(app).await,
+        "compare" => compare(app).await,
+        "convert" => convert(app).await,
         "node" => node(app).await,
         "hub" => hub(app).await,
         _ => error!("Invalid command")
@@ -64,12 +68,39 @@ async fn bombard(app: App<'_>) {
     //Get data file path
     config.data_file = app.arg_value_as_str(cmd::DATA_FILE_ARG_NAME);
     config.report_file = app.arg_value_as_str(cmd::REPORT_FILE_ARG_NAME);
+    config.report_format = app.arg_value_as_str(cmd::REPORT_FORMAT_ARG_NAME);
+    config.scenarios_file = scenarios_file_path;
+
+    let tags = match run::parse_tags(&app.arg_values(cmd::TAG_ARG_NAME)) {
+        Ok(tags) => tags,
+        Err(err) => {
+            error!("{}", err);
+            return
+        }
+    };
 
     info!("Prepare bombardier");
-    let bombardier = Bombardier::new(config, env_content, scenarios_content).unwrap();
+    let bombardier = match Bombardier::new(config, env_content, scenarios_content, tags) {
+        Ok(b) => b,
+        Err(err) => {
+            error!("Error while preparing bombardier {}", err);
+            return
+        }
+    };
     
+    //Live dashboard is shown unless quiet mode is requested or output is not a terminal
+    let dashboard = match is_dashboard_enabled(&app) {
+        true => Some(Dashboard::new(&bombardier.config)),
+        false => None
+    };
+
+    let metrics = match get_metrics(&app) {
+        Ok(metrics) => metrics,
+        Err(_) => return
+    };
+
     let (stats_consumer, sender) = 
-    match stats::StatsConsumer::new(&bombardier.config, Arc::new(Mutex::new(None))).await {
+    match stats::StatsConsumer::new(&bombardier.config, &bombardier.run, Arc::new(Mutex::new(None)), dashboard, metrics).await {
         Ok(consumer) => consumer,
         Err(err) => {
             error!("Error while initializing stats consumer {}", err);
@@ -79,29 +110,94 @@ async fn bombard(app: App<'_>) {
 
     let stats_receiver_handle = stats_consumer.consume().await;
 
-    info!("Bombarding !!!");
+    info!("Bombarding !!! Run id is {}", bombardier.run.id);
     match bombardier.bombard(sender).await {
         Err(err) => error!("Bombarding failed : {}", err),
         Ok(()) => info!("Bombarding Complete. Run report command to get details")
     }   
 
-    stats_receiver_handle.await.unwrap();
+    let histograms = stats_receiver_handle.await.unwrap();
+    report::print_latency_table(&histograms);
+}
+
+//Metrics are served only when metrics port is provided
+fn get_metrics(app: &App) -> Result<Option<Arc<Metrics>>, Box<dyn std::error::Error>> {
+    match app.arg_value_as_u16(cmd::METRICS_PORT_ARG_NAME) {
+        0 => Ok(None),
+        port => {
+            let metrics = Arc::new(Metrics::new());
+            metrics::serve(port, metrics.clone())?;
+            Ok(Some(metrics))
+        }
+    }
+}
+
+pub fn is_dashboard_enabled(app: &App) -> bool {
+    app.subcommand() == "bombard" && !app.arg_is_present(cmd::QUIET_ARG_NAME) && io::stdout().is_terminal()
 }
 
 async fn report(app: App<'_>) {
     let report_file = app.arg_value_as_str(cmd::REPORT_FILE_ARG_NAME);
+    let format = app.arg_value_as_str(cmd::FORMAT_ARG_NAME);
+    let output_file = app.arg_value_as_str(cmd::OUTPUT_FILE_ARG_NAME);
+    let thresholds_file = app.arg_value_as_str(cmd::THRESHOLDS_FILE_ARG_NAME);
+    let interval = app.arg_value_as_str(cmd::INTERVAL_ARG_NAME);
+    let group_by = app.arg_value_as_str(cmd::GROUP_BY_ARG_NAME);
 
     info!("Generating report");
-    if let Err(err) =  report::display(&report_file).await {
+    if let Err(err) =  report::generate(&report_file, &format, &output_file, &thresholds_file, &interval, &group_by).await {
         error!("Error while displaying reports : {}", err)
     }
 }
 
+async fn compare(app: App<'_>) {
+    let baseline_file = app.arg_value_as_str(cmd::BASELINE_FILE_ARG_NAME);
+    let current_file = app.arg_value_as_str(cmd::CURRENT_FILE_ARG_NAME);
+    //Tolerance is validated as a percentage by the parser
+    let tolerance = match app.arg_value_as_str(cmd::TOLERANCE_ARG_NAME).parse::<f64>() {
+        Ok(tolerance) => tolerance,
+        Err(err) => {
+            error!("Invalid tolerance : {}", err);
+            std::process::exit(1)
+        }
+    };
+
+    info!("Comparing {} with baseline {}", current_file, baseline_file);
+    match report::compare::compare_reports(&baseline_file, &current_file, tolerance).await {
+        Ok(true) if app.arg_is_present(cmd::FAIL_ON_REGRESSION_ARG_NAME) => {
+            error!("Regressions found beyond tolerance of {} %", tolerance);
+            std::process::exit(1)
+        },
+        Ok(_) => (),
+        Err(err) => {
+            error!("Error while comparing reports : {}", err);
+            std::process::exit(1)
+        }
+    }
+}
+
+async fn convert(app: App<'_>) {
+    let report_file = app.arg_value_as_str(cmd::REPORT_FILE_ARG_NAME);
+    let output_file = app.arg_value_as_str(cmd::OUTPUT_FILE_ARG_NAME);
+
+    info!("Converting {} to {}", report_file, output_file);
+    let (input, output) = (report_file.clone(), output_file.clone());
+    match tokio::task::spawn_blocking(move || report::binary::convert_to_csv(&input, &output)).await {
+        Ok(Ok(count)) => info!("Converted {} stats to {}", count, output_file),
+        Ok(Err(err)) => error!("Error while converting report file {} : {}", report_file, err),
+        Err(err) => error!("Error while converting report file {} : {}", report_file, err)
+    }
+}
+
 async fn node(app: App<'_>) {
     let hub_address = app.arg_value_as_str(cmd::HUB_ADDRESS_ARG_NAME);
+    let metrics = match get_metrics(&app) {
+        Ok(metrics) => metrics,
+        Err(_) => return
+    };
 
     info!("Starting bombardier as a node");
-    if let Err(err) =  server::node::start(hub_address).await {
+    if let Err(err) =  server::node::start(hub_address, metrics).await {
         error!("Error occured in the node : {}", err)
     }
 }
@@ -5,11 +5,16 @@ use flexi_logger::{
     opt_format
 };
 
-pub fn initiate(with_file: bool) {
+pub fn initiate(with_file: bool, with_console: bool) {
     let mut logger = Logger::try_with_env().unwrap().format(opt_format);
             
     if with_file {
-        logger = logger.log_to_file(FileSpec::default()).duplicate_to_stderr(Duplicate::Info).print_message();
+        let duplicate = match with_console {
+            true => Duplicate::Info,
+            false => Duplicate::None
+        };
+
+        logger = logger.log_to_file(FileSpec::default()).duplicate_to_stderr(duplicate).print_message();
     }
 
     logger.start().unwrap();
@@ -6,8 +6,10 @@ static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
 #[tokio::main]
 async fn main()  {
-    logger::initiate(true);
-
     let app = cmd::App::new();
+
+    //Logs are not written to console while the dashboard is shown
+    logger::initiate(true, !bombardier::is_dashboard_enabled(&app));
+
     bombardier::process_subcommand(app).await;
 }
\ No newline at end of file
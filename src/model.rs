@@ -1,6 +1,8 @@
 use serde::{Serialize, Deserialize, Deserializer, de::Error};
 use rustc_hash::FxHashMap as HashMap;
 
+use std::fmt;
+
 //Config is the model for execution configuration
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Config {
@@ -19,6 +21,9 @@ pub struct Config {
     #[serde(rename = "thinkTime")]
     pub think_time: u32,
 
+    #[serde(default)]
+    pub pacing: u64,
+
     #[serde(deserialize_with = "check_non_zero")]
     #[serde(rename = "rampUpTime")]
     #[serde(default = "default_to_one")]
@@ -32,12 +37,33 @@ pub struct Config {
     #[serde(rename = "continueOnError")]
     pub continue_on_error: bool,
 
+    #[serde(default = "default_connect_timeout")]
+    #[serde(rename = "connectTimeout")]
+    pub connect_timeout: u64,
+
+    #[serde(default = "default_request_timeout")]
+    #[serde(rename = "requestTimeout")]
+    pub request_timeout: u64,
+
     #[serde(default)]
     pub database: Database,
 
+    //Sinks stats are written to, report file and database are used when not configured
+    #[serde(default, deserialize_with = "deserialize_outputs")]
+    pub outputs: Vec<Output>,
+
     #[serde(default)]
     pub ssl: Ssl,
 
+    #[serde(default)]
+    pub oauth2: Option<OAuth2>,
+
+    #[serde(default)]
+    pub retry: Option<Retry>,
+
+    #[serde(default)]
+    pub stats: StatsSettings,
+
     #[serde(skip_deserializing)]
     #[serde(skip_serializing)]
     pub distributed: bool,
@@ -48,7 +74,25 @@ pub struct Config {
 
     #[serde(skip_deserializing)]
     #[serde(skip_serializing)]
-    pub report_file: String
+    pub scenarios_file: String,
+
+    #[serde(skip_deserializing)]
+    #[serde(skip_serializing)]
+    pub report_file: String,
+
+    #[serde(skip_deserializing)]
+    #[serde(skip_serializing)]
+    pub report_format: String,
+
+    //Set by the hub to the number of nodes the test is sent to, 0 when not distributed
+    #[serde(default)]
+    #[serde(rename = "nodeCount")]
+    pub node_count: usize,
+
+    //Set by the node before bombarding, so that stats of every node can be told apart
+    #[serde(skip_deserializing)]
+    #[serde(skip_serializing)]
+    pub node_id: String
 }
 
 fn check_non_zero <'de, D>(deserializer: D) -> Result<u32, D::Error> 
@@ -65,6 +109,14 @@ fn default_to_one() -> u32 {
     1
 }
 
+fn default_connect_timeout() -> u64 {
+    10000
+}
+
+fn default_request_timeout() -> u64 {
+    60000
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct Database {
     #[serde(rename = "type")]
@@ -82,6 +134,59 @@ pub struct Database {
 
     #[serde(default)]
     pub name: String,
+
+    //Organization, bucket and API token of InfluxDB v2, v1 api is used when org is not set
+    #[serde(default)]
+    pub org: String,
+
+    #[serde(default)]
+    pub bucket: String,
+
+    #[serde(default)]
+    pub token: String,
+}
+
+//Sink of stats with its settings. Type is one of csv, binary, binary-zstd, jsonl, stdout-summary or the type of a database,
+//settings of the database are used by database types
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Output {
+    //File written by csv, binary and jsonl outputs
+    #[serde(default)]
+    pub file: String,
+
+    #[serde(flatten)]
+    pub database: Database
+}
+
+impl Output {
+    pub fn new(output_type: &str) -> Output {
+        Output {
+            file: String::new(),
+            database: Database { db_type: output_type.to_owned(), ..Default::default() }
+        }
+    }
+
+    pub fn get_type(&self) -> String {
+        self.database.db_type.to_lowercase()
+    }
+}
+
+//Output can be just its type, like csv, or a map with its type and settings
+fn deserialize_outputs<'de, D>(deserializer: D) -> Result<Vec<Output>, D::Error> where D: Deserializer<'de> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TypeOrOutput {
+        Type(String),
+        Output(Output)
+    }
+
+    let outputs = Vec::<TypeOrOutput>::deserialize(deserializer)?;
+    Ok(outputs.into_iter()
+        .map(|output| match output {
+            TypeOrOutput::Type(output_type) => Output::new(&output_type),
+            TypeOrOutput::Output(output) => output
+        })
+        .collect())
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
@@ -105,6 +210,218 @@ pub struct Ssl {
     pub keystore_password: String,
 }
 
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct OAuth2 {
+    #[serde(rename = "tokenUrl")]
+    pub token_url: String,
+
+    #[serde(default)]
+    pub grant: OAuth2Grant,
+
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+
+    #[serde(default)]
+    #[serde(rename = "clientSecret")]
+    pub client_secret: String,
+
+    #[serde(default)]
+    pub username: String,
+
+    #[serde(default)]
+    pub password: String,
+
+    #[serde(default)]
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+
+    #[serde(default)]
+    pub scope: String,
+
+    #[serde(default)]
+    #[serde(rename = "basicAuth")]
+    pub basic_auth: bool,
+
+    #[serde(default)]
+    pub cache: TokenCache,
+
+    #[serde(default = "default_refresh_before")]
+    #[serde(rename = "refreshBefore")]
+    pub refresh_before: u64
+}
+
+fn default_refresh_before() -> u64 {
+    30
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub enum OAuth2Grant {
+    #[default]
+    #[serde(rename = "clientCredentials")]
+    ClientCredentials,
+
+    #[serde(rename = "password")]
+    Password,
+
+    #[serde(rename = "refreshToken")]
+    RefreshToken
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub enum TokenCache {
+    #[default]
+    #[serde(rename = "global")]
+    Global,
+
+    #[serde(rename = "thread")]
+    Thread
+}
+
+//Stats are written to the report, hub and database in batches of batch size, or whatever is collected in flush interval (in ms).
+//Every sink has a queue of queue size batches, a full queue of database drops the batch while a full queue of report or hub buffers it in memory
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct StatsSettings {
+    #[serde(default = "default_batch_size")]
+    #[serde(rename = "batchSize")]
+    pub batch_size: usize,
+
+    #[serde(default = "default_flush_interval")]
+    #[serde(rename = "flushInterval")]
+    pub flush_interval: u64,
+
+    #[serde(default = "default_queue_size")]
+    #[serde(rename = "queueSize")]
+    pub queue_size: usize
+}
+
+impl Default for StatsSettings {
+    fn default() -> Self {
+        StatsSettings {
+            batch_size: default_batch_size(),
+            flush_interval: default_flush_interval(),
+            queue_size: default_queue_size()
+        }
+    }
+}
+
+fn default_batch_size() -> usize {
+    100
+}
+
+fn default_flush_interval() -> u64 {
+    1000
+}
+
+fn default_queue_size() -> usize {
+    100
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Retry {
+    #[serde(default = "default_max_attempts")]
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: u32,
+
+    #[serde(default = "default_retry_status_codes")]
+    #[serde(rename = "statusCodes")]
+    pub status_codes: Vec<u16>,
+
+    #[serde(default = "default_retry_errors")]
+    pub errors: Vec<ErrorKind>,
+
+    #[serde(default)]
+    pub backoff: Backoff
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_retry_status_codes() -> Vec<u16> {
+    vec![502, 503, 504]
+}
+
+fn default_retry_errors() -> Vec<ErrorKind> {
+    vec![ErrorKind::Timeout, ErrorKind::Connect]
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Backoff {
+    #[serde(default)]
+    #[serde(rename = "type")]
+    pub backoff_type: BackoffType,
+
+    #[serde(default = "default_backoff_delay")]
+    pub delay: u64,
+
+    #[serde(default = "default_backoff_max_delay")]
+    #[serde(rename = "maxDelay")]
+    pub max_delay: u64,
+
+    #[serde(default)]
+    pub jitter: bool
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            backoff_type: BackoffType::default(),
+            delay: default_backoff_delay(),
+            max_delay: default_backoff_max_delay(),
+            jitter: false
+        }
+    }
+}
+
+fn default_backoff_delay() -> u64 {
+    100
+}
+
+fn default_backoff_max_delay() -> u64 {
+    10000
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub enum BackoffType {
+    #[default]
+    #[serde(rename = "fixed")]
+    Fixed,
+
+    #[serde(rename = "exponential")]
+    Exponential
+}
+
+//Kinds of errors occurring while executing a request. All apart from assertion are errors where no response is received
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ErrorKind {
+    #[serde(rename = "timeout")]
+    Timeout,
+
+    #[serde(rename = "connect")]
+    Connect,
+
+    #[serde(rename = "tls")]
+    Tls,
+
+    #[serde(rename = "assertion")]
+    Assertion,
+
+    #[serde(rename = "other")]
+    Other
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Timeout => write!(f, "timeout"),
+            ErrorKind::Connect => write!(f, "connect"),
+            ErrorKind::Tls => write!(f, "tls"),
+            ErrorKind::Assertion => write!(f, "assertion"),
+            ErrorKind::Other => write!(f, "other")
+        }
+    }
+}
+
 fn check_der_or_pem <'de, D>(deserializer: D) -> Result<String, D::Error> 
 where D: Deserializer<'de> {   
     let val = String::deserialize(deserializer)?;
@@ -140,6 +457,9 @@ pub struct Environment {
 pub struct Scenario {
     pub name: String,
 
+    #[serde(default)]
+    pub auth: Option<Auth>,
+
     #[serde(default)]
     pub requests: Vec<Request>,
 }
@@ -152,6 +472,10 @@ pub struct Request {
 
     pub name: String,
 
+    //Name of the scenario the request belongs to, set while parsing
+    #[serde(default)]
+    pub scenario: String,
+
     pub url: String,
 
     pub method: String,
@@ -165,10 +489,29 @@ pub struct Request {
     #[serde(default)]
     pub extractors: Vec<Extractor>,
 
+    #[serde(default)]
+    pub scripts: Scripts,
+
+    #[serde(default)]
+    pub auth: Option<Auth>,
+
+    #[serde(default)]
+    pub retry: Option<Retry>,
+
+    #[serde(default)]
+    #[serde(rename = "requestTimeout")]
+    pub request_timeout: u64,
+
     #[serde(default)]
     pub requires_preprocessing: bool
 }
 
+impl Request {
+    pub fn has_scripts(&self) -> bool {
+        !(self.scripts.pre_request.trim().is_empty() && self.scripts.post_response.trim().is_empty())
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct Body {
     #[serde(default)]
@@ -210,6 +553,164 @@ impl Default for FormDataFieldType {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Scripts {
+    #[serde(default)]
+    #[serde(rename = "preRequest")]
+    pub pre_request: String,
+
+    #[serde(default)]
+    #[serde(rename = "postResponse")]
+    pub post_response: String,
+
+    #[serde(default = "default_script_retries")]
+    #[serde(rename = "maxRetries")]
+    pub max_retries: u32,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Scripts {
+            pre_request: String::new(),
+            post_response: String::new(),
+            max_retries: default_script_retries()
+        }
+    }
+}
+
+fn default_script_retries() -> u32 {
+    3
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum Auth {
+    Basic(BasicAuth),
+    Bearer(BearerAuth),
+    Hmac(HmacAuth),
+    AwsSigV4(AwsSigV4Auth),
+    OAuth1(OAuth1Auth)
+}
+
+impl Auth {
+    //Signatures having timestamp or nonce have to be computed on every execution
+    pub fn is_dynamic(&self) -> bool {
+        !matches!(self, Auth::Basic(_) | Auth::Bearer(_))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BasicAuth {
+    pub username: String,
+
+    #[serde(default)]
+    pub password: String
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BearerAuth {
+    pub token: String
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HmacAuth {
+    pub key: String,
+
+    #[serde(default = "default_auth_header")]
+    pub header: String,
+
+    #[serde(default)]
+    pub prefix: String,
+
+    #[serde(default = "default_canonical_string")]
+    #[serde(rename = "canonicalString")]
+    pub canonical_string: String,
+
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    #[serde(default)]
+    #[serde(rename = "timestampHeader")]
+    pub timestamp_header: String,
+
+    #[serde(default)]
+    #[serde(rename = "nonceHeader")]
+    pub nonce_header: String
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AwsSigV4Auth {
+    #[serde(rename = "accessKey")]
+    pub access_key: String,
+
+    #[serde(rename = "secretKey")]
+    pub secret_key: String,
+
+    #[serde(default)]
+    #[serde(rename = "sessionToken")]
+    pub session_token: String,
+
+    pub region: String,
+
+    pub service: String,
+
+    //Headers to sign besides host, content-type and x-amz-* headers
+    #[serde(default)]
+    #[serde(rename = "signedHeaders")]
+    pub signed_headers: Vec<String>
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct OAuth1Auth {
+    #[serde(rename = "consumerKey")]
+    pub consumer_key: String,
+
+    #[serde(rename = "consumerSecret")]
+    pub consumer_secret: String,
+
+    #[serde(default)]
+    pub token: String,
+
+    #[serde(default)]
+    #[serde(rename = "tokenSecret")]
+    pub token_secret: String,
+
+    #[serde(default)]
+    #[serde(rename = "signatureMethod")]
+    pub signature_method: OAuth1SignatureMethod,
+
+    #[serde(default)]
+    pub realm: String
+}
+
+fn default_auth_header() -> String {
+    String::from("authorization")
+}
+
+fn default_canonical_string() -> String {
+    String::from("{method}\n{path}\n{query}\n{timestamp}\n{body}")
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub enum Encoding {
+    #[default]
+    Hex,
+    Base64
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub enum OAuth1SignatureMethod {
+    #[default]
+    #[serde(rename = "HMAC-SHA1")]
+    HmacSha1,
+
+    #[serde(rename = "HMAC-SHA256")]
+    HmacSha256,
+
+    #[serde(rename = "PLAINTEXT")]
+    Plaintext
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct Extractor {
     #[serde(default)]
@@ -253,4 +754,49 @@ impl Default for ExtractorType {
 pub struct Env {
     pub key: String,
     pub value: String
-}
\ No newline at end of file
+}
+
+//Thresholds for marking requests as failed in the report, latencies are in ms and error rate in %.
+//Thresholds of a request override the ones defined at the top level
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Thresholds {
+    #[serde(flatten)]
+    pub default: Threshold,
+
+    #[serde(default)]
+    pub requests: HashMap<String, Threshold>
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Threshold {
+    #[serde(default)]
+    pub avg: Option<f64>,
+
+    #[serde(default)]
+    pub max: Option<f64>,
+
+    #[serde(default)]
+    pub p90: Option<f64>,
+
+    #[serde(default)]
+    pub p95: Option<f64>,
+
+    #[serde(default)]
+    pub p99: Option<f64>,
+
+    #[serde(default)]
+    #[serde(rename = "p99.9")]
+    pub p99_9: Option<f64>,
+
+    #[serde(default)]
+    #[serde(rename = "p99.99")]
+    pub p99_99: Option<f64>,
+
+    #[serde(default)]
+    #[serde(rename = "errorRate")]
+    pub error_rate: Option<f64>,
+
+    #[serde(default)]
+    #[serde(rename = "minThroughput")]
+    pub min_throughput: Option<f64>
+}
@@ -1,3 +1,4 @@
 pub mod parser;
 pub mod preprocessor;
-pub mod postprocessor;
\ No newline at end of file
+pub mod postprocessor;
+pub mod resolver;
\ No newline at end of file
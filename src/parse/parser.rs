@@ -1,14 +1,15 @@
 use log::{error, info, warn};
 
 use std::{
-    error::Error
+    error::Error,
+    path::Path
 };
 
 use rustc_hash::FxHashMap as HashMap;
 
 use crate::{
     model::{Environment, Config, Request, Root}, 
-    parse::preprocessor
+    parse::resolver::Resolver
 };
 
 pub fn parse_config(content: &str) -> Result<Config, Box<dyn std::error::Error>> {
@@ -31,9 +32,23 @@ pub fn parse_config(content: &str) -> Result<Config, Box<dyn std::error::Error>>
     Ok(config)
 }
 
-pub fn parse_requests(content: &str, env_map: &HashMap<String, String>) -> Result<Vec<Request>, Box<dyn Error>> {
+pub fn parse_requests(content: &str, scenarios_file: &str, env_map: &HashMap<String, String>) -> Result<Vec<Request>, Box<dyn Error>> {
     info!("Preparing bombardier requests");
-    let scenarios_yml = preprocessor::param_substitution(content, env_map);
+
+    //Includes and refs are resolved relative to the scenarios file, content which is not read from a file can only refer itself
+    let mut resolver = Resolver::new(env_map);
+    let resolved = match scenarios_file.is_empty() {
+        true => resolver.resolve_content(content),
+        false => resolver.resolve(content, Path::new(scenarios_file))
+    };
+
+    let scenarios_yml = match resolved {
+        Ok(v) => serde_yaml::to_string(&v)?, //serialized back as deserializing from value is strict about scalar types
+        Err(err) => {
+            error!("Resolving includes and refs failed: {}", err.to_string());
+            return Err(err)
+        }
+    };
 
     let root: Root = match serde_yaml::from_str(&scenarios_yml) {
         Ok(r) => r,
@@ -48,6 +63,13 @@ pub fn parse_requests(content: &str, env_map: &HashMap<String, String>) -> Resul
     for scenario in root.scenarios {
         for mut request in scenario.requests {
             request.id = uuid::Uuid::new_v4();
+            request.scenario = scenario.name.clone();
+
+            //Scenario level auth is the default for all its requests
+            if request.auth.is_none() {
+                request.auth = scenario.auth.clone();
+            }
+
             request.requires_preprocessing = param_substitution_required(&request);
             requests.push(request);
         }
@@ -83,7 +105,7 @@ fn param_substitution_required(request: &Request) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::{model::{ExtractFrom, ExtractorType}, parser::*};
+    use crate::{model::{Auth, ExtractFrom, ExtractorType}, parser::*};
 
     #[test]
     fn test_parse_config() {
@@ -203,12 +225,13 @@ mod tests {
                 authHeader: 'headers.authorization'
                 host: 'headers.host'";
     
-        let scenarios = parse_requests(scenarios_yaml, &HashMap::default());
+        let scenarios = parse_requests(scenarios_yaml, "", &HashMap::default());
         assert!(scenarios.is_ok());
     
         let requests = scenarios.unwrap();
         assert_eq!(requests.len(),1);
         assert_eq!(requests[0].name, "echoGet");
+        assert_eq!(requests[0].scenario, "scenario1");
         assert_eq!(requests[0].method, "GET");
         assert_eq!(requests[0].url, "https://google.com/");
         assert_eq!(requests[0].extractors.len(), 1);
@@ -242,7 +265,7 @@ mod tests {
             param1Value: param1_value";
     
         let env_map = parse_env_map(env_map_yaml).unwrap();
-        let requests = parse_requests(scenarios_yaml, &env_map).unwrap();
+        let requests = parse_requests(scenarios_yaml, "", &env_map).unwrap();
     
         assert_eq!(requests[0].name, "echoGet");
         assert_eq!(requests[0].method, "GET");
@@ -252,6 +275,33 @@ mod tests {
         assert_eq!(requests[0].requires_preprocessing, true); //true as {{param2Value}} was not part of env map
     }
     
+    #[test]
+    fn test_parse_request_with_scenario_auth() {
+        let scenarios_yaml = r"
+        version: 1.0
+        scenarios:
+        - name: scenario1
+          auth:
+            type: Bearer
+            token: '{{token}}'
+          requests:
+          - name: echoGet
+            method: GET
+            url: 'https://google.com/'
+          - name: echoPost
+            method: POST
+            url: 'https://google.com/'
+            auth:
+              type: Basic
+              username: user";
+
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default()).unwrap();
+
+        assert!(matches!(&requests[0].auth, Some(Auth::Bearer(bearer)) if bearer.token == "{{token}}"));
+        assert!(matches!(&requests[1].auth, Some(Auth::Basic(basic)) if basic.username == "user"));
+        assert!(requests[0].requires_preprocessing); //true as token in scenario auth needs substitution
+    }
+    
     #[test]
     fn test_error_for_missing_request_url() {
         let scenarios_yaml = r"
@@ -262,7 +312,7 @@ mod tests {
           - name: echoGet
           - method: GET";
     
-        let requests = parse_requests(scenarios_yaml, &HashMap::default());
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default());
         assert!(requests.is_err());
         assert!(requests.err().unwrap().to_string().contains("missing field `url`"));
     }
@@ -277,7 +327,7 @@ mod tests {
           - url: 'http://google.com/'
             method: GET";
     
-        let requests = parse_requests(scenarios_yaml, &HashMap::default());
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default());
         assert!(requests.is_err());
         assert!(requests.err().unwrap().to_string().contains("missing field `name`"));
     }
@@ -292,7 +342,7 @@ mod tests {
           - name: echoGet
             url: 'http://google.com/'";
     
-        let requests = parse_requests(scenarios_yaml, &HashMap::default());
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default());
         assert!(requests.is_err());
         assert!(requests.err().unwrap().to_string().contains("missing field `method`"));
     }
@@ -313,7 +363,7 @@ mod tests {
               raw: '{"test": "test"}'
         "#;
         
-        let requests = parse_requests(scenarios_yaml, &HashMap::default()).unwrap();
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default()).unwrap();
         assert_eq!(requests[0].body.raw,String::from(r#"{"test": "test"}"#));
     }
     
@@ -338,7 +388,7 @@ mod tests {
                   type: File
         "#;
         
-        let requests = parse_requests(scenarios_yaml, &HashMap::default()).unwrap();
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default()).unwrap();
         assert_eq!(requests[0].body.formdata.len(),2);
     }
 
@@ -359,11 +409,26 @@ mod tests {
                 authHeader: 'headers.authorization'
                 host: 'headers.host'";
 
-        let requests = parse_requests(scenarios_yaml, &HashMap::default());
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default());
         assert!(requests.is_err());
         assert!(requests.err().unwrap().to_string().contains("expected one of `GjsonPath`, `Xpath`, `RegEx`, `None`"));        
     }
 
+    #[test]
+    fn test_error_for_include_in_content_not_read_from_file() {
+        let scenarios_yaml = r"
+        version: 1.0
+        include:
+        - common.yml
+        scenarios:
+        - name: scenario1
+          requests:
+          - $ref: 'common.yml#/requests/login'";
+
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default());
+        assert!(requests.err().unwrap().to_string().contains("includes and refs to other files are not supported for scenarios which are not read from a file"));
+    }
+
     #[test]
     fn test_error_for_invalid_extractor_from() {
         let scenarios_yaml = r"
@@ -381,7 +446,7 @@ mod tests {
                 authHeader: 'headers.authorization'
                 host: 'headers.host'";
 
-        let requests = parse_requests(scenarios_yaml, &HashMap::default());
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default());
         assert!(requests.is_err());
         assert!(requests.err().unwrap().to_string().contains("expected `Body` or `Headers`"));        
     }
@@ -401,7 +466,7 @@ mod tests {
               extract:
                 authHeader: 'headers.authorization'";
 
-        let requests = parse_requests(scenarios_yaml, &HashMap::default()).unwrap();
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default()).unwrap();
         assert_eq!(requests[0].extractors[0].from, ExtractFrom::Body);        
     }
 
@@ -420,7 +485,7 @@ mod tests {
               extract:
                 server: server";
 
-        let requests = parse_requests(scenarios_yaml, &HashMap::default()).unwrap();
+        let requests = parse_requests(scenarios_yaml, "", &HashMap::default()).unwrap();
         assert_eq!(requests[0].extractors[0].extractor_type, ExtractorType::None);    
     }
 }
\ No newline at end of file
@@ -3,10 +3,7 @@ use libxml::parser::Parser as xml_parser;
 use libxml::xpath::Context;
 use log::{debug, error, warn};
 use regex::Regex;
-use reqwest::{
-    Response, 
-    header::{HeaderMap, CONTENT_TYPE}
-};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
 use rustc_hash::FxHashMap as HashMap;
 
 use std::error::Error;
@@ -86,12 +83,12 @@ fn extract<T: Extractor>(extractor: T, body: &str, map: &HashMap<String, String>
     Ok(())
 }
 
-pub async fn process(response: Response, extractors: &[model::Extractor], env_map: &mut HashMap<String, String>) -> Result<(), Box<dyn Error + 'static>> {
+pub fn process(headers: &HeaderMap, body: &str, extractors: &[model::Extractor], env_map: &mut HashMap<String, String>) -> Result<(), Box<dyn Error + 'static>> {
     //run the extractors for headers first
-    execute_header_extractors(response.headers(), extractors, env_map)?;
+    execute_header_extractors(headers, extractors, env_map)?;
 
     //run body extractors
-    execute_body_extractors(response, extractors, env_map).await?;
+    execute_body_extractors(headers, body, extractors, env_map)?;
 
     Ok(())
 }
@@ -121,12 +118,11 @@ fn execute_header_extractors(headers: &HeaderMap, extractors: &[model::Extractor
     Ok(())
 }
 
-async fn execute_body_extractors(response: Response, extractors: &[model::Extractor], env_map: &mut HashMap<String, String>)
+fn execute_body_extractors(headers: &HeaderMap, body: &str, extractors: &[model::Extractor], env_map: &mut HashMap<String, String>)
 -> Result<(), Box<dyn Error + 'static>> {
 
-    let is_json_response = is_json_response(&response);
-    let is_xml_response = !is_json_response && is_xml_response(&response);
-    let body = get_response_as_string(response).await; 
+    let is_json_response = is_json_response(headers);
+    let is_xml_response = !is_json_response && is_xml_response(headers);
 
     for extractor in extractors {
         match extractor.from {
@@ -138,7 +134,7 @@ async fn execute_body_extractors(response: Response, extractors: &[model::Extrac
                             continue;
                         }
         
-                        extract(JsonExtractor, &body, &extractor.extract, env_map)?; 
+                        extract(JsonExtractor, body, &extractor.extract, env_map)?; 
                     },
                     ExtractorType::Xpath => {
                         if !is_xml_response {
@@ -146,10 +142,10 @@ async fn execute_body_extractors(response: Response, extractors: &[model::Extrac
                             continue;
                         }
         
-                        extract(XpathExtractor, &body, &extractor.extract, env_map)?; 
+                        extract(XpathExtractor, body, &extractor.extract, env_map)?; 
                     },
                     ExtractorType::RegEx => {
-                        extract(RegExExtractor, &body, &extractor.extract, env_map)?; 
+                        extract(RegExExtractor, body, &extractor.extract, env_map)?; 
                     },
                     _ => {
                         error!("Invalid extractor type found to extract from body: {:?}", extractor.extractor_type)
@@ -163,26 +159,16 @@ async fn execute_body_extractors(response: Response, extractors: &[model::Extrac
     Ok(())
 }
 
-fn is_json_response(response: &Response) -> bool {
-    let content_type = get_response_content_type(response.headers());
+fn is_json_response(headers: &HeaderMap) -> bool {
+    let content_type = get_response_content_type(headers);
     content_type.contains("json")
 }
 
-fn is_xml_response(response: &Response) -> bool {
-    let content_type = get_response_content_type(response.headers());
+fn is_xml_response(headers: &HeaderMap) -> bool {
+    let content_type = get_response_content_type(headers);
     content_type.contains("xml") || content_type.contains("html")
 }
 
-async fn get_response_as_string(response: Response) -> String {
-    match response.text().await {
-        Ok(s) => s,
-        Err(err) => {
-            error!("Error while getting response as String: {}", err);
-            String::from("")
-        }
-    }
-}
-
 fn get_response_content_type(headers: &HeaderMap) -> &str {
     match headers.get(CONTENT_TYPE) {
         Some(v) => {
@@ -200,42 +186,42 @@ fn get_response_content_type(headers: &HeaderMap) -> &str {
 
 #[cfg(test)]
 mod tests {
-    use crate::{parse::postprocessor::*};
+    use crate::{parse::postprocessor::*, protocol::http::read_body};
     
     #[tokio::test]
     async fn test_is_json_response() {
         let response = reqwest::get("https://httpbin.org/get").await.unwrap();
-        assert!(is_json_response(&response));
+        assert!(is_json_response(response.headers()));
     }
 
     #[tokio::test]
     async fn test_is_not_json_response() {
         let response = reqwest::get("https://google.com/").await.unwrap();
-        assert!(!is_json_response(&response));
+        assert!(!is_json_response(response.headers()));
     }
 
     #[tokio::test]
     async fn test_is_xml_response() {
         let response = reqwest::get("https://httpbin.org/xml").await.unwrap();
-        assert!(is_xml_response(&response));
+        assert!(is_xml_response(response.headers()));
     }
 
     #[tokio::test]
     async fn test_is_html_response() {
         let response = reqwest::get("https://httpbin.org/html").await.unwrap();
-        assert!(is_xml_response(&response));
+        assert!(is_xml_response(response.headers()));
     }
 
     #[tokio::test]
     async fn test_is_not_xml_response() {
         let response = reqwest::get("https://httpbin.org/get").await.unwrap();
-        assert!(!is_xml_response(&response));
+        assert!(!is_xml_response(response.headers()));
     }
 
     #[tokio::test]
     async fn test_json_extractor() {
         let response = reqwest::get("https://httpbin.org/get").await.unwrap();
-        let response_as_str = &get_response_as_string(response).await;
+        let response_as_str = &read_body(response).await.0;
         let json_extractor = JsonExtractor{};
         assert_eq!(json_extractor.extract("headers.Host", response_as_str).unwrap(), String::from("httpbin.org"));
     }
@@ -243,7 +229,7 @@ mod tests {
     #[tokio::test]
     async fn test_xml_extractor() {
         let response = reqwest::get("https://httpbin.org/xml").await.unwrap();
-        let response_as_str = &get_response_as_string(response).await;
+        let response_as_str = &read_body(response).await.0;
         let xml_extractor = XpathExtractor{};
         assert_eq!(xml_extractor.extract("//slide/title", response_as_str).unwrap(), String::from("Wake up to WonderWidgets!"));
         assert_eq!(xml_extractor.extract("//slide/@type", response_as_str).unwrap(), String::from("all"));
@@ -253,7 +239,7 @@ mod tests {
     #[tokio::test]
     async fn test_html_extractor() {
         let response = reqwest::get("https://httpbin.org/html").await.unwrap();
-        let response_as_str = &get_response_as_string(response).await;
+        let response_as_str = &read_body(response).await.0;
         let xml_extractor = XpathExtractor{};
         assert_eq!(xml_extractor.extract("//h1", response_as_str).unwrap(), String::from("Herman Melville - Moby-Dick"));
         assert!(xml_extractor.extract("//body//p", response_as_str).unwrap().contains("summer-cool weather"));
@@ -293,7 +279,7 @@ mod tests {
     #[tokio::test]
     async fn test_extractor() {
         let response = reqwest::get("https://httpbin.org/get").await.unwrap();
-        let response_as_str = &get_response_as_string(response).await;
+        let response_as_str = &read_body(response).await.0;
         let mut extractor = HashMap::default();
         extractor.insert(String::from("host"), String::from("headers.Host"));
         let mut env_map = HashMap::default();
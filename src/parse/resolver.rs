@@ -0,0 +1,552 @@
+use log::{debug, info};
+use rustc_hash::FxHashMap as HashMap;
+use serde_yaml::{Mapping, Value};
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf}
+};
+
+use crate::parse::preprocessor;
+
+const INCLUDE_KEY: &str = "include";
+const REQUESTS_KEY: &str = "requests";
+const SCENARIOS_KEY: &str = "scenarios";
+const REF_KEY: &str = "$ref";
+
+//Path of scenarios content which is not read from a file, used only in messages
+const CONTENT_PATH: &str = "scenarios content";
+
+//Resolves `include` and `$ref` in a scenarios document so that it can be deserialized into model::Root
+pub struct Resolver<'a> {
+    env_map: &'a HashMap<String, String>,
+    include_stack: Vec<PathBuf>,
+    ref_stack: Vec<String>,
+    documents: HashMap<PathBuf, Value>,
+    files: HashMap<PathBuf, Value>,
+    reads_files: bool
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(env_map: &'a HashMap<String, String>) -> Self {
+        Resolver {
+            env_map,
+            include_stack: vec![],
+            ref_stack: vec![],
+            documents: HashMap::default(),
+            files: HashMap::default(),
+            reads_files: true
+        }
+    }
+
+    //Resolves scenarios content which is not read from a file, like scenarios uploaded to the hub.
+    //Other files cannot be included or referred as there is no file to resolve their paths against
+    pub fn resolve_content(&mut self, content: &str) -> Result<Value, Box<dyn Error>> {
+        self.reads_files = false;
+        self.resolve(content, Path::new(CONTENT_PATH))
+    }
+
+    //Resolves the root scenarios content, file path is used to resolve relative includes and refs
+    pub fn resolve(&mut self, content: &str, file_path: &Path) -> Result<Value, Box<dyn Error>> {
+        let file_path = normalize(file_path);
+        let document = self.parse_content(content, &file_path)?;
+        let document = self.resolve_includes(document, &file_path)?;
+        self.documents.insert(file_path.clone(), document.clone());
+
+        self.resolve_refs(document, &file_path)
+    }
+
+    fn parse_content(&self, content: &str, file_path: &Path) -> Result<Value, Box<dyn Error>> {
+        let content = preprocessor::param_substitution(content, self.env_map);
+        match serde_yaml::from_str(&content) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(format!("Parsing {} failed: {}", file_path.display(), err).into())
+        }
+    }
+
+    //Loads a file along with all its includes, documents are cached by their path
+    fn load_document(&mut self, file_path: &Path) -> Result<Value, Box<dyn Error>> {
+        if let Some(document) = self.documents.get(file_path) {
+            return Ok(document.clone());
+        }
+
+        let document = self.load_file(file_path)?;
+        let document = self.resolve_includes(document, file_path)?;
+        self.documents.insert(file_path.to_path_buf(), document.clone());
+
+        Ok(document)
+    }
+
+    //Loads a file without resolving its includes, files are cached by their path
+    fn load_file(&mut self, file_path: &Path) -> Result<Value, Box<dyn Error>> {
+        if let Some(file) = self.files.get(file_path) {
+            return Ok(file.clone());
+        }
+
+        if !self.reads_files {
+            return Err(format!("Unable to read file {}: includes and refs to other files are not supported for scenarios which are not read from a file, like the ones uploaded to the hub", 
+                file_path.display()).into());
+        }
+
+        info!("Reading included scenarios file {}", file_path.display());
+        let content = match fs::read_to_string(file_path) {
+            Ok(c) => c,
+            Err(err) => return Err(format!("Unable to read file {}: {}", file_path.display(), err).into())
+        };
+
+        let file = self.parse_content(&content, file_path)?;
+        self.files.insert(file_path.to_path_buf(), file.clone());
+
+        Ok(file)
+    }
+
+    //Merges request templates and scenarios of all included files into the document.
+    //Every file is merged once even if it is included by several files, like when two included files include the same file
+    fn resolve_includes(&mut self, mut document: Value, file_path: &Path) -> Result<Value, Box<dyn Error>> {
+        let mut included = vec![];
+        self.collect_includes(&document, file_path, &mut included)?;
+
+        if let Some(root) = document.as_mapping_mut() {
+            root.remove(&key(INCLUDE_KEY));
+        }
+
+        if included.is_empty() {
+            return Ok(document);
+        }
+
+        let mut templates = Mapping::new();
+        let mut scenarios = vec![];
+
+        //Files are in the order of their includes with included files before the including ones
+        for include_path in included {
+            let file = self.load_file(&include_path)?;
+
+            //Refs inside included templates are relative to the file they were defined in
+            if let Some(Value::Mapping(t)) = file.get(REQUESTS_KEY) {
+                for (name, template) in t {
+                    templates.insert(name.clone(), self.resolve_refs(template.clone(), &include_path)?);
+                }
+            }
+
+            if let Some(Value::Sequence(s)) = file.get(SCENARIOS_KEY) {
+                for scenario in s {
+                    scenarios.push(self.resolve_refs(scenario.clone(), &include_path)?);
+                }
+            }
+        }
+
+        let root = match document.as_mapping_mut() {
+            Some(root) => root,
+            None => return Err(format!("{} should be a yml mapping", file_path.display()).into())
+        };
+
+        //Templates defined in the including file take precedence
+        if let Some(Value::Mapping(local_templates)) = root.get(&key(REQUESTS_KEY)) {
+            for (name, template) in local_templates {
+                templates.insert(name.clone(), template.clone());
+            }
+        }
+
+        //Scenarios from included files are executed before the ones in the including file
+        if let Some(Value::Sequence(local_scenarios)) = root.get(&key(SCENARIOS_KEY)) {
+            scenarios.extend(local_scenarios.iter().cloned());
+        }
+
+        if !templates.is_empty() {
+            root.insert(key(REQUESTS_KEY), Value::Mapping(templates));
+        }
+
+        root.insert(key(SCENARIOS_KEY), Value::Sequence(scenarios));
+        Ok(document)
+    }
+
+    //Adds paths of files included by the document, depth first, skipping the ones already added
+    fn collect_includes(&mut self, document: &Value, file_path: &Path, included: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+        let includes = match document.get(INCLUDE_KEY) {
+            Some(Value::Sequence(includes)) => includes.clone(),
+            Some(Value::String(include)) => vec![Value::String(include.clone())],
+            Some(Value::Null) | None => return Ok(()),
+            Some(_) => return Err(format!("'{}' in {} should be a list of file paths", INCLUDE_KEY, file_path.display()).into())
+        };
+
+        self.include_stack.push(file_path.to_path_buf());
+
+        for include in includes {
+            let include = match include.as_str() {
+                Some(i) => i.to_owned(),
+                None => return Err(format!("Invalid include {:?} in {}, should be a file path", include, file_path.display()).into())
+            };
+
+            let include_path = relative_to(file_path, &include);
+            debug!("Including {} in {}", include_path.display(), file_path.display());
+
+            if self.include_stack.contains(&include_path) {
+                return Err(format!("Cyclic include detected: {}", cycle(&self.include_stack, &include_path)).into());
+            }
+
+            if included.contains(&include_path) {
+                debug!("Skipping {} in {} as it is already included", include_path.display(), file_path.display());
+                continue;
+            }
+
+            let file = self.load_file(&include_path)?;
+            self.collect_includes(&file, &include_path, included)?;
+            included.push(include_path);
+        }
+
+        self.include_stack.pop();
+        Ok(())
+    }
+
+    //Replaces every mapping having a $ref with the referred node merged with the remaining keys as overrides
+    fn resolve_refs(&mut self, value: Value, file_path: &Path) -> Result<Value, Box<dyn Error>> {
+        match value {
+            Value::Mapping(mut mapping) => {
+                let reference = mapping.remove(&key(REF_KEY));
+
+                let mut resolved = Mapping::new();
+                for (k, v) in mapping {
+                    resolved.insert(k, self.resolve_refs(v, file_path)?);
+                }
+
+                match reference {
+                    Some(Value::String(reference)) => {
+                        let target = self.resolve_ref(&reference, file_path)?;
+                        Ok(merge(target, Value::Mapping(resolved)))
+                    },
+                    Some(other) => Err(format!("Invalid {} {:?} in {}, should be a string", REF_KEY, other, file_path.display()).into()),
+                    None => Ok(Value::Mapping(resolved))
+                }
+            },
+            Value::Sequence(sequence) => {
+                let mut resolved = Vec::with_capacity(sequence.len());
+                for v in sequence {
+                    resolved.push(self.resolve_refs(v, file_path)?);
+                }
+
+                Ok(Value::Sequence(resolved))
+            },
+            v => Ok(v)
+        }
+    }
+
+    fn resolve_ref(&mut self, reference: &str, file_path: &Path) -> Result<Value, Box<dyn Error>> {
+        let (file, pointer) = match reference.find('#') {
+            Some(i) => (&reference[..i], &reference[i+1..]),
+            None => (reference, "")
+        };
+
+        let target_path = match file {
+            "" => file_path.to_path_buf(),
+            f => relative_to(file_path, f)
+        };
+
+        let ref_id = format!("{}#{}", target_path.display(), pointer);
+        if self.ref_stack.contains(&ref_id) {
+            let chain = self.ref_stack.iter().chain(std::iter::once(&ref_id))
+                .map(|r| r.as_str()).collect::<Vec<&str>>().join(" -> ");
+            return Err(format!("Cyclic {} detected: {}", REF_KEY, chain).into());
+        }
+
+        debug!("Resolving {} {} from {}", REF_KEY, reference, file_path.display());
+        let document = match self.documents.get(&target_path) {
+            Some(d) => d.clone(),
+            None => self.load_document(&target_path)?
+        };
+
+        let node = match get_node(&document, pointer) {
+            Some(n) => n.clone(),
+            None => return Err(format!("Unable to resolve {} '{}' in {}: nothing found at '{}'",
+                REF_KEY, reference, file_path.display(), pointer).into())
+        };
+
+        //Referred node can have its own refs which are relative to its own file
+        self.ref_stack.push(ref_id);
+        let resolved = self.resolve_refs(node, &target_path);
+        self.ref_stack.pop();
+
+        resolved
+    }
+}
+
+fn key(k: &str) -> Value {
+    Value::String(k.to_owned())
+}
+
+//Gets the node for a pointer like /requests/login, sequences can be indexed by position or by the name of the element
+fn get_node<'v>(document: &'v Value, pointer: &str) -> Option<&'v Value> {
+    let mut node = document;
+
+    for segment in pointer.split('/').filter(|s| !s.is_empty()) {
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        node = match node {
+            Value::Mapping(m) => m.get(&key(&segment))?,
+            Value::Sequence(s) => match segment.parse::<usize>() {
+                Ok(i) => s.get(i)?,
+                Err(_) => s.iter().find(|v| v.get("name").and_then(|n| n.as_str()) == Some(segment.as_str()))?
+            },
+            _ => return None
+        };
+    }
+
+    Some(node)
+}
+
+//Deep merges overrides into base, mappings are merged while all other values are replaced
+fn merge(base: Value, overrides: Value) -> Value {
+    match (base, overrides) {
+        (Value::Mapping(mut base), Value::Mapping(overrides)) => {
+            for (k, v) in overrides {
+                let merged = match base.remove(&k) {
+                    Some(b) => merge(b, v),
+                    None => v
+                };
+
+                base.insert(k, merged);
+            }
+
+            Value::Mapping(base)
+        },
+        (_, overrides) => overrides
+    }
+}
+
+fn relative_to(file_path: &Path, path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return normalize(path);
+    }
+
+    match file_path.parent() {
+        Some(dir) => normalize(&dir.join(path)),
+        None => normalize(path)
+    }
+}
+
+//Removes . and .. components so the same file is always represented by the same path
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir => {
+                if normalized.file_name().is_some() {
+                    normalized.pop();
+                } else {
+                    normalized.push("..");
+                }
+            },
+            c => normalized.push(c.as_os_str())
+        }
+    }
+
+    normalized
+}
+
+fn cycle(stack: &[PathBuf], path: &Path) -> String {
+    stack.iter()
+        .skip_while(|p| *p != path)
+        .chain(std::iter::once(&path.to_path_buf()))
+        .map(|p| p.display().to_string())
+        .collect::<Vec<String>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write, path::Path};
+    use tempdir::TempDir;
+
+    use crate::parse::resolver::*;
+
+    fn write_file(dir: &TempDir, name: &str, content: &str) {
+        let mut file = File::create(dir.path().join(name)).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_get_node() {
+        let document: Value = serde_yaml::from_str(r"
+        requests:
+          login:
+            name: login
+        scenarios:
+        - name: scenario1
+          requests:
+          - name: echoGet").unwrap();
+
+        assert_eq!(get_node(&document, "/requests/login/name").unwrap().as_str(), Some("login"));
+        assert_eq!(get_node(&document, "/scenarios/0/requests/echoGet/name").unwrap().as_str(), Some("echoGet"));
+        assert!(get_node(&document, "/requests/logout").is_none());
+        assert!(get_node(&document, "").unwrap().is_mapping());
+    }
+
+    #[test]
+    fn test_merge_overrides() {
+        let base: Value = serde_yaml::from_str(r"
+        name: login
+        method: POST
+        headers:
+          accept: application/json
+          x-version: 1").unwrap();
+
+        let overrides: Value = serde_yaml::from_str(r"
+        name: adminLogin
+        headers:
+          x-version: 2").unwrap();
+
+        let merged = merge(base, overrides);
+        assert_eq!(merged.get("name").unwrap().as_str(), Some("adminLogin"));
+        assert_eq!(merged.get("method").unwrap().as_str(), Some("POST"));
+        assert_eq!(merged.get("headers").unwrap().get("accept").unwrap().as_str(), Some("application/json"));
+        assert_eq!(merged.get("headers").unwrap().get("x-version").unwrap().as_u64(), Some(2));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("./a/b/../c.yml")), PathBuf::from("a/c.yml"));
+        assert_eq!(relative_to(Path::new("tests/scenarios.yml"), "common.yml"), PathBuf::from("tests/common.yml"));
+        assert_eq!(relative_to(Path::new("scenarios.yml"), "../common.yml"), PathBuf::from("../common.yml"));
+        assert_eq!(relative_to(Path::new("../scenarios.yml"), "../common.yml"), PathBuf::from("../../common.yml"));
+    }
+
+    #[test]
+    fn test_resolve_local_ref() {
+        let content = r"
+        version: 1.0
+        requests:
+          login:
+            name: login
+            method: POST
+            url: 'https://google.com/login'
+        scenarios:
+        - name: scenario1
+          requests:
+          - $ref: '#/requests/login'
+            name: adminLogin";
+
+        let env_map = HashMap::default();
+        let document = Resolver::new(&env_map).resolve(content, Path::new("scenarios.yml")).unwrap();
+        let request = get_node(&document, "/scenarios/0/requests/0").unwrap();
+        assert_eq!(request.get("name").unwrap().as_str(), Some("adminLogin"));
+        assert_eq!(request.get("method").unwrap().as_str(), Some("POST"));
+        assert!(request.get(REF_KEY).is_none());
+    }
+
+    #[test]
+    fn test_resolve_include_and_file_ref() {
+        let dir = TempDir::new("test_resolve_include_and_file_ref").unwrap();
+        write_file(&dir, "common.yml", r"
+        requests:
+          login:
+            name: login
+            method: POST
+            url: '{{baseUrl}}/login'
+          refresh:
+            $ref: '#/requests/login'
+            name: refresh
+        scenarios:
+        - name: setup
+          requests:
+          - $ref: '#/requests/login'");
+
+        let content = r"
+        version: 1.0
+        include:
+        - common.yml
+        scenarios:
+        - name: scenario1
+          requests:
+          - $ref: 'common.yml#/requests/refresh'
+          - $ref: '#/requests/login'
+            headers:
+              authorization: jwt";
+
+        let mut env_map = HashMap::default();
+        env_map.insert(String::from("baseUrl"), String::from("https://google.com"));
+
+        let root_path = dir.path().join("scenarios.yml");
+        let document = Resolver::new(&env_map).resolve(content, &root_path).unwrap();
+
+        let scenarios = document.get(SCENARIOS_KEY).unwrap().as_sequence().unwrap();
+        assert_eq!(scenarios.len(), 2);
+        assert_eq!(scenarios[0].get("name").unwrap().as_str(), Some("setup"));
+
+        let requests = scenarios[1].get("requests").unwrap().as_sequence().unwrap();
+        assert_eq!(requests[0].get("name").unwrap().as_str(), Some("refresh"));
+        assert_eq!(requests[0].get("url").unwrap().as_str(), Some("https://google.com/login"));
+        assert_eq!(requests[1].get("name").unwrap().as_str(), Some("login"));
+        assert_eq!(requests[1].get("headers").unwrap().get("authorization").unwrap().as_str(), Some("jwt"));
+    }
+
+    #[test]
+    fn test_file_included_by_several_files_is_merged_once() {
+        let dir = TempDir::new("test_file_included_by_several_files_is_merged_once").unwrap();
+        write_file(&dir, "common.yml", "requests:\n  login:\n    name: login\nscenarios:\n- name: setup\n");
+        write_file(&dir, "orders.yml", "include:\n- common.yml\nscenarios:\n- name: orders\n");
+        write_file(&dir, "users.yml", "include:\n- ./common.yml\nscenarios:\n- name: users\n");
+
+        let env_map = HashMap::default();
+        let content = "include:\n- orders.yml\n- users.yml\n- common.yml\nscenarios:\n- name: main\n";
+        let document = Resolver::new(&env_map).resolve(content, &dir.path().join("scenarios.yml")).unwrap();
+
+        let names = document.get(SCENARIOS_KEY).unwrap().as_sequence().unwrap().iter()
+            .map(|s| s.get("name").unwrap().as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, ["setup", "orders", "users", "main"]);
+        assert!(document.get(REQUESTS_KEY).unwrap().get("login").is_some());
+        assert!(document.get(INCLUDE_KEY).is_none());
+    }
+
+    #[test]
+    fn test_error_for_cyclic_include() {
+        let dir = TempDir::new("test_error_for_cyclic_include").unwrap();
+        write_file(&dir, "a.yml", "include:\n- b.yml\n");
+        write_file(&dir, "b.yml", "include:\n- a.yml\n");
+
+        let env_map = HashMap::default();
+        let result = Resolver::new(&env_map).resolve("include:\n- a.yml\n", &dir.path().join("scenarios.yml"));
+        let err = result.err().unwrap().to_string();
+        assert!(err.contains("Cyclic include detected"));
+        assert!(err.contains("a.yml -> ") && err.contains("b.yml -> "));
+    }
+
+    #[test]
+    fn test_error_for_cyclic_ref() {
+        let content = r"
+        requests:
+          a:
+            $ref: '#/requests/b'
+          b:
+            $ref: '#/requests/a'
+        scenarios:
+        - name: scenario1
+          requests:
+          - $ref: '#/requests/a'";
+
+        let env_map = HashMap::default();
+        let result = Resolver::new(&env_map).resolve(content, Path::new("scenarios.yml"));
+        assert!(result.err().unwrap().to_string().contains("Cyclic $ref detected"));
+    }
+
+    #[test]
+    fn test_error_for_missing_ref() {
+        let content = r"
+        scenarios:
+        - name: scenario1
+          requests:
+          - $ref: '#/requests/login'";
+
+        let env_map = HashMap::default();
+        let result = Resolver::new(&env_map).resolve(content, Path::new("scenarios.yml"));
+        assert!(result.err().unwrap().to_string().contains("Unable to resolve $ref '#/requests/login'"));
+    }
+
+    #[test]
+    fn test_error_for_missing_include() {
+        let env_map = HashMap::default();
+        let result = Resolver::new(&env_map).resolve("include:\n- missing.yml\n", Path::new("scenarios.yml"));
+        assert!(result.err().unwrap().to_string().contains("Unable to read file missing.yml"));
+    }
+}
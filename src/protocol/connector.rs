@@ -0,0 +1,303 @@
+use hyper::{
+    Uri,
+    client::connect::{Connected, Connection}
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::{self, TcpStream}
+};
+use tokio_native_tls::{TlsConnector, TlsStream};
+use tower_service::Service;
+
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, atomic::{AtomicBool, AtomicU64, Ordering}},
+    task::{Context, Poll},
+    time::{Duration, Instant}
+};
+
+use crate::{
+    model::ErrorKind,
+    protocol::{http::TransportError, proxy::{Proxies, Proxy}}
+};
+
+//Response of a proxy to CONNECT is expected to be much smaller than this
+const MAX_TUNNEL_RESPONSE_SIZE: usize = 8192;
+
+//Time (in µs) taken to set up a connection
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timings {
+    pub dns: u64,
+    pub connect: u64,
+    pub tls: u64
+}
+
+//Details of a connection, hyper adds them to the extensions of every response received on it.
+//As an HTTP/1 connection is used by one request at a time, bytes read or written since they were last taken belong to the current request
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    timings: Timings,
+    is_new: Arc<AtomicBool>,
+    sent: Arc<AtomicU64>,
+    received: Arc<AtomicU64>
+}
+
+impl ConnectionInfo {
+    fn new(timings: Timings) -> Self {
+        ConnectionInfo {
+            timings,
+            is_new: Arc::new(AtomicBool::new(true)),
+            sent: Arc::new(AtomicU64::new(0)),
+            received: Arc::new(AtomicU64::new(0))
+        }
+    }
+
+    //Timings are returned only for the first request on the connection, requests on a reused connection do not set it up
+    pub fn take_timings(&self) -> Timings {
+        match self.is_new.swap(false, Ordering::SeqCst) {
+            true => self.timings,
+            false => Timings::default()
+        }
+    }
+
+    pub fn take_bytes_sent(&self) -> u64 {
+        self.sent.swap(0, Ordering::SeqCst)
+    }
+
+    pub fn take_bytes_received(&self) -> u64 {
+        self.received.swap(0, Ordering::SeqCst)
+    }
+}
+
+//Connector of hyper which times DNS lookup, TCP connect and TLS handshake and counts bytes written to and read from the socket.
+//Bytes include TLS records, so the handshake is counted in the bytes of the first request on a connection.
+//Connections to proxies are timed instead when a proxy is used, https urls are tunneled through them with CONNECT
+#[derive(Clone)]
+pub struct TimedConnector {
+    tls: TlsConnector,
+    connect_timeout: Option<Duration>,
+    proxies: Arc<Proxies>
+}
+
+impl TimedConnector {
+    pub fn new(tls: native_tls::TlsConnector, connect_timeout: Option<Duration>, proxies: Arc<Proxies>) -> Self {
+        TimedConnector {
+            tls: TlsConnector::from(tls),
+            connect_timeout,
+            proxies
+        }
+    }
+
+    async fn connect(self, uri: Uri) -> Result<TimedStream, TransportError> {
+        let is_tls = uri.scheme_str() == Some("https");
+        let host = uri.host()
+            .ok_or_else(|| TransportError::new(ErrorKind::Connect, format!("Missing host in url {}", uri)))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_owned();
+        let port = uri.port_u16().unwrap_or(if is_tls { 443 } else { 80 });
+
+        let proxy = self.proxies.get(&uri);
+        let (connect_host, connect_port) = match proxy {
+            Some(proxy) => (proxy.host.as_str(), proxy.port),
+            None => (host.as_str(), port)
+        };
+
+        //No lookup is needed for ip addresses
+        let start_time = Instant::now();
+        let addrs: Vec<SocketAddr> = match connect_host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, connect_port)],
+            Err(_) => net::lookup_host((connect_host, connect_port)).await
+                .map_err(|err| TransportError::from_source(ErrorKind::Connect, "dns error", err))?
+                .collect()
+        };
+        let dns = start_time.elapsed().as_micros() as u64;
+
+        //Tunnel through the proxy is counted in the connect time
+        let start_time = Instant::now();
+        let stream = connect_tcp(&addrs).await?;
+        let mut info = ConnectionInfo::new(Timings::default());
+        let mut stream = CountedStream { stream, info: info.clone() };
+        if let (Some(proxy), true) = (proxy, is_tls) {
+            tunnel(&mut stream, &host, port, proxy).await?;
+        }
+        info.timings = Timings { dns, connect: start_time.elapsed().as_micros() as u64, tls: 0 };
+
+        if !is_tls {
+            return Ok(TimedStream { stream: Stream::Plain(stream), info, is_proxied: proxy.is_some() })
+        }
+
+        let start_time = Instant::now();
+        let stream = self.tls.connect(&host, stream).await
+            .map_err(|err| TransportError::from_source(ErrorKind::Tls, "tls handshake error", err))?;
+        info.timings.tls = start_time.elapsed().as_micros() as u64;
+
+        Ok(TimedStream { stream: Stream::Tls(Box::new(stream)), info, is_proxied: false })
+    }
+}
+
+//Asks the proxy to open a tunnel to the host and reads its response till the end of its headers
+async fn tunnel(stream: &mut CountedStream, host: &str, port: u16, proxy: &Proxy) -> Result<(), TransportError> {
+    let authority = match host.contains(':') {
+        true => format!("[{}]:{}", host, port),
+        false => format!("{}:{}", host, port)
+    };
+
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority).into_bytes();
+    if let Some(authorization) = &proxy.authorization {
+        request.extend_from_slice(b"Proxy-Authorization: ");
+        request.extend_from_slice(authorization.as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+    request.extend_from_slice(b"\r\n");
+
+    let map_err = |err| TransportError::from_source(ErrorKind::Connect, "proxy tunnel error", err);
+    stream.write_all(&request).await.map_err(map_err)?;
+
+    let mut response = Vec::new();
+    let mut buf = [0; 1024];
+    while !response.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await.map_err(map_err)?;
+        if read == 0 || response.len() + read > MAX_TUNNEL_RESPONSE_SIZE {
+            return Err(TransportError::new(ErrorKind::Connect, String::from("proxy tunnel error: invalid response to CONNECT")))
+        }
+
+        response.extend_from_slice(&buf[..read]);
+    }
+
+    let status_line = String::from_utf8_lossy(&response).lines().next().unwrap_or_default().to_owned();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(TransportError::new(ErrorKind::Connect, format!("proxy tunnel error: proxy responded with {}", status_line)))
+    }
+}
+
+//Addresses are tried in order and error of the last one is returned if none of them connect
+async fn connect_tcp(addrs: &[SocketAddr]) -> Result<TcpStream, TransportError> {
+    let mut last_err = None;
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => {
+                stream.set_nodelay(true).map_err(|err| TransportError::from_source(ErrorKind::Connect, "tcp connect error", err))?;
+                return Ok(stream)
+            },
+            Err(err) => last_err = Some(err)
+        }
+    }
+
+    Err(match last_err {
+        Some(err) => TransportError::from_source(ErrorKind::Connect, "tcp connect error", err),
+        None => TransportError::new(ErrorKind::Connect, "dns error: no addresses found".to_owned())
+    })
+}
+
+impl Service<Uri> for TimedConnector {
+    type Response = TimedStream;
+    type Error = TransportError;
+    type Future = Pin<Box<dyn Future<Output = Result<TimedStream, TransportError>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    //Connect timeout covers lookup, connect and handshake
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector = self.clone();
+        Box::pin(async move {
+            match connector.connect_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, connector.connect(uri)).await
+                    .unwrap_or_else(|_| Err(TransportError::new(ErrorKind::Timeout, format!("connect timed out after {} ms", timeout.as_millis())))),
+                None => connector.connect(uri).await
+            }
+        })
+    }
+}
+
+//TCP stream counting the bytes read and written
+pub struct CountedStream {
+    stream: TcpStream,
+    info: ConnectionInfo
+}
+
+impl AsyncRead for CountedStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut this.stream).poll_read(cx, buf);
+        this.info.received.fetch_add((buf.filled().len() - filled) as u64, Ordering::SeqCst);
+        poll
+    }
+}
+
+impl AsyncWrite for CountedStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.stream).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            this.info.sent.fetch_add(written as u64, Ordering::SeqCst);
+        }
+
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+enum Stream {
+    Plain(CountedStream),
+    Tls(Box<TlsStream<CountedStream>>)
+}
+
+pub struct TimedStream {
+    stream: Stream,
+    info: ConnectionInfo,
+    is_proxied: bool
+}
+
+//Requests on connections to a proxy are sent with the absolute url
+impl Connection for TimedStream {
+    fn connected(&self) -> Connected {
+        Connected::new().proxy(self.is_proxied).extra(self.info.clone())
+    }
+}
+
+impl AsyncRead for TimedStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf)
+        }
+    }
+}
+
+impl AsyncWrite for TimedStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf)
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx)
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx)
+        }
+    }
+}
@@ -1,29 +1,88 @@
-use log::{error, info, warn};
+use hyper::{
+    Body as HyperBody,
+    Client as HyperClient,
+    body::{Bytes, HttpBody},
+    header::{ACCEPT, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, PROXY_AUTHORIZATION, REFERER, SET_COOKIE, TRANSFER_ENCODING, WWW_AUTHENTICATE}
+};
+use log::{debug, error, info, warn};
+use native_tls::{Certificate, Identity, TlsConnector};
+use percent_encoding::percent_decode_str;
+use rand::Rng;
 use reqwest::{
     Client, 
+    Method,
     Request,
     Response, 
-    Certificate, 
-    Identity
+    StatusCode,
+    cookie::{CookieStore, Jar},
+    header::{HeaderMap, HeaderValue}
 };
-use tokio::fs;
+use tokio::{fs, time::{Instant, Sleep}};
+use url::Url;
 
 use std::{
     error::Error,
+    fmt,
+    pin::Pin,
+    sync::{Arc, atomic::{AtomicU64, Ordering}},
     time,
 };
 
-use crate::{model};
+use crate::{
+    model::{self, BackoffType, ErrorKind, Retry},
+    protocol::{connector::{ConnectionInfo, TimedConnector, Timings}, proxy::Proxies}
+};
+
+//Redirects are followed like reqwest does by default, which errors on the 10th redirect
+const MAX_REDIRECTS: usize = 10;
 
+//Requests are built with reqwest and sent with hyper over a connector which times setting up of connections and counts bytes,
+//as reqwest does not allow to plug in a connector. Only HTTP/1.1 is supported, as with reqwest and native tls.
+//Redirects, cookies, credentials in urls and proxies from environment variables are handled like reqwest does by default
 pub struct HttpClient {
     client: Client,
+    transport: HyperClient<TimedConnector>,
+    timeout: Option<time::Duration>,
+    cookies: Option<Jar>,
+    proxies: Arc<Proxies>
 }
 
 impl HttpClient {
     pub async fn new(config: &model::Config) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let client = get_async_client(config).await?;
+        let tls = get_tls_connector(config).await?;
+
+        //Timeouts are disabled if set to 0
+        let connect_timeout = match config.connect_timeout {
+            0 => None,
+            timeout => Some(time::Duration::from_millis(timeout))
+        };
+
+        let timeout = match config.request_timeout {
+            0 => None,
+            timeout => Some(time::Duration::from_millis(timeout))
+        };
+
+        let cookies = match config.handle_cookies {
+            true => {
+                info!("Enabling cookie store");
+                Some(Jar::default())
+            },
+            false => None
+        };
+
+        Ok(HttpClient::build(tls, connect_timeout, timeout, cookies, Proxies::from_env())?)
+    }
 
-        Ok(HttpClient {client})
+    fn build(tls: TlsConnector, connect_timeout: Option<time::Duration>, timeout: Option<time::Duration>, cookies: Option<Jar>,
+             proxies: Proxies) -> Result<Self, reqwest::Error> {
+        let proxies = Arc::new(proxies);
+        Ok(HttpClient {
+            client: Client::builder().build()?,
+            transport: HyperClient::builder().build(TimedConnector::new(tls, connect_timeout, proxies.clone())),
+            timeout,
+            cookies,
+            proxies
+        })
     }
 
     pub fn get_client(&self) -> &Client {
@@ -60,59 +119,794 @@ async fn get_identity(path: &str, password: &str) -> Result<Identity, Box<dyn Er
         }
     };
 
-    Ok(Identity::from_pkcs12_der(&ks, password)?)
+    Ok(Identity::from_pkcs12(&ks, password)?)
 }
 
-async fn get_async_client(config: &model::Config)  -> Result<Client, Box<dyn Error + Send + Sync>> {
-    let mut client_builder = Client::builder()
-        .use_native_tls();
-
-    if config.handle_cookies {
-        info!("Enabling cookie store");
-        client_builder = client_builder.cookie_store(true);
-    }
+async fn get_tls_connector(config: &model::Config)  -> Result<TlsConnector, Box<dyn Error + Send + Sync>> {
+    let mut builder = TlsConnector::builder();
 
     if config.ssl.ignore_ssl {
         warn!("SSL validation has been disabled, this is dangerous as all invalid certs would be accepted");
-        client_builder = client_builder.danger_accept_invalid_certs(true);
+        builder.danger_accept_invalid_certs(true);
     } else {
         if config.ssl.accept_invalid_hostnames {
             warn!("SSL hostname validation has been disabled, this is dangerous as all certs with non matching hostnames would be accepted");
-            client_builder = client_builder.danger_accept_invalid_hostnames(true);
+            builder.danger_accept_invalid_hostnames(true);
         }
     
         if !config.ssl.certificate.is_empty() {
             let cert = get_certificate(&config.ssl.certificate).await?;
             info!("Adding new trusted certificate {}", &config.ssl.certificate);
-            client_builder = client_builder.add_root_certificate(cert);
+            builder.add_root_certificate(cert);
         }
 
         if !config.ssl.keystore.is_empty() {
             let ks = get_identity(&config.ssl.keystore, &config.ssl.keystore_password).await?;
             info!("Adding new keystore {}", &config.ssl.keystore);
-            client_builder = client_builder.identity(ks);
+            builder.identity(ks);
         }
     }
 
-    Ok(client_builder.build()?)
+    Ok(builder.build()?)
+}
+
+//Error of sending a request along with its kind. Errors of the connector are found as the source of connect errors of hyper
+#[derive(Debug)]
+pub struct TransportError {
+    kind: ErrorKind,
+    message: String,
+    source: Option<Box<dyn Error + Send + Sync>>
+}
+
+impl TransportError {
+    pub fn new(kind: ErrorKind, message: String) -> Self {
+        TransportError { kind, message, source: None }
+    }
+
+    pub fn from_source<E: Into<Box<dyn Error + Send + Sync>>>(kind: ErrorKind, message: &str, source: E) -> Self {
+        TransportError { kind, message: message.to_owned(), source: Some(source.into()) }
+    }
+
+    fn timeout(timeout: time::Duration) -> Self {
+        TransportError::new(ErrorKind::Timeout, format!("request timed out after {} ms", timeout.as_millis()))
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{}: {}", self.message, source),
+            None => write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl Error for TransportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|source| source.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+//Bytes received for a response, bytes of the body are added once it is read
+#[derive(Clone, Debug, Default)]
+pub struct ByteCounter(Arc<AtomicU64>);
+
+impl ByteCounter {
+    fn add(&self, bytes: u64) {
+        self.0.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+//Bytes and connection timings of sending a request, summed up over redirects
+#[derive(Default)]
+struct Transfer {
+    connection: Option<ConnectionInfo>,
+    bytes_sent: u64,
+    bytes_received: ByteCounter,
+    timings: Timings
+}
+
+impl Transfer {
+    //Called once headers of a response are received, bytes of the request and response so far are taken from its connection
+    fn record(&mut self, response: &hyper::Response<HyperBody>) {
+        self.connection = response.extensions().get::<ConnectionInfo>().cloned();
+        if let Some(connection) = &self.connection {
+            let timings = connection.take_timings();
+            self.timings.dns += timings.dns;
+            self.timings.connect += timings.connect;
+            self.timings.tls += timings.tls;
+            self.bytes_sent += connection.take_bytes_sent();
+            self.bytes_received.add(connection.take_bytes_received());
+        }
+    }
 }
 
 impl HttpClient {
-    pub async fn execute(&self, request: Request) -> Result<(Response, u32), Box<dyn Error + Send + Sync>>  {  
+    pub async fn execute(&self, request: Request) -> Result<(Response, u64), Box<dyn Error + Send + Sync>>  {  
         //Initialising timestamps
         let start_time = time::Instant::now();
-        let resp = self.client.execute(request).await?;
-        let end_time = start_time.elapsed().as_millis() as u32;
+        let (resp, _) = self.send(request).await?;
+        let end_time = start_time.elapsed().as_micros() as u64;
        
         Ok((resp, end_time))
     }
+
+    //Request timeout covers redirects and reading of the response body, like with reqwest
+    async fn send(&self, mut request: Request) -> Result<(Response, Transfer), Box<dyn Error + Send + Sync>> {
+        let deadline = request.timeout().copied().or(self.timeout).map(|timeout| (Instant::now() + timeout, timeout));
+        let body = match request.body_mut().take() {
+            Some(body) => read_request_body(body).await?,
+            None => Bytes::new()
+        };
+
+        let mut transfer = Transfer::default();
+        let redirects = self.follow_redirects(&request, body, &mut transfer);
+        let response = match deadline {
+            Some((deadline, timeout)) => tokio::time::timeout_at(deadline, redirects).await.map_err(|_| TransportError::timeout(timeout))??,
+            None => redirects.await?
+        };
+
+        let response = response.map(|body| ResponseBody::new(body, transfer.connection.clone(), transfer.bytes_received.clone(), deadline).into_body());
+        Ok((Response::from(response), transfer))
+    }
+
+    async fn follow_redirects(&self, request: &Request, body: Bytes, transfer: &mut Transfer) -> Result<hyper::Response<HyperBody>, Box<dyn Error + Send + Sync>> {
+        let mut method = request.method().clone();
+        let mut url = request.url().clone();
+        let mut headers = request.headers().clone();
+        let mut body = body;
+
+        //Credentials in the url are sent as basic auth, also to the urls redirected to on the same host, as reqwest does
+        if !url.username().is_empty() || url.password().is_some() {
+            let username = percent_decode_str(url.username()).decode_utf8()?.to_string();
+            let password = percent_decode_str(url.password().unwrap_or_default()).decode_utf8()?.to_string();
+            let credentials = base64::encode(format!("{}:{}", username, password));
+            headers.entry(AUTHORIZATION).or_insert(HeaderValue::from_str(&format!("Basic {}", credentials))?);
+
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+        }
+
+        for _ in 0..MAX_REDIRECTS {
+            let response = self.transport.request(self.get_hyper_request(&method, &url, &headers, body.clone())?).await?;
+            transfer.record(&response);
+            self.store_cookies(response.headers(), &url);
+
+            let location = match get_redirect_url(&response, &url) {
+                Some(location) => location,
+                None => return Ok(response)
+            };
+
+            //Body is read so that the connection is reused
+            let status = response.status();
+            hyper::body::to_bytes(response.into_body()).await?;
+            if let Some(connection) = &transfer.connection {
+                transfer.bytes_received.add(connection.take_bytes_received());
+            }
+
+            if matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER) {
+                if method != Method::HEAD {
+                    method = Method::GET;
+                }
+
+                body = Bytes::new();
+                for name in [CONTENT_TYPE, CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING] {
+                    headers.remove(name);
+                }
+            }
+
+            //Credentials are not sent to other hosts
+            if location.host_str() != url.host_str() || location.port_or_known_default() != url.port_or_known_default() {
+                for name in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE] {
+                    headers.remove(name);
+                }
+            }
+
+            //Referer is not sent from https to http
+            if !(url.scheme() == "https" && location.scheme() == "http") {
+                let mut referer = url.clone();
+                let _ = referer.set_username("");
+                let _ = referer.set_password(None);
+                referer.set_fragment(None);
+                if let Ok(referer) = HeaderValue::from_str(referer.as_str()) {
+                    headers.insert(REFERER, referer);
+                }
+            }
+
+            debug!("Following redirect with status {} from {} to {}", status, url, location);
+            url = location;
+        }
+
+        Err(TransportError::new(ErrorKind::Other, format!("too many redirects, last one to {}", url)).into())
+    }
+
+    //Default accept header and cookies are added as reqwest does.
+    //Credentials of the proxy are sent with requests forwarded by it, https requests send them while opening the tunnel
+    fn get_hyper_request(&self, method: &Method, url: &Url, headers: &HeaderMap, body: Bytes) -> Result<hyper::Request<HyperBody>, Box<dyn Error + Send + Sync>> {
+        let mut url = url.clone();
+        let mut headers = headers.clone();
+        url.set_fragment(None);
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+
+        headers.entry(ACCEPT).or_insert_with(|| HeaderValue::from_static("*/*"));
+        if let Some(cookie) = self.cookies.as_ref().and_then(|cookies| cookies.cookies(&url)) {
+            headers.entry(COOKIE).or_insert(cookie);
+        }
+
+        let body = match body.is_empty() {
+            true => HyperBody::empty(),
+            false => HyperBody::from(body)
+        };
+
+        let uri: hyper::Uri = url.as_str().parse()?;
+        if uri.scheme_str() == Some("http") {
+            if let Some(authorization) = self.proxies.get(&uri).and_then(|proxy| proxy.authorization.clone()) {
+                headers.entry(PROXY_AUTHORIZATION).or_insert(authorization);
+            }
+        }
+
+        let mut request = hyper::Request::new(body);
+        *request.method_mut() = method.clone();
+        *request.uri_mut() = uri;
+        *request.headers_mut() = headers;
+        Ok(request)
+    }
+
+    fn store_cookies(&self, headers: &HeaderMap, url: &Url) {
+        if let Some(cookies) = &self.cookies {
+            cookies.set_cookies(&mut headers.get_all(SET_COOKIE).iter(), url);
+        }
+    }
+}
+
+//Streamed bodies, like of multipart forms, are read in memory as hyper does not take bodies of reqwest
+async fn read_request_body(body: reqwest::Body) -> Result<Bytes, reqwest::Error> {
+    match body.as_bytes() {
+        Some(bytes) => Ok(Bytes::copy_from_slice(bytes)),
+        None => Response::from(hyper::Response::new(body)).bytes().await
+    }
+}
+
+fn get_redirect_url(response: &hyper::Response<HyperBody>, url: &Url) -> Option<Url> {
+    match response.status() {
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER | StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => {
+            let location = response.headers().get(LOCATION)?.to_str().ok()?;
+            url.join(location).ok()
+        },
+        _ => None
+    }
+}
+
+//Body of the response which takes bytes read on the connection once it ends and errors if it is not read till the request timeout
+struct ResponseBody {
+    body: HyperBody,
+    connection: Option<ConnectionInfo>,
+    bytes_received: ByteCounter,
+    deadline: Option<(Pin<Box<Sleep>>, time::Duration)>
+}
+
+impl ResponseBody {
+    fn new(body: HyperBody, connection: Option<ConnectionInfo>, bytes_received: ByteCounter, deadline: Option<(Instant, time::Duration)>) -> Self {
+        let deadline = deadline.map(|(deadline, timeout)| (Box::pin(tokio::time::sleep_until(deadline)), timeout));
+        ResponseBody { body, connection, bytes_received, deadline }
+    }
+
+    async fn next_chunk(&mut self) -> Option<Result<Bytes, Box<dyn Error + Send + Sync>>> {
+        let chunk = match self.deadline.as_mut() {
+            Some((sleep, timeout)) => tokio::select! {
+                chunk = self.body.data() => chunk.map(|chunk| chunk.map_err(|err| err.into())),
+                _ = sleep => Some(Err(TransportError::timeout(*timeout).into()))
+            },
+            None => self.body.data().await.map(|chunk| chunk.map_err(|err| err.into()))
+        };
+
+        if !matches!(chunk, Some(Ok(_))) {
+            if let Some(connection) = &self.connection {
+                self.bytes_received.add(connection.take_bytes_received());
+            }
+        }
+
+        chunk
+    }
+
+    fn into_body(self) -> HyperBody {
+        HyperBody::wrap_stream(futures::stream::unfold(Some(self), |body| async move {
+            let mut body = body?;
+            match body.next_chunk().await {
+                Some(Ok(chunk)) => Some((Ok(chunk), Some(body))),
+                Some(Err(err)) => Some((Err(err), None)),
+                None => None
+            }
+        }))
+    }
+}
+
+//Outcome of executing a request, latency (in µs) is measured till response headers are received and even if no response is received.
+//Bytes are counted on the connection and timings of the connection are set only if it was set up for the request
+pub struct Execution {
+    pub result: Result<Response, Box<dyn Error + Send + Sync>>,
+    pub latency: u64,
+    pub attempt: u32,
+    pub bytes_sent: u64,
+    pub bytes_received: ByteCounter,
+    pub timings: Timings
+}
+
+impl Execution {
+    //Status is 0 when no response is received
+    pub fn status(&self) -> u16 {
+        match &self.result {
+            Ok(response) => response.status().as_u16(),
+            Err(_) => 0
+        }
+    }
+
+    pub fn error_kind(&self) -> Option<ErrorKind> {
+        match &self.result {
+            Ok(_) => None,
+            Err(err) => Some(get_error_kind(err.as_ref()))
+        }
+    }
+}
+
+//Reads the complete body of the response, returning it along with its size and time taken (in µs) to download it
+pub async fn read_body(mut response: Response) -> (String, u64, u64) {
+    let start_time = time::Instant::now();
+    let mut body = Vec::new();
+
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            Ok(None) => break,
+            Err(err) => {
+                error!("Error while reading response body: {}", err);
+                break
+            }
+        }
+    }
+
+    let download = start_time.elapsed().as_micros() as u64;
+    let size = body.len() as u64;
+    (String::from_utf8_lossy(&body).to_string(), size, download)
 }
 
 impl HttpClient {
-    pub fn get_default_async_client() -> Result<HttpClient, reqwest::Error> {
-        let client = Client::builder()
-            .build()?;
+    //Executes the request as per the retry policy, `on_retry` is called for every attempt which is being retried.
+    //Returns the final execution, request is executed only once if there is no retry policy
+    pub async fn execute_with_retry<F>(&self, request: Request, retry: Option<&Retry>, mut on_retry: F) -> Execution
+    where F: FnMut(&Execution) {
+        let max_attempts = retry.map_or(1, |retry| retry.max_attempts);
+        let mut request = request;
+        let mut attempt = 1;
+
+        loop {
+            let next_request = match attempt < max_attempts {
+                true => request.try_clone(),
+                false => None
+            };
+
+            let start_time = time::Instant::now();
+            let execution = match self.send(request).await {
+                Ok((response, transfer)) => Execution { result: Ok(response), latency: start_time.elapsed().as_micros() as u64, attempt,
+                    bytes_sent: transfer.bytes_sent, bytes_received: transfer.bytes_received, timings: transfer.timings },
+                Err(err) => Execution { result: Err(err), latency: start_time.elapsed().as_micros() as u64, attempt,
+                    bytes_sent: 0, bytes_received: ByteCounter::default(), timings: Timings::default() }
+            };
+
+            let retry = match retry {
+                Some(retry) => retry,
+                None => return execution
+            };
+
+            let error_kind = execution.error_kind();
+            let should_retry = match &error_kind {
+                Some(error_kind) => retry.errors.contains(error_kind),
+                None => retry.status_codes.contains(&execution.status())
+            };
 
-        Ok(HttpClient {client})
+            request = match (should_retry, next_request) {
+                (true, Some(next_request)) => next_request,
+                (true, None) if attempt < max_attempts => {
+                    warn!("Request body is a stream and cannot be cloned, hence it is not retried");
+                    return execution
+                },
+                _ => return execution
+            };
+
+            on_retry(&execution);
+
+            let delay = get_backoff_delay(retry, attempt);
+            debug!("Retrying request after {} ms as attempt {} failed with status {} / error {:?}", delay.as_millis(), attempt, execution.status(), error_kind);
+            tokio::time::sleep(delay).await;
+
+            attempt += 1;
+        }
+    }
+}
+
+//Timeouts and errors of the connector, like TLS handshake failures, are found in the source chain as hyper and reqwest wrap them
+pub fn get_error_kind(err: &(dyn Error + Send + Sync + 'static)) -> ErrorKind {
+    let mut source: Option<&(dyn Error + 'static)> = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<TransportError>() {
+            return err.kind.clone()
+        }
+
+        source = err.source();
+    }
+
+    ErrorKind::Other
+}
+
+//Delay before the next attempt, exponential backoff doubles the delay after every attempt
+//and jitter picks a random delay between 0 and the computed one
+fn get_backoff_delay(retry: &Retry, attempt: u32) -> time::Duration {
+    let backoff = &retry.backoff;
+    let delay = match backoff.backoff_type {
+        BackoffType::Fixed => backoff.delay,
+        BackoffType::Exponential => backoff.delay.saturating_mul(2u64.saturating_pow(attempt - 1))
+    };
+
+    let delay = delay.min(backoff.max_delay);
+    let delay = match backoff.jitter && delay > 0 {
+        true => rand::thread_rng().gen_range(0..=delay),
+        false => delay
+    };
+
+    time::Duration::from_millis(delay)
+}
+
+impl HttpClient {
+    pub fn get_default_async_client() -> Result<HttpClient, Box<dyn Error + Send + Sync>> {
+        Ok(HttpClient::build(TlsConnector::new()?, None, None, None, Proxies::from_env())?)
     } 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::RequestBuilder;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener, sync::mpsc};
+    use warp::{Filter, http::{HeaderMap as WarpHeaderMap, Method as WarpMethod}};
+
+    use std::{net::SocketAddr, sync::{Arc, atomic::{AtomicU32, Ordering}}};
+
+    use crate::protocol::http::*;
+
+    fn get_retry(yaml: &str) -> Retry {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_retry_defaults() {
+        let retry = get_retry("{}");
+        assert_eq!(retry.max_attempts, 3);
+        assert_eq!(retry.status_codes, vec![502, 503, 504]);
+        assert_eq!(retry.errors, vec![ErrorKind::Timeout, ErrorKind::Connect]);
+        assert_eq!(retry.backoff.backoff_type, BackoffType::Fixed);
+        assert_eq!(get_backoff_delay(&retry, 3).as_millis(), 100);
+    }
+
+    #[test]
+    fn test_exponential_backoff_delay() {
+        let retry = get_retry("backoff:\n  type: exponential\n  delay: 100\n  maxDelay: 500");
+        assert_eq!(get_backoff_delay(&retry, 1).as_millis(), 100);
+        assert_eq!(get_backoff_delay(&retry, 2).as_millis(), 200);
+        assert_eq!(get_backoff_delay(&retry, 3).as_millis(), 400);
+        assert_eq!(get_backoff_delay(&retry, 4).as_millis(), 500);
+        assert_eq!(get_backoff_delay(&retry, 100).as_millis(), 500);
+    }
+
+    #[test]
+    fn test_backoff_delay_with_jitter() {
+        let retry = get_retry("backoff:\n  delay: 100\n  jitter: true");
+        for _ in 0..20 {
+            assert!(get_backoff_delay(&retry, 1).as_millis() <= 100);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_retry() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let calls_clone = calls.clone();
+
+        //Fails twice with 503 and then succeeds
+        let route = warp::any().map(move || {
+            match calls_clone.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                _ => warp::http::StatusCode::OK
+            }
+        });
+
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = HttpClient::get_default_async_client().unwrap();
+        let request = client.get_client().get(format!("http://{}/", addr)).build().unwrap();
+        let retry = get_retry("backoff:\n  delay: 1");
+
+        let mut retried = vec![];
+        let execution = client.execute_with_retry(request, Some(&retry), |e| retried.push((e.attempt, e.status()))).await;
+
+        assert_eq!(execution.status(), 200);
+        assert_eq!(execution.attempt, 3);
+        assert_eq!(retried, vec![(1, 503), (2, 503)]);
+
+        //Final attempt is returned once max attempts are exhausted
+        calls.store(0, Ordering::SeqCst);
+        let request = client.get_client().get(format!("http://{}/", addr)).build().unwrap();
+        let retry = get_retry("maxAttempts: 2\nbackoff:\n  delay: 1");
+
+        let execution = client.execute_with_retry(request, Some(&retry), |_| ()).await;
+        assert_eq!(execution.status(), 503);
+        assert_eq!(execution.attempt, 2);
+
+        //Request is executed only once without retry policy
+        calls.store(0, Ordering::SeqCst);
+        let request = client.get_client().get(format!("http://{}/", addr)).build().unwrap();
+
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.status(), 503);
+        assert_eq!(execution.attempt, 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_retry_on_connect_error() {
+        let client = HttpClient::get_default_async_client().unwrap();
+        let request = client.get_client().get("http://127.0.0.1:1/").build().unwrap();
+        let retry = get_retry("maxAttempts: 2\nbackoff:\n  delay: 1");
+
+        let mut retried = vec![];
+        let execution = client.execute_with_retry(request, Some(&retry), |e| retried.push((e.attempt, e.status(), e.error_kind()))).await;
+
+        assert_eq!(execution.error_kind(), Some(ErrorKind::Connect));
+        assert_eq!(execution.attempt, 2);
+        assert_eq!(retried, vec![(1, 0, Some(ErrorKind::Connect))]);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_is_classified() {
+        let route = warp::any().and_then(|| async {
+            tokio::time::sleep(time::Duration::from_secs(2)).await;
+            Ok::<_, warp::Rejection>(warp::reply())
+        });
+
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config: model::Config = serde_yaml::from_str("requestTimeout: 100").unwrap();
+        assert_eq!(config.connect_timeout, 10000);
+
+        let client = HttpClient::new(&config).await.unwrap();
+        let request = client.get_client().get(format!("http://{}/", addr)).build().unwrap();
+
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.status(), 0);
+        assert_eq!(execution.error_kind(), Some(ErrorKind::Timeout));
+        assert!(execution.latency >= 100_000 && execution.latency < 2_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_tls_error_is_classified() {
+        //Server does not speak TLS so the handshake fails
+        let (addr, server) = warp::serve(warp::any().map(warp::reply)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = HttpClient::get_default_async_client().unwrap();
+        let request = client.get_client().get(format!("https://{}/", addr)).build().unwrap();
+
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.error_kind(), Some(ErrorKind::Tls));
+    }
+
+    #[tokio::test]
+    async fn test_bytes_and_timings() {
+        let (addr, server) = warp::serve(warp::any().map(|| "hello")).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = HttpClient::get_default_async_client().unwrap();
+        let url = format!("http://localhost:{}/post?a=1", addr.port());
+        let request = client.get_client().post(&url).header("content-type", "text/plain").body("hello").build().unwrap();
+
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert!(execution.timings.connect > 0);
+        assert_eq!(execution.timings.tls, 0);
+
+        //POST /post?a=1 HTTP/1.1 + content-type + content-length + accept + host headers + hello
+        let host = format!("host: localhost:{}\r\n", addr.port());
+        assert_eq!(execution.bytes_sent, (25 + 26 + 19 + 13 + host.len() + 2 + 5) as u64);
+
+        //HTTP/1.1 200 OK + content-type + content-length + date headers + hello, body is counted once it is read
+        let bytes_received = execution.bytes_received.clone();
+        let (body, _, _) = read_body(execution.result.unwrap()).await;
+        assert_eq!(body, "hello");
+        assert_eq!(bytes_received.get(), 17 + 41 + 19 + 37 + 2 + 5);
+
+        //Connection is reused for the next request, so it is not timed again
+        let request = client.get_client().get(&url).build().unwrap();
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.timings, Timings::default());
+        assert!(execution.bytes_sent > 0);
+    }
+
+    #[tokio::test]
+    async fn test_read_body() {
+        let route = warp::any().map(|| "a".repeat(100_000));
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = HttpClient::get_default_async_client().unwrap();
+        let request = client.get_client().get(format!("http://{}/", addr)).build().unwrap();
+
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        let bytes_received = execution.bytes_received.clone();
+
+        let (body, size, _) = read_body(execution.result.unwrap()).await;
+        assert_eq!(size, 100_000);
+        assert_eq!(body.len(), 100_000);
+        assert!(bytes_received.get() > 100_000 && bytes_received.get() < 100_200);
+    }
+
+    #[tokio::test]
+    async fn test_redirects_and_cookies() {
+        let login = warp::path("login").map(|| {
+            warp::http::Response::builder().status(302).header("location", "/orders").header("set-cookie", "session=abc; Path=/").body("")
+        });
+        let orders = warp::path("orders").and(warp::header::optional::<String>("cookie")).map(|cookie: Option<String>| cookie.unwrap_or_default());
+
+        let (addr, server) = warp::serve(login.or(orders)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config: model::Config = serde_yaml::from_str("handleCookies: true").unwrap();
+        let client = HttpClient::new(&config).await.unwrap();
+        let request = client.get_client().post(format!("http://{}/login", addr)).body("user=a").build().unwrap();
+
+        //Redirect is followed with GET and cookie set by the first response
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.status(), 200);
+        let (body, _, _) = read_body(execution.result.unwrap()).await;
+        assert_eq!(body, "session=abc");
+    }
+
+    //Server echoing the request received on /echo after redirects, credentials and cookies are handled by the client
+    fn start_redirect_server() -> SocketAddr {
+        let redirect = |status: u16, location: String| warp::http::Response::builder().status(status).header("location", location).body("");
+        let echo = warp::path("echo").and(warp::method()).and(warp::header::headers_cloned()).and(warp::body::bytes())
+            .map(|method: WarpMethod, headers: WarpHeaderMap, body: Bytes| {
+                let get = |name: &str| headers.get(name).map(|value| value.to_str().unwrap_or_default().to_owned());
+                format!("{} authorization={:?} cookie={:?} content-type={:?} referer={:?} accept={:?} body={}", method, get("authorization"),
+                        get("cookie"), get("content-type"), get("referer"), get("accept"), String::from_utf8_lossy(&body))
+            });
+        let status = warp::path!("redirect" / u16).map(move |status| redirect(status, String::from("/echo")));
+        let chain = warp::path!("chain" / u16).map(move |n: u16| match n {
+            0 => redirect(302, String::from("/echo")),
+            n => redirect(302, format!("/chain/{}", n - 1))
+        });
+        let login = warp::path("login").map(move || warp::http::Response::builder().status(302).header("location", "/echo")
+            .header("set-cookie", "session=abc; Path=/").body(""));
+        let cross_host = warp::path("cross").and(warp::header::<String>("host"))
+            .map(move |host: String| redirect(307, format!("http://{}/echo", host.replace("127.0.0.1", "localhost"))));
+
+        let (addr, server) = warp::serve(echo.or(status).or(chain).or(login).or(cross_host)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    type BuildRequest = Box<dyn Fn(&Client) -> RequestBuilder>;
+
+    #[tokio::test]
+    async fn test_same_as_reqwest() {
+        let addr = start_redirect_server();
+        let requests: Vec<BuildRequest> = vec![
+            Box::new(move |c| c.get(format!("http://{}/echo#fragment", addr))),
+            Box::new(move |c| c.post(format!("http://{}/redirect/301", addr)).header("content-type", "text/plain").body("a=1")),
+            Box::new(move |c| c.post(format!("http://{}/redirect/302", addr)).header("content-type", "text/plain").body("a=1")),
+            Box::new(move |c| c.put(format!("http://{}/redirect/303", addr)).body("a=1")),
+            Box::new(move |c| c.put(format!("http://{}/redirect/307", addr)).header("content-type", "text/plain").body("a=1")),
+            Box::new(move |c| c.post(format!("http://{}/redirect/308", addr)).body("a=1")),
+            Box::new(move |c| c.get(format!("http://user:p%40ss@{}/redirect/302", addr))),
+            Box::new(move |c| c.get(format!("http://{}/redirect/302", addr)).header("authorization", "Bearer abc").header("accept", "text/plain")),
+            Box::new(move |c| c.post(format!("http://user:pass@{}/cross", addr)).header("cookie", "a=1").body("a=1")),
+            Box::new(move |c| c.get(format!("http://{}/login", addr))),
+            Box::new(move |c| c.get(format!("http://{}/echo", addr))),
+            Box::new(move |c| c.get(format!("http://{}/chain/8", addr))),
+            Box::new(move |c| c.get(format!("http://{}/chain/9", addr)))
+        ];
+
+        let client = HttpClient::build(TlsConnector::new().unwrap(), None, None, Some(Jar::default()), Proxies::default()).unwrap();
+        let reqwest_client = Client::builder().cookie_store(true).no_proxy().build().unwrap();
+
+        for build in requests {
+            let request = build(client.get_client()).build().unwrap();
+            let url = request.url().to_string();
+            let execution = client.execute_with_retry(request, None, |_| ()).await;
+            let status = execution.status();
+            let actual = match execution.result {
+                Ok(response) => format!("{} {}", status, read_body(response).await.0),
+                Err(err) => format!("error {}", err.to_string().contains("too many redirects"))
+            };
+
+            let expected = match build(&reqwest_client).send().await {
+                Ok(response) => format!("{} {}", response.status().as_u16(), response.text().await.unwrap()),
+                Err(err) => format!("error {}", err.is_redirect())
+            };
+
+            assert_eq!(actual, expected, "Response to {} is not the same as with reqwest", url);
+        }
+    }
+
+    //Proxy which responds to every request with the response and sends the head of the request received
+    async fn start_proxy(response: &'static str) -> (SocketAddr, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut head = Vec::new();
+                let mut buf = [0; 1024];
+                while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => head.extend_from_slice(&buf[..read])
+                    }
+                }
+
+                let _ = sender.send(String::from_utf8_lossy(&head).to_lowercase());
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (addr, receiver)
+    }
+
+    fn get_proxy_client(http_proxy: &str, https_proxy: &str) -> HttpClient {
+        HttpClient::build(TlsConnector::new().unwrap(), None, None, None, Proxies::new(Some(http_proxy), Some(https_proxy), Some("localhost"))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_http_proxy() {
+        let (addr, mut heads) = start_proxy("HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok").await;
+        let client = get_proxy_client(&format!("http://user:pass@{}", addr), "");
+
+        //Request is sent to the proxy with the absolute url and credentials of the proxy
+        let request = client.get_client().get("http://example.com/orders?a=1").build().unwrap();
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.status(), 200);
+        assert!(execution.timings.connect > 0);
+
+        let head = heads.recv().await.unwrap();
+        assert!(head.starts_with("get http://example.com/orders?a=1 http/1.1\r\n"));
+        assert!(head.contains(&format!("proxy-authorization: basic {}\r\n", base64::encode("user:pass").to_lowercase())));
+
+        //Hosts in NO_PROXY are connected to directly
+        let request = client.get_client().get("http://localhost:1/").build().unwrap();
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.error_kind(), Some(ErrorKind::Connect));
+    }
+
+    #[tokio::test]
+    async fn test_https_proxy_tunnel() {
+        //TLS handshake is started once the tunnel is open, it fails as the proxy does not forward it
+        let (addr, mut heads) = start_proxy("HTTP/1.1 200 Connection established\r\n\r\n").await;
+        let client = get_proxy_client("", &format!("{}", addr));
+
+        let request = client.get_client().get("https://example.com/orders").build().unwrap();
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.error_kind(), Some(ErrorKind::Tls));
+        assert_eq!(heads.recv().await.unwrap(), "connect example.com:443 http/1.1\r\nhost: example.com:443\r\n\r\n");
+
+        //Tunnel refused by the proxy is a connect error
+        let (addr, _heads) = start_proxy("HTTP/1.1 407 Proxy Authentication Required\r\ncontent-length: 0\r\n\r\n").await;
+        let client = get_proxy_client("", &format!("{}", addr));
+
+        let request = client.get_client().get("https://example.com/orders").build().unwrap();
+        let execution = client.execute_with_retry(request, None, |_| ()).await;
+        assert_eq!(execution.error_kind(), Some(ErrorKind::Connect));
+        assert!(execution.result.unwrap_err().to_string().contains("407"));
+    }
+}
@@ -1,2 +1,4 @@
+pub mod connector;
 pub mod http;
+pub mod proxy;
 pub mod socket;
\ No newline at end of file
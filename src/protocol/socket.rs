@@ -49,6 +49,14 @@ impl <T> WebSocketStream<T> where T: AsyncRead + AsyncWrite + Unpin {
     }
 }
 
+//Local address of the connection, identifies the node to the hub
+pub fn get_local_address(websocket: &TTWebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>) -> Option<String> {
+    match websocket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream.local_addr().ok().map(|address| address.to_string()),
+        _ => None
+    }
+}
+
 pub async fn connect(url: String) -> Result<TTWebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>, Box<dyn std::error::Error>> {
      match connect_async(url::Url::parse(&url).unwrap()).await {
          Ok((ws, _)) => Ok(ws),
@@ -1,216 +1,430 @@
+pub mod aggregator;
+pub mod binary;
+pub mod compare;
 pub mod csv;
+pub mod dashboard;
+pub mod histogram;
+pub mod html;
+pub mod jsonl;
+pub mod json;
+pub mod junit;
+pub mod markdown;
+pub mod metrics;
+pub mod run;
+pub mod sink;
 pub mod stats;
+pub mod summary;
+pub mod threshold;
+pub mod timeline;
+pub mod writer;
 
-use chrono::{DateTime, Duration};
+use chrono::{DateTime, FixedOffset};
 use prettytable::{Table, row, cell};
-use rayon::prelude::*;
-use rustc_hash::FxHashSet as HashSet;
-use log::error;
-
-use crate::report::stats::Stats;
-use crate::data::{self, DataProvider};
+use rustc_hash::FxHashMap as HashMap;
+use log::info;
+use serde::Serialize;
+use tokio::fs;
+
+use crate::report::{
+    aggregator::{Aggregate, GroupBy, RequestAggregate}, 
+    histogram::{LatencyHistograms, LatencySummary}, 
+    run::Run,
+    stats::Stats, 
+    timeline::{TimeBucket, Timeline}
+};
+use crate::model::Thresholds;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f %z";
+
+//Summary of a request, latencies are in µs
+#[derive(Serialize)]
+pub struct RequestSummary {
+    pub name: String,
+    pub hits: usize,
+    pub throughput: f32,
+    pub latency: LatencySummary,
+    pub errors: usize,
+
+    #[serde(rename = "errorRate")]
+    pub error_rate: f32
+}
 
-pub async fn display(report_file: &str) -> Result<(), Box<dyn std::error::Error>> {
+//Summary of the execution, token requests are not included
+#[derive(Serialize)]
+pub struct ReportSummary {
+    #[serde(rename = "executionTime")]
+    pub execution_time: i64,
 
-    let mut data_provider = get_data_provider(report_file).await?;
+    #[serde(rename = "totalHits")]
+    pub total_hits: usize,
 
-    let stats= get_stats(&mut data_provider).await?;
-    let names = get_request_name_set(&stats);
+    pub throughput: f32,
 
-    let mut table = Table::new();
-    table.add_row(row![FY => "Request", "Total Hits", "Hits/s", "Min", "Avg", "Max", "90%", "95%", "99%", "Errors", "Error Rate"]);
-        
-    let mut total_hits = 0;
-    let mut total_errors = 0;
+    #[serde(rename = "totalErrors")]
+    pub total_errors: usize,
 
-    let et = get_execution_time(&stats);
+    #[serde(rename = "errorRate")]
+    pub error_rate: f32,
 
-    for name in names {
-        let name_filter: Vec<&Stats> = filter_stats_by_name(&stats, name);
-        let num = name_filter.len();
+    pub requests: Vec<RequestSummary>,
 
-        let latencies: Vec<u32> = get_sorted_latencies(&name_filter);
+    //Stats of all the requests in every interval, only if interval is provided
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub intervals: Vec<TimeBucket>,
 
-        let (min, max) = (latencies[0], latencies[num-1]);
-        let (pc_90, pc_95, pc_99) = get_all_percentiles(&latencies);
+    //Run of the report file, reports of older versions do not have it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<Run>
+}
 
-        let avg= sum_of_latencies(&latencies) / num;
-        let tput= num as f32 / et as f32;
-        let errors = get_error_count(&name_filter);
-        let error_rate= errors as f32 * 100.0 / num as f32;
+//Generates the report in given format, text report is printed to console while others are written to the output file.
+//Stats are also reported for every interval (like 10s) if it is provided
+pub async fn generate(report_file: &str, format: &str, output_file: &str, thresholds_file: &str, interval: &str, group_by: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let interval = match interval.is_empty() {
+        true => 0,
+        false => timeline::parse_interval(interval)?
+    };
+
+    let group_by = &GroupBy::parse(group_by)?;
+    if format.is_empty() || format == "text" {
+        return display(report_file, interval, group_by).await
+    }
 
-        table.add_row(row![&name, &num.to_string(), &tput.to_string(), &min.to_string(), 
-                            &avg.to_string(), &max.to_string(), &pc_90.to_string(), &pc_95.to_string(), 
-                            &pc_99.to_string(), &errors.to_string(), &error_rate.to_string()]);
+    let thresholds = get_thresholds(thresholds_file).await?;
+    let run = run::read(report_file).await;
+    let get_summary = |aggregate: &Aggregate| ReportSummary { run: run.clone(), ..get_report_summary(aggregate) };
+
+    let (content, extension) = match format {
+        "html" => (html::render(&aggregate_for_html(report_file, interval, group_by).await?), "html"),
+        "json" => (json::render(&get_summary(&aggregate(report_file, interval, group_by).await?))?, "json"),
+        "junit" => (junit::render(&get_summary(&aggregate(report_file, 0, group_by).await?), &thresholds), "xml"),
+        "markdown" => (markdown::render(&get_summary(&aggregate(report_file, interval, group_by).await?), &thresholds), "md"),
+        _ => return Err(format!("Invalid report format {}", format).into())
+    };
+
+    let output_file = match output_file.is_empty() {
+        true => format!("report.{}", extension),
+        false => output_file.to_owned()
+    };
+
+    fs::write(&output_file, content).await?;
+    info!("Report written to {}", output_file);
+    Ok(())
+}
 
-        total_hits += num;
-        total_errors += errors;
+pub async fn display(report_file: &str, interval: u64, group_by: &GroupBy) -> Result<(), Box<dyn std::error::Error>> {
+    let aggregate = aggregate(report_file, interval, group_by).await?;
+    if let Some(run) = run::read(report_file).await {
+        println!("{}", get_run_description(&run));
     }
 
-    table.printstd();
+    let et = aggregate.get_execution_time();
+    let summaries = aggregate.get_request_summaries();
+    print_stats_table(&summaries);
+
+    let (total_hits, total_errors) = get_totals(&summaries);
     print_summary_table(et, total_hits, total_errors);
 
+    if interval > 0 {
+        print_interval_table(&aggregate.get_time_buckets());
+    }
+
+    if aggregate.histograms.is_corrected() {
+        print_corrected_latency_table(&aggregate.histograms);
+    }
+
+    let requests = aggregate.get_requests();
+
+    //Older reports do not have the breakdown
+    if requests.iter().any(|(_, r)| r.ttfb > 0 || r.bytes_received > 0) {
+        print_phase_table(&requests);
+    }
+
+    if total_errors > 0 {
+        print_error_table(&requests);
+    }
+
+    if requests.iter().any(|(_, r)| r.get_retries() > 0) {
+        print_retry_table(&requests);
+    }
+
+    if !aggregate.token_requests.is_empty() {
+        println!("Token requests (not included in above summary)");
+        print_stats_table(&aggregate.get_token_request_summaries());
+    }
+
     Ok(())
 }
 
-async fn get_data_provider(report_file: &str) -> Result<DataProvider, Box<dyn std::error::Error>> {
-    match data::DataProvider::new(report_file).await {
-        Some(data_provider) => Ok(data_provider),
-        None => {
-            error!("Unable to initialize data provider for {}", report_file);
-            return Err("Unable to initialize data provider".into())
-        }
+//Interval stats need the start of execution, which is known only after a pass over the report file
+async fn aggregate(report_file: &str, interval: u64, group_by: &GroupBy) -> Result<Aggregate, Box<dyn std::error::Error>> {
+    let timeline = match interval {
+        0 => None,
+        _ => aggregator::get_time_range(report_file).await?
+                .get_start_time()
+                .map(|start_time| Timeline::new(start_time, interval))
+    };
+
+    aggregator::aggregate(report_file, timeline, group_by).await
+}
+
+//Html report always has charts over time, interval is chosen as per execution time if it is not provided
+async fn aggregate_for_html(report_file: &str, interval: u64, group_by: &GroupBy) -> Result<Aggregate, Box<dyn std::error::Error>> {
+    let time_range = aggregator::get_time_range(report_file).await?;
+    let interval = match interval {
+        0 => html::get_interval(time_range.get_execution_time()),
+        interval => interval
+    };
+
+    aggregator::aggregate(report_file, time_range.get_start_time().map(|start_time| Timeline::new(start_time, interval)), group_by).await
+}
+
+async fn get_thresholds(thresholds_file: &str) -> Result<Thresholds, Box<dyn std::error::Error>> {
+    if thresholds_file.is_empty() {
+        return Ok(Thresholds::default())
     }
+
+    let content = fs::read_to_string(thresholds_file).await?;
+    Ok(serde_yaml::from_str(&content)?)
 }
 
-async fn get_stats(data_provider: &mut DataProvider) -> Result<Vec<Stats>, Box<dyn std::error::Error>> {
-    let mut stats: Vec<Stats> = data_provider.get_records_as().await.unwrap();
-    stats.sort_by_key(|k| k.timestamp.clone()); //required for distributed execution
-    Ok(stats)
+//Intervals are not computed if interval is 0
+pub fn get_report_summary(aggregate: &Aggregate) -> ReportSummary {
+    let execution_time = aggregate.get_execution_time();
+    let requests = aggregate.get_request_summaries();
+    let (total_hits, total_errors) = get_totals(&requests);
+
+    ReportSummary {
+        execution_time,
+        total_hits,
+        throughput: get_throughput(total_hits, execution_time),
+        total_errors,
+        error_rate: get_error_rate(total_errors, total_hits),
+        requests,
+        intervals: aggregate.get_time_buckets(),
+        run: None
+    }
 }
 
-fn get_request_name_set(stats: &[Stats]) -> HashSet<&str> {
-    stats.iter()
-        .map(|s| s.name.as_str())
-        .collect()
+//Run id with start and end time and tags, like run 6f1c3a52-.. from 2021-01-01T10:00:00+05:30 to 2021-01-01T10:05:00+05:30 (env=qa)
+pub fn get_run_description(run: &Run) -> String {
+    let mut description = format!("Run {} from {} to {}", run.id, run.started_at, if run.ended_at.is_empty() { "-" } else { &run.ended_at });
+    if !run.tags.is_empty() {
+        let tags = run.tags.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<String>>().join(", ");
+        description.push_str(&format!(" ({})", tags));
+    }
+
+    description
 }
 
-fn filter_stats_by_name<'a>(stats: &'a [Stats], name: &str) -> Vec<&'a Stats> {
-    stats.par_iter()
-        .filter(|s| s.name == name)
+//Percentiles are computed from an HDR histogram of every request
+pub fn get_request_summaries(requests: &HashMap<String, RequestAggregate>, histograms: &LatencyHistograms, et: i64) -> Vec<RequestSummary> {
+    histograms.get_names().into_iter()
+        .filter_map(|name| {
+            let request = requests.get(name)?;
+            let (hits, errors) = (request.hits, request.get_errors());
+
+            Some(RequestSummary {
+                name: name.to_owned(),
+                hits,
+                throughput: get_throughput(hits, et),
+                latency: histograms.get_summary(name).unwrap(),
+                errors,
+                error_rate: get_error_rate(errors, hits)
+            })
+        })
         .collect()
 }
 
-fn get_sorted_latencies(stats: &[&Stats]) -> Vec<u32> {
-    let mut latencies = stats.par_iter()
-    .map(|s| s.latency)
-    .collect::<Vec<u32>>();
+//Throughput is 0 when execution time is 0, like when all the stats are of the same second, so that it is not infinite
+pub fn get_throughput(hits: usize, et: i64) -> f32 {
+    match et {
+        et if et <= 0 => 0.0,
+        _ => hits as f32 / et as f32
+    }
+}
 
-    latencies.par_sort_unstable();
-    latencies
+pub fn get_error_rate(errors: usize, hits: usize) -> f32 {
+    match hits {
+        0 => 0.0,
+        _ => errors as f32 * 100.0 / hits as f32
+    }
 }
 
-fn get_error_count(stats: &[&Stats]) -> usize {
-    stats.par_iter()
-        .filter(|s| s.status >= 400)
-        .count()
+//Total hits and errors
+pub fn get_totals(summaries: &[RequestSummary]) -> (usize, usize) {
+    summaries.iter().fold((0, 0), |(hits, errors), s| (hits + s.hits, errors + s.errors))
 }
 
-fn sum_of_latencies(latencies: &[u32]) -> usize {
-    latencies.par_iter().sum::<u32>() as usize
+//Latencies are in ms
+fn print_stats_table(summaries: &[RequestSummary]) {
+    let mut table = Table::new();
+    table.add_row(row![FY => "Request", "Total Hits", "Hits/s", "Min", "Avg", "Max", "90%", "95%", "99%", "99.9%", "99.99%", "Errors", "Error Rate"]);
+
+    for s in summaries {
+        table.add_row(row![&s.name, &s.hits.to_string(), &s.throughput.to_string(), &histogram::format_latency(s.latency.min), 
+                            &histogram::format_latency(s.latency.mean), &histogram::format_latency(s.latency.max), 
+                            &histogram::format_latency(s.latency.pc_90), &histogram::format_latency(s.latency.pc_95), 
+                            &histogram::format_latency(s.latency.pc_99), &histogram::format_latency(s.latency.pc_99_9), 
+                            &histogram::format_latency(s.latency.pc_99_99), &s.errors.to_string(), &s.error_rate.to_string()]);
+    }
+
+    table.printstd();
 }
 
-fn get_percentile(sorted_vector: &[u32], p: usize) -> u32 {
-    let len = sorted_vector.len();
+//Stats of all the requests in every interval along with the threads running, latencies are in ms
+fn print_interval_table(buckets: &[TimeBucket]) {
+    let mut table = Table::new();
+    table.add_row(row![FC => "Interval", "Threads", "Hits", "Hits/s", "Avg", "50%", "90%", "95%", "99%", "Errors", "Error Rate"]);
 
-    if len == 0 {
-        return 0
-    }
+    for b in buckets {
+        let latency = |f: fn(&LatencySummary) -> u64| b.latency.as_ref().map_or(String::from("-"), |l| histogram::format_latency(f(l)));
+        let name = format!("{} - {}", dashboard::format_duration(b.start), dashboard::format_duration(b.end));
 
-    match p*len/100 {
-        0 => sorted_vector[0],
-        _ => sorted_vector[(p*len/100)-1]
+        table.add_row(row![&name, &b.threads.to_string(), &b.hits.to_string(), &b.throughput.to_string(), &latency(|l| l.mean),
+                            &latency(|l| l.pc_50), &latency(|l| l.pc_90), &latency(|l| l.pc_95), &latency(|l| l.pc_99),
+                            &b.errors.to_string(), &b.error_rate.to_string()]);
     }
+
+    table.printstd();
 }
 
-fn get_all_percentiles(times: &[u32]) -> (u32, u32, u32) {
-    (get_percentile(times, 90), get_percentile(times, 95), get_percentile(times, 99))
+//Printed at the end of execution from the histograms maintained while consuming stats
+pub fn print_latency_table(histograms: &LatencyHistograms) {
+    if histograms.is_empty() {
+        return
+    }
+
+    let mut table = Table::new();
+    table.add_row(row![FY => "Request", "Total Hits", "Min", "Avg", "Max", "90%", "95%", "99%", "99.9%", "99.99%"]);
+
+    for name in histograms.get_names() {
+        let summary = histograms.get_summary(name).unwrap();
+        table.add_row(row![&name, &summary.count.to_string(), &histogram::format_latency(summary.min), 
+                            &histogram::format_latency(summary.mean), &histogram::format_latency(summary.max), 
+                            &histogram::format_latency(summary.pc_90), &histogram::format_latency(summary.pc_95), 
+                            &histogram::format_latency(summary.pc_99), &histogram::format_latency(summary.pc_99_9), 
+                            &histogram::format_latency(summary.pc_99_99)]);
+    }
+
+    table.printstd();
+
+    if histograms.is_corrected() {
+        print_corrected_latency_table(histograms);
+    }
 }
 
-fn print_summary_table(et: i64, total_hits: usize, total_errors: usize) {
-    let mut sum_table = Table::new();
-    sum_table.add_row(row![FG => "Total Execution Time (in secs)", "Total Hits", "Hits/s", "Total Errors", "Error Rate"]);
+//Corrected latencies include the samples for requests which could not be sent as per pacing while waiting for a delayed response
+fn print_corrected_latency_table(histograms: &LatencyHistograms) {
+    println!("Latencies corrected for coordinated omission");
 
-    let ttput =  total_hits as f32 / et as f32;
-    let err_rate = total_errors as f32 * 100.0 / total_hits as f32;
+    let mut table = Table::new();
+    table.add_row(row![FM => "Request", "Samples", "Avg", "Max", "90%", "95%", "99%", "99.9%", "99.99%"]);
+
+    for name in histograms.get_names() {
+        if let Some(summary) = histograms.get_corrected_summary(name) {
+            table.add_row(row![&name, &summary.count.to_string(), &histogram::format_latency(summary.mean), 
+                                &histogram::format_latency(summary.max), &histogram::format_latency(summary.pc_90), 
+                                &histogram::format_latency(summary.pc_95), &histogram::format_latency(summary.pc_99), 
+                                &histogram::format_latency(summary.pc_99_9), &histogram::format_latency(summary.pc_99_99)]);
+        }
+    }
 
-    sum_table.add_row(row![&et.to_string(), &total_hits.to_string(), &ttput.to_string(), &total_errors.to_string(), &err_rate.to_string()]);
-    sum_table.printstd();
+    table.printstd();
 }
 
-fn get_execution_time(stats: &[Stats]) -> i64 {
-    if stats.len() == 0 {
-        return 0
+fn print_phase_table(requests: &[(&str, &RequestAggregate)]) {
+    let mut table = Table::new();
+    table.add_row(row![FB => "Request", "Avg DNS", "Avg Connect", "Avg TLS", "Avg TTFB", "Avg Download", "Avg Bytes Sent", "Avg Bytes Received", "Total Bytes Sent", "Total Bytes Received"]);
+
+    for (name, r) in requests {
+        let num = r.attempts as u64;
+        table.add_row(row![name, &histogram::format_latency(r.dns / num), &histogram::format_latency(r.connect / num), &histogram::format_latency(r.tls / num), 
+                            &histogram::format_latency(r.ttfb / num), &histogram::format_latency(r.download / num), &(r.bytes_sent / num).to_string(), 
+                            &(r.bytes_received / num).to_string(), &r.bytes_sent.to_string(), &r.bytes_received.to_string()]);
     }
 
-    let format = "%Y-%m-%d %H:%M:%S%.6f %z";
-    let starttime = DateTime::parse_from_str(&stats[0].timestamp, format).unwrap() - Duration::milliseconds(stats[0].latency as i64);
-    let endtime = DateTime::parse_from_str(&stats[stats.len()-1].timestamp, format).unwrap();
-    endtime.signed_duration_since(starttime).num_seconds()
+    table.printstd();
 }
 
-#[test]
-fn test_get_percentile() {
-    let times = &[200, 203, 210, 256, 315]; //must be sorted slice
-    assert_eq!(get_percentile(times, 10), 200);
-    assert_eq!(get_percentile(times, 59), 203);
-    assert_eq!(get_percentile(times, 60), 210);
-    assert_eq!(get_percentile(times, 90), 256);
-    assert_eq!(get_percentile(&[], 51), 0);
+const ERROR_CATEGORIES: [&str; 7] = ["4xx", "5xx", "timeout", "connect", "tls", "assertion", "other"];
+
+fn print_error_table(requests: &[(&str, &RequestAggregate)]) {
+    let mut table = Table::new();
+    table.add_row(row![FR => "Request", "HTTP 4xx", "HTTP 5xx", "Timeout", "Connect", "TLS", "Assertion", "Other"]);
+
+    for (name, r) in requests {
+        let breakdown = &r.error_breakdown;
+        table.add_row(row![&name, &breakdown[0].to_string(), &breakdown[1].to_string(), &breakdown[2].to_string(), 
+                            &breakdown[3].to_string(), &breakdown[4].to_string(), &breakdown[5].to_string(), &breakdown[6].to_string()]);
+    }
+
+    table.printstd();
 }
 
-#[test]
-fn test_get_all_percentile() {
-    let times = &[1,2,3,4,5,6,7,8,9,10]; //must be sorted slice
-    assert_eq!(get_all_percentiles(times), (9,9,9));
+//Assertion failures are reported as such irrespective of the status
+fn get_error_category(stats: &Stats) -> Option<String> {
+    match (&stats.error_kind, stats.status) {
+        (Some(error_kind), _) => Some(error_kind.to_string()),
+        (None, 0) => Some(String::from("other")),
+        (None, status) if status >= 500 => Some(String::from("5xx")),
+        (None, status) if status >= 400 => Some(String::from("4xx")),
+        _ => None
+    }
 }
 
-#[test]
-fn test_get_execution_time() {
-    //Latency of 1st element is subtracted from its timestamp to get start time 
-    //so if 1st element takes 1 sec, the execution time should be 2+1
-    let stats1 = Stats::new("name1", 200, 1000, 1); 
-    std::thread::sleep(std::time::Duration::from_secs(2));
-    let stats2 = Stats::new("name2", 200, 150, 1);
-    assert_eq!(get_execution_time(&[stats1, stats2]), 3);
+//Every execution of a request starts with attempt 1, so success rates are computed against the executions
+fn print_retry_table(requests: &[(&str, &RequestAggregate)]) {
+    let mut table = Table::new();
+    table.add_row(row![FC => "Request", "Executions", "Attempts", "Retries", "First Try Success Rate", "Eventual Success Rate"]);
 
-    //Check negative 
-    let stats1 = Stats::new("name1", 200, 0, 1);
-    std::thread::sleep(std::time::Duration::from_secs(1));
-    let stats2 = Stats::new("name2", 200, 0, 1);
-    assert_eq!(get_execution_time(&[stats2, stats1]), -1);
+    for (name, r) in requests {
+        let (first_try_rate, eventual_rate) = r.get_success_rates();
+        table.add_row(row![name, &r.hits.to_string(), &r.attempts.to_string(), &r.get_retries().to_string(), 
+                            &first_try_rate.to_string(), &eventual_rate.to_string()]);
+    }
 
-    //check empty
-    assert_eq!(get_execution_time(&[]), 0);
+    table.printstd();
 }
 
-#[test]
-fn test_get_request_name_set() {
-    let stats = vec![Stats::new("name1", 0, 0, 0), Stats::new("name1", 0, 0, 0), Stats::new("name2", 0, 0, 0)];
-    let names = get_request_name_set(&stats);
-    assert_eq!(names.len(), 2);
+//Status is 0 when no response was received
+pub fn is_error(stats: &Stats) -> bool {
+    stats.status >= 400 || stats.status == 0 || stats.error_kind.is_some()
+}
+
+fn print_summary_table(et: i64, total_hits: usize, total_errors: usize) {
+    let mut sum_table = Table::new();
+    sum_table.add_row(row![FG => "Total Execution Time (in secs)", "Total Hits", "Hits/s", "Total Errors", "Error Rate"]);
+
+    let ttput = get_throughput(total_hits, et);
+    let err_rate = get_error_rate(total_errors, total_hits);
 
-    let names = get_request_name_set(&[]);
-    assert_eq!(names.len(), 0);
+    sum_table.add_row(row![&et.to_string(), &total_hits.to_string(), &ttput.to_string(), &total_errors.to_string(), &err_rate.to_string()]);
+    sum_table.printstd();
 }
 
-#[test]
-fn test_filter_stats_by_name() {
-    let stats = vec![Stats::new("name1", 0, 0, 0), Stats::new("name1", 0, 0, 0), Stats::new("name2", 0, 0, 0)];
-    assert_eq!(filter_stats_by_name(&stats, "name1").len(), 2);
-    assert_eq!(filter_stats_by_name(&stats, "name2").len(), 1);
-    assert_eq!(filter_stats_by_name(&stats, "name3").len(), 0);
-    assert_eq!(filter_stats_by_name(&[], "name2").len(), 0);
+pub fn parse_timestamp(timestamp: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()
 }
 
 #[test]
-fn test_get_sorted_latencies() {
-    let stats1 = Stats::new("name1", 0, 250, 0);
-    let stats2 = Stats::new("name1", 0, 100, 0);
-    let stats3 = Stats::new("name1", 0, 300, 0);
-    let stats4 = Stats::new("name1", 0, 50, 0);
-    let stats = vec![&stats1, &stats2, &stats3, &stats4];
-
-    assert_eq!(get_sorted_latencies(&stats), vec![stats4.latency,stats2.latency,stats1.latency,stats3.latency]);
+fn test_get_request_summaries() {
+    let stats = vec![Stats::new("name1", 200, 250, 0), Stats::new("name1", 399, 100, 0), Stats::new("name1", 400, 300, 0),
+        Stats::new("name1", 504, 50, 0), Stats::new("name2", 200, 100, 0)];
+
+    let summaries = Aggregate::from_stats(&stats, 0).get_request_summaries();
+    assert_eq!(summaries.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>(), vec!["name1", "name2"]);
+    assert_eq!((summaries[0].hits, summaries[0].errors, summaries[0].error_rate), (4, 2, 50.0));
+    assert_eq!(get_totals(&summaries), (5, 2));
 }
 
 #[test]
-fn test_get_error_count() {
-    let stats1 = Stats::new("name1", 200, 250, 0);
-    let stats2 = Stats::new("name1", 399, 100, 0);
-    let stats3 = Stats::new("name1", 400, 300, 0);
-    let stats4 = Stats::new("name1", 504, 50, 0);
-    let stats = vec![&stats1, &stats2, &stats3, &stats4];
-
-    assert_eq!(get_error_count(&stats), 2);
-}
\ No newline at end of file
+fn test_rates_without_execution_time_or_hits() {
+    let summary = get_report_summary(&Aggregate::from_stats(&[Stats::new("name1", 500, 250, 0)], 0));
+    assert_eq!(summary.execution_time, 0);
+    assert_eq!((summary.throughput, summary.error_rate), (0.0, 100.0));
+    assert_eq!(summary.requests[0].throughput, 0.0);
+
+    let summary = get_report_summary(&Aggregate::from_stats(&[], 0));
+    assert_eq!((summary.total_hits, summary.throughput, summary.error_rate), (0, 0.0, 0.0));
+}
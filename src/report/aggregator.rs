@@ -0,0 +1,665 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use csv_async::{AsyncReaderBuilder, StringRecord, Trim};
+use futures::StreamExt;
+use log::{error, info};
+use rustc_hash::FxHashMap as HashMap;
+use tokio::{
+    fs::{self, File},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader, SeekFrom},
+    task
+};
+
+use std::{collections::BTreeMap, sync::Arc, thread};
+
+use crate::report::{
+    self,
+    ERROR_CATEGORIES,
+    RequestSummary,
+    binary,
+    histogram::LatencyHistograms,
+    stats::Stats,
+    timeline::{TimeBucket, Timeline}
+};
+
+//Report files smaller than this are read in a single chunk
+const MIN_CHUNK_SIZE: u64 = 1024 * 1024;
+
+//Fields of stats the report can be grouped by
+pub const GROUP_BY_FIELDS: [&str; 10] = ["name", "scenario", "thread", "iteration", "method", "url", "status", "error-kind", "error-message", "node"];
+
+//Stats are grouped by request name unless other fields are given as comma separated list.
+//Values of multiple fields are joined with ' | ' and empty values are shown as '-'
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupBy {
+    fields: Vec<&'static str>
+}
+
+impl Default for GroupBy {
+    fn default() -> Self {
+        GroupBy { fields: vec!["name"] }
+    }
+}
+
+impl GroupBy {
+    pub fn parse(group_by: &str) -> Result<GroupBy, String> {
+        if group_by.trim().is_empty() {
+            return Ok(GroupBy::default())
+        }
+
+        let fields = group_by.split(',')
+            .map(|field| GROUP_BY_FIELDS.iter().find(|f| **f == field.trim()).copied()
+                .ok_or_else(|| format!("Invalid group by field {}, should be one of {}", field.trim(), GROUP_BY_FIELDS.join(", "))))
+            .collect::<Result<Vec<&str>, String>>()?;
+
+        Ok(GroupBy { fields })
+    }
+
+    fn is_default(&self) -> bool {
+        self.fields == ["name"]
+    }
+
+    fn get_key(&self, stats: &Stats) -> String {
+        self.fields.iter()
+            .map(|field| {
+                let value = match *field {
+                    "name" => stats.name.clone(),
+                    "scenario" => stats.scenario.clone(),
+                    "thread" => stats.thread_id.to_string(),
+                    "iteration" => stats.iteration.to_string(),
+                    "method" => stats.method.clone(),
+                    "url" => stats.url.clone(),
+                    "status" => stats.status.to_string(),
+                    "error-kind" => report::get_error_category(stats).unwrap_or_default(),
+                    "error-message" => stats.error_message.clone(),
+                    _ => stats.node_id.clone()
+                };
+
+                match value.is_empty() {
+                    true => String::from("-"),
+                    false => value
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" | ")
+    }
+}
+
+//Counters of a request, ttfb, download, dns, connect and tls are sums in µs of every attempt.
+//Hits are executions of the request, every execution starts with attempt 1 and retries are counted only in attempts
+#[derive(Default, Clone)]
+pub struct RequestAggregate {
+    pub hits: usize,
+    pub attempts: usize,
+
+    //Count of errors of every attempt for every category in the order of ERROR_CATEGORIES
+    pub error_breakdown: [usize; ERROR_CATEGORIES.len()],
+
+    pub first_try_successes: usize,
+    pub successes: usize,
+    pub ttfb: u64,
+    pub download: u64,
+    pub dns: u64,
+    pub connect: u64,
+    pub tls: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub statuses: BTreeMap<u16, usize>
+}
+
+impl RequestAggregate {
+    fn record(&mut self, stats: &Stats) {
+        let is_error = report::is_error(stats);
+
+        self.attempts += 1;
+        self.successes += !is_error as usize;
+
+        if let Some(index) = report::get_error_category(stats).and_then(|category| ERROR_CATEGORIES.iter().position(|c| *c == category)) {
+            self.error_breakdown[index] += 1;
+        }
+
+        if stats.attempt == 1 {
+            self.hits += 1;
+            self.first_try_successes += !is_error as usize;
+        }
+
+        self.ttfb += stats.ttfb;
+        self.download += stats.download;
+        self.dns += stats.dns;
+        self.connect += stats.connect;
+        self.tls += stats.tls;
+        self.bytes_sent += stats.bytes_sent;
+        self.bytes_received += stats.bytes_received;
+        *self.statuses.entry(stats.status).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: RequestAggregate) {
+        self.hits += other.hits;
+        self.attempts += other.attempts;
+        self.successes += other.successes;
+        self.error_breakdown.iter_mut().zip(other.error_breakdown).for_each(|(count, other)| *count += other);
+        self.first_try_successes += other.first_try_successes;
+        self.ttfb += other.ttfb;
+        self.download += other.download;
+        self.dns += other.dns;
+        self.connect += other.connect;
+        self.tls += other.tls;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+
+        for (status, count) in other.statuses {
+            *self.statuses.entry(status).or_default() += count;
+        }
+    }
+
+    pub fn get_retries(&self) -> usize {
+        self.attempts.saturating_sub(self.hits)
+    }
+
+    //Requests are retried only on errors, so an execution succeeds if any of its attempts succeeds and fails otherwise
+    pub fn get_errors(&self) -> usize {
+        self.hits.saturating_sub(self.successes)
+    }
+
+    pub fn get_success_rates(&self) -> (f32, f32) {
+        match self.hits {
+            0 => (0.0, 0.0),
+            hits => (self.first_try_successes as f32 * 100.0 / hits as f32, self.successes as f32 * 100.0 / hits as f32)
+        }
+    }
+}
+
+//Timestamps are parsed and compared as instants, as nodes may write them with different offsets.
+//Of the stats with the same timestamp, the one read first is the first and the one read last is the last
+#[derive(Default, Clone)]
+pub struct TimeRange {
+    first: Option<(DateTime<FixedOffset>, u64)>,
+    last: Option<DateTime<FixedOffset>>
+}
+
+impl TimeRange {
+    fn record(&mut self, stats: &Stats) {
+        let timestamp = match report::parse_timestamp(&stats.timestamp) {
+            Some(timestamp) => timestamp,
+            None => return
+        };
+
+        if self.first.as_ref().is_none_or(|(first, _)| timestamp < *first) {
+            self.first = Some((timestamp, stats.latency));
+        }
+
+        if self.last.as_ref().is_none_or(|last| timestamp >= *last) {
+            self.last = Some(timestamp);
+        }
+    }
+
+    //Other is expected to be read after self
+    fn merge(&mut self, other: TimeRange) {
+        if let Some((timestamp, latency)) = other.first {
+            if self.first.as_ref().is_none_or(|(first, _)| timestamp < *first) {
+                self.first = Some((timestamp, latency));
+            }
+        }
+
+        if let Some(timestamp) = other.last {
+            if self.last.as_ref().is_none_or(|last| timestamp >= *last) {
+                self.last = Some(timestamp);
+            }
+        }
+    }
+
+    //Execution starts when the first request is sent, i.e. its latency before its timestamp
+    pub fn get_start_time(&self) -> Option<DateTime<FixedOffset>> {
+        let (timestamp, latency) = self.first?;
+        Some(timestamp - Duration::microseconds(latency as i64))
+    }
+
+    pub fn get_execution_time(&self) -> i64 {
+        match (self.get_start_time(), self.last) {
+            (Some(start_time), Some(end_time)) => end_time.signed_duration_since(start_time).num_seconds(),
+            _ => 0
+        }
+    }
+}
+
+//Everything needed for the report computed in a single pass over the stats, so that memory used
+//depends only on the number of requests and intervals and not on the number of stats.
+//Token requests are aggregated separately so that they do not skew the numbers of the load
+#[derive(Default, Clone)]
+pub struct Aggregate {
+    pub requests: HashMap<String, RequestAggregate>,
+    pub histograms: LatencyHistograms,
+    pub token_requests: HashMap<String, RequestAggregate>,
+    pub token_histograms: LatencyHistograms,
+    pub time_range: TimeRange,
+    pub timeline: Option<Timeline>
+}
+
+impl Aggregate {
+    pub fn new(timeline: Option<Timeline>) -> Self {
+        Aggregate {
+            timeline,
+            ..Default::default()
+        }
+    }
+
+    //Used in tests to aggregate stats already in memory
+    #[cfg(test)]
+    pub fn from_stats(stats: &[Stats], interval: u64) -> Self {
+        let mut time_range = TimeRange::default();
+        stats.iter().filter(|s| !s.is_token_request()).for_each(|s| time_range.record(s));
+
+        let timeline = match interval {
+            0 => None,
+            _ => time_range.get_start_time().map(|start_time| Timeline::new(start_time, interval))
+        };
+
+        let mut aggregate = Aggregate::new(timeline);
+        stats.iter().for_each(|s| aggregate.record(s));
+        aggregate
+    }
+
+    pub fn record(&mut self, stats: &Stats) {
+        if stats.is_token_request() {
+            self.token_requests.entry(stats.name.clone()).or_default().record(stats);
+            self.token_histograms.record(stats);
+            return
+        }
+
+        self.requests.entry(stats.name.clone()).or_default().record(stats);
+        self.histograms.record(stats);
+        self.time_range.record(stats);
+
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.record(stats);
+        }
+    }
+
+    //Other is expected to be the aggregate of the stats read after the stats of self
+    pub fn merge(&mut self, other: Aggregate) {
+        merge_requests(&mut self.requests, other.requests);
+        merge_requests(&mut self.token_requests, other.token_requests);
+        self.histograms.merge(other.histograms);
+        self.token_histograms.merge(other.token_histograms);
+        self.time_range.merge(other.time_range);
+
+        match (self.timeline.as_mut(), other.timeline) {
+            (Some(timeline), Some(other)) => timeline.merge(other),
+            (None, Some(other)) => self.timeline = Some(other),
+            _ => ()
+        }
+    }
+
+    pub fn get_execution_time(&self) -> i64 {
+        self.time_range.get_execution_time()
+    }
+
+    pub fn get_request_summaries(&self) -> Vec<RequestSummary> {
+        report::get_request_summaries(&self.requests, &self.histograms, self.get_execution_time())
+    }
+
+    pub fn get_token_request_summaries(&self) -> Vec<RequestSummary> {
+        report::get_request_summaries(&self.token_requests, &self.token_histograms, self.get_execution_time())
+    }
+
+    //Aggregates of requests sorted by name
+    pub fn get_requests(&self) -> Vec<(&str, &RequestAggregate)> {
+        self.histograms.get_names().into_iter()
+            .filter_map(|name| Some((name, self.requests.get(name)?)))
+            .collect()
+    }
+
+    pub fn get_time_buckets(&self) -> Vec<TimeBucket> {
+        self.timeline.as_ref().map_or_else(Vec::new, |timeline| timeline.get_buckets())
+    }
+}
+
+fn merge_requests(requests: &mut HashMap<String, RequestAggregate>, other: HashMap<String, RequestAggregate>) {
+    for (name, request) in other {
+        requests.entry(name).or_default().merge(request);
+    }
+}
+
+//Report file is split in chunks which are aggregated in parallel and merged in the order of chunks.
+//Stats are aggregated under the key of their group, token requests are always aggregated by name
+pub async fn aggregate(report_file: &str, timeline: Option<Timeline>, group_by: &GroupBy) -> Result<Aggregate, Box<dyn std::error::Error>> {
+    let group_by = group_by.clone();
+    let aggregates = aggregate_chunks(report_file, Aggregate::new(timeline), move |aggregate, mut stats| {
+        if !group_by.is_default() && !stats.is_token_request() {
+            stats.name = group_by.get_key(&stats);
+        }
+
+        aggregate.record(&stats)
+    }).await?;
+
+    Ok(aggregates.into_iter().fold(Aggregate::default(), |mut aggregate, other| {
+        aggregate.merge(other);
+        aggregate
+    }))
+}
+
+//Start time is required upfront for interval stats, so it is computed with a separate pass over the report file
+pub async fn get_time_range(report_file: &str) -> Result<TimeRange, Box<dyn std::error::Error>> {
+    let time_ranges = aggregate_chunks(report_file, TimeRange::default(), |time_range, stats| {
+        if !stats.is_token_request() {
+            time_range.record(&stats)
+        }
+    }).await?;
+
+    Ok(time_ranges.into_iter().fold(TimeRange::default(), |mut time_range, other| {
+        time_range.merge(other);
+        time_range
+    }))
+}
+
+//Binary report files can only be read sequentially, so they are read in a single chunk
+async fn aggregate_chunks<T, F>(report_file: &str, initial: T, record: F) -> Result<Vec<T>, Box<dyn std::error::Error>>
+where T: Clone + Send + 'static, F: Fn(&mut T, Stats) + Send + Sync + 'static {
+    if binary::is_binary(report_file).await? {
+        info!("Aggregating binary report file {}", report_file);
+        let report_file = report_file.to_owned();
+        let value = task::spawn_blocking(move || binary::read(&report_file, initial, record)).await??;
+        return Ok(vec![value])
+    }
+
+    let (headers, data_start) = get_headers(report_file).await?;
+    let chunks = get_chunks(report_file, data_start).await?;
+    info!("Aggregating {} in {} chunk(s)", report_file, chunks.len());
+
+    let record = Arc::new(record);
+    let handles = chunks.into_iter()
+        .map(|chunk| tokio::spawn(aggregate_chunk(report_file.to_owned(), chunk, headers.clone(), initial.clone(), record.clone())))
+        .collect::<Vec<_>>();
+
+    let mut values = Vec::with_capacity(handles.len());
+    for handle in handles {
+        values.push(handle.await??);
+    }
+
+    Ok(values)
+}
+
+async fn aggregate_chunk<T, F>(report_file: String, (start, end): (u64, u64), headers: StringRecord, mut value: T, record: Arc<F>) -> Result<T, std::io::Error>
+where F: Fn(&mut T, Stats) {
+    let mut file = File::open(&report_file).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+
+    let mut reader = AsyncReaderBuilder::new()
+        .has_headers(false)
+        .trim(Trim::All)
+        .create_reader(file.take(end - start));
+
+    let mut records = reader.records();
+    while let Some(result) = records.next().await {
+        match result.and_then(|r| r.deserialize::<Stats>(Some(&headers))) {
+            Ok(stats) => record(&mut value, stats),
+            Err(err) => error!("Invalid record in {} : {}", report_file, err)
+        }
+    }
+
+    Ok(value)
+}
+
+//Headers are trimmed as they are written with a space after every comma.
+//Returned along with the position where the records start
+async fn get_headers(report_file: &str) -> Result<(StringRecord, u64), std::io::Error> {
+    let mut reader = BufReader::new(File::open(report_file).await?);
+    let mut line = String::new();
+    let len = reader.read_line(&mut line).await?;
+
+    let headers = line.split(',')
+        .map(str::trim)
+        .collect::<StringRecord>();
+
+    Ok((headers, len as u64))
+}
+
+//Byte ranges of records, a chunk for every available thread for large files.
+//Ranges are split at line boundaries so that every chunk starts with a record
+async fn get_chunks(report_file: &str, data_start: u64) -> Result<Vec<(u64, u64)>, std::io::Error> {
+    let size = fs::metadata(report_file).await?.len();
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get() as u64);
+    let count = (size.saturating_sub(data_start) / MIN_CHUNK_SIZE).clamp(1, threads);
+
+    let mut reader = BufReader::new(File::open(report_file).await?);
+    let mut boundaries = vec![data_start];
+
+    for i in 1..count {
+        let offset = data_start + (size - data_start) * i / count - 1;
+        reader.seek(SeekFrom::Start(offset)).await?;
+
+        let mut line = Vec::new();
+        let len = reader.read_until(b'\n', &mut line).await? as u64;
+        boundaries.push((offset + len).min(size));
+    }
+
+    boundaries.push(size);
+    boundaries.dedup();
+
+    Ok(boundaries.windows(2)
+        .map(|w| (w[0], w[1]))
+        .filter(|(start, end)| start < end)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+    use tempdir::TempDir;
+
+    use crate::{model::ErrorKind, report::aggregator::*};
+
+    fn get_aggregate(stats: &[Stats]) -> Aggregate {
+        Aggregate::from_stats(stats, 0)
+    }
+
+    #[test]
+    fn test_record() {
+        let mut stats = vec![Stats::new("name1", 200, 100, 0), Stats::new("name1", 404, 100, 0), Stats::new("name1", 503, 100, 0),
+            Stats::new("name1", 0, 100, 0), Stats::new("name1", 0, 100, 0), Stats::new("name1", 200, 100, 0), Stats::new("name1", 500, 100, 0),
+            Stats::new("name2", 200, 100, 0), Stats::new("oauth2:token", 200, 100, 0)];
+        stats[3].error_kind = Some(ErrorKind::Timeout);
+        stats[4].error_kind = Some(ErrorKind::Tls);
+        stats[5].error_kind = Some(ErrorKind::Assertion);
+
+        let aggregate = get_aggregate(&stats);
+        let request = &aggregate.requests["name1"];
+        assert_eq!((request.hits, request.get_errors()), (7, 6));
+        assert_eq!(request.error_breakdown, [1, 2, 1, 0, 1, 1, 0]);
+        assert_eq!(request.statuses.iter().map(|(s, c)| (*s, *c)).collect::<Vec<(u16, usize)>>(), vec![(0, 2), (200, 2), (404, 1), (500, 1), (503, 1)]);
+
+        assert_eq!(aggregate.get_requests().iter().map(|(name, _)| *name).collect::<Vec<&str>>(), vec!["name1", "name2"]);
+        assert_eq!(aggregate.token_requests.len(), 1);
+        assert_eq!(aggregate.get_token_request_summaries()[0].name, "oauth2:token");
+    }
+
+    #[test]
+    fn test_retries() {
+        let mut stats = vec![Stats::new("name1", 200, 100, 0), Stats::new("name1", 503, 100, 0), Stats::new("name1", 200, 100, 0),
+            Stats::new("name1", 0, 100, 0), Stats::new("name1", 503, 100, 0)];
+        stats[2].attempt = 2;
+        stats[4].attempt = 2;
+
+        //3 executions, 1st succeeded on first try, 2nd on retry and 3rd failed
+        let aggregate = get_aggregate(&stats);
+        let request = &aggregate.requests["name1"];
+        assert_eq!((request.hits, request.attempts, request.get_retries(), request.get_errors()), (3, 5, 2, 1));
+
+        //Failed attempts which were retried are not errors of the request
+        let summary = &aggregate.get_request_summaries()[0];
+        assert_eq!((summary.hits, summary.errors), (3, 1));
+        assert!((summary.error_rate - 33.333).abs() < 0.01);
+
+        let (first_try_rate, eventual_rate) = request.get_success_rates();
+        assert!((first_try_rate - 33.333).abs() < 0.01);
+        assert!((eventual_rate - 66.666).abs() < 0.01);
+
+        assert_eq!(RequestAggregate::default().get_success_rates(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_phases() {
+        let mut stats1 = Stats::new("name1", 200, 150, 0);
+        stats1.ttfb = 100;
+        stats1.download = 50;
+        stats1.dns = 10;
+        stats1.connect = 20;
+        stats1.tls = 30;
+        stats1.bytes_sent = 200;
+        stats1.bytes_received = 1000;
+
+        let request = &get_aggregate(&[stats1.clone(), stats1]).requests["name1"];
+        assert_eq!((request.ttfb, request.download, request.bytes_sent, request.bytes_received), (200, 100, 400, 2000));
+        assert_eq!((request.dns, request.connect, request.tls), (20, 40, 60));
+    }
+
+    #[test]
+    fn test_get_execution_time() {
+        //Latency of 1st element is subtracted from its timestamp to get start time
+        //so if 1st element takes 1 sec, the execution time should be 2+1
+        let mut stats = vec![Stats::new("name2", 200, 150, 1), Stats::new("name1", 200, 1_000_000, 1)];
+        stats[0].timestamp = String::from("2021-01-01 00:00:03.000000 +0000");
+        stats[1].timestamp = String::from("2021-01-01 00:00:01.000000 +0000");
+        assert_eq!(get_aggregate(&stats).get_execution_time(), 3);
+
+        //timestamps written by nodes in different timezones are compared as instants
+        stats[0].timestamp = String::from("2021-01-01 01:00:03.000000 +0100");
+        stats[1].timestamp = String::from("2020-12-31 23:00:01.000000 -0100");
+        assert_eq!(get_aggregate(&stats).get_execution_time(), 3);
+
+        //token requests are not included
+        stats[1].name = String::from("oauth2:token");
+        assert_eq!(get_aggregate(&stats).get_execution_time(), 0);
+
+        assert_eq!(get_aggregate(&[]).get_execution_time(), 0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let stats = (0..10)
+            .map(|i| Stats {
+                timestamp: format!("2021-01-01 00:00:{:02}.000000 +0000", i),
+                ..Stats::new(["name1", "name2"][i % 2], [200, 500][i % 3 / 2], 1000, i as u16)
+            })
+            .collect::<Vec<Stats>>();
+
+        //chunks are aggregated with the start time of the whole execution
+        let expected = Aggregate::from_stats(&stats, 2);
+        let get_chunk_aggregate = |stats: &[Stats]| {
+            let mut aggregate = Aggregate::new(expected.time_range.get_start_time().map(|start_time| Timeline::new(start_time, 2)));
+            stats.iter().for_each(|s| aggregate.record(s));
+            aggregate
+        };
+
+        let mut aggregate = get_chunk_aggregate(&stats[4..]);
+        aggregate.merge(get_chunk_aggregate(&stats[..4]));
+
+        assert_eq!(aggregate.get_execution_time(), expected.get_execution_time());
+        assert_eq!(serde_json::to_string(&aggregate.get_request_summaries()).unwrap(), serde_json::to_string(&expected.get_request_summaries()).unwrap());
+        assert_eq!(serde_json::to_string(&aggregate.get_time_buckets()).unwrap(), serde_json::to_string(&expected.get_time_buckets()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_aggregate() {
+        let tmp_dir = TempDir::new("tmp").unwrap();
+        let report_file = tmp_dir.path().join("report.csv");
+        let mut file = File::create(&report_file).unwrap();
+
+        //records of about 4 MB span over multiple chunks
+        writeln!(file, "timestamp, thread_count, status, latency, name, attempt, error_kind, ttfb, download, bytes_sent, bytes_received, expected_interval").unwrap();
+        let count = 36000;
+        for i in 0..count {
+            let stats = Stats {
+                timestamp: format!("2021-01-01 00:{:02}:{:02}.000000 +0000", i * 60 / count, i % 60),
+                ..Stats::new(["name1", "name2", "oauth2:token"][i % 3], [200, 500][i % 4 / 3], 1000 + i as u64 % 100, 1)
+            };
+            write!(file, "{}", stats).unwrap();
+        }
+
+        let report_file = report_file.to_str().unwrap();
+        let chunks = get_chunks(report_file, get_headers(report_file).await.unwrap().1).await.unwrap();
+        assert!(chunks.windows(2).all(|w| w[0].1 == w[1].0));
+
+        let time_range = get_time_range(report_file).await.unwrap();
+        let aggregate = aggregate(report_file, time_range.get_start_time().map(|start_time| Timeline::new(start_time, 600)), &GroupBy::default()).await.unwrap();
+
+        let hits = aggregate.requests.values().map(|r| r.hits).sum::<usize>() + aggregate.token_requests["oauth2:token"].hits;
+        assert_eq!(hits, count);
+        assert_eq!(aggregate.requests["name1"].get_errors(), count / 12);
+        //last request is a token request
+        assert_eq!(aggregate.get_execution_time(), 3598);
+        assert_eq!(aggregate.get_time_buckets().len(), 6);
+        assert_eq!(aggregate.histograms.get_summary("name2").unwrap().count as usize, count / 3);
+    }
+
+    #[test]
+    fn test_group_by() {
+        assert_eq!(GroupBy::parse("").unwrap(), GroupBy::default());
+        assert!(GroupBy::parse("name").unwrap().is_default());
+        assert!(GroupBy::parse("scenario, unknown").is_err());
+
+        let mut stats = Stats::new("getOrders", 503, 100, 1);
+        stats.scenario = String::from("orders");
+        stats.thread_id = 4;
+        stats.iteration = 12;
+        stats.method = String::from("GET");
+
+        assert_eq!(GroupBy::parse("scenario").unwrap().get_key(&stats), "orders");
+        assert_eq!(GroupBy::parse("name,thread,iteration").unwrap().get_key(&stats), "getOrders | 4 | 12");
+        assert_eq!(GroupBy::parse("method, status, error-kind").unwrap().get_key(&stats), "GET | 503 | 5xx");
+        assert_eq!(GroupBy::parse("node,error-message").unwrap().get_key(&stats), "- | -");
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_group_by() {
+        let dir = TempDir::new("test_aggregate_group_by").unwrap();
+        let file_path = dir.path().join("report.csv");
+        let report_file = file_path.to_str().unwrap();
+
+        let stats = (0..30)
+            .map(|i| Stats {
+                node_id: format!("10.0.0.{}:5000", i % 2),
+                ..Stats::new(["getOrders", "postOrder", "oauth2:token"][i % 3], 200, 1000, 1)
+            })
+            .collect::<Vec<Stats>>();
+
+        let mut writer = crate::report::csv::CSVWriter::new(report_file).await.unwrap();
+        writer.write(&stats).await;
+
+        let aggregate = aggregate(report_file, None, &GroupBy::parse("node").unwrap()).await.unwrap();
+        let mut nodes = aggregate.requests.keys().cloned().collect::<Vec<String>>();
+        nodes.sort();
+        assert_eq!(nodes, vec!["10.0.0.0:5000", "10.0.0.1:5000"]);
+        assert_eq!(aggregate.requests.values().map(|r| r.hits).sum::<usize>(), 20);
+
+        //token requests are not grouped
+        assert_eq!(aggregate.token_requests["oauth2:token"].hits, 10);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_binary() {
+        let dir = TempDir::new("test_aggregate_binary").unwrap();
+        let file_path = dir.path().join("report.bin");
+        let report_file = file_path.to_str().unwrap();
+
+        let stats = (0..100)
+            .map(|i| Stats {
+                timestamp: format!("2021-01-01 00:00:{:02}.000000 +0000", i / 2),
+                ..Stats::new("getOrders", if i % 10 == 0 { 500 } else { 200 }, 1000, 1)
+            })
+            .collect::<Vec<Stats>>();
+
+        let mut writer = binary::BinaryWriter::new(report_file, true).await.unwrap();
+        writer.write(&stats).await;
+        writer.finish().await;
+
+        let aggregate = aggregate(report_file, None, &GroupBy::default()).await.unwrap();
+        let request = &aggregate.requests["getOrders"];
+        assert_eq!((request.hits, request.get_errors()), (100, 10));
+        assert_eq!(aggregate.get_execution_time(), 49);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_invalid_file() {
+        assert!(aggregate("/some/file/path/report.csv", None, &GroupBy::default()).await.is_err());
+    }
+}
@@ -0,0 +1,456 @@
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use log::{info, warn};
+use rustc_hash::FxHashMap as HashMap;
+use tokio::{fs, io::AsyncWriteExt};
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind as IoErrorKind, Read, Write}
+};
+
+use crate::{
+    model::ErrorKind,
+    report::{self, csv, sink::ResultSink, stats::Stats}
+};
+
+//Binary report file starts with the magic bytes, version and flags followed by the records.
+//Every record is prefixed with the length of its payload so that readers can skip the record types they do not know.
+//When compressed, records are written as a single zstd stream.
+//
+//  STRING : type 1 | id u32 | value (utf-8)
+//  STATS  : type 2 | timestamp i64 (µs since epoch) | utc offset i32 (secs) | name id u32 | scenario id u32 | method id u32 | node id u32
+//           | thread_count u16 | thread_id u32 | iteration u64 | status u16 | attempt u32 | error kind u8 (0 if none)
+//           | latency u64 | ttfb u64 | download u64 | dns u64 | connect u64 | tls u64 | bytes_sent u64 | bytes_received u64 | expected_interval u64
+//           | url length u32 | url (utf-8) | error message length u32 | error message (utf-8)
+//
+//Request names, scenarios, methods and node ids are written once as STRING records and are referred by their id in STATS records.
+//Urls and error messages can be different for every request, so they are written in STATS records. All numbers are little endian
+pub const MAGIC: &[u8; 4] = b"BMBR";
+const VERSION: u8 = 2;
+const COMPRESSED: u8 = 1;
+
+const STRING_RECORD: u8 = 1;
+const STATS_RECORD: u8 = 2;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+const ERROR_KINDS: [ErrorKind; 5] = [ErrorKind::Timeout, ErrorKind::Connect, ErrorKind::Tls, ErrorKind::Assertion, ErrorKind::Other];
+
+pub struct BinaryWriter {
+    report_file: fs::File,
+    strings: HashMap<String, u32>,
+    encoder: Option<zstd::stream::write::Encoder<'static, Vec<u8>>>
+}
+
+impl BinaryWriter {
+    pub async fn new(report_file: &str, compress: bool) -> Result<BinaryWriter, io::Error> {
+        info!("Initiating BinaryWriter");
+        let mut report_file = fs::File::create(report_file).await?;
+
+        let flags = match compress {
+            true => COMPRESSED,
+            false => 0
+        };
+
+        let mut header = MAGIC.to_vec();
+        header.extend([VERSION, flags]);
+        report_file.write_all(&header).await?;
+
+        let encoder = match compress {
+            true => Some(zstd::stream::write::Encoder::new(Vec::new(), COMPRESSION_LEVEL)?),
+            false => None
+        };
+
+        Ok(BinaryWriter {
+            report_file,
+            strings: HashMap::default(),
+            encoder
+        })
+    }
+
+    pub async fn write(&mut self, stats: &[Stats]) {
+        let mut records = Vec::with_capacity(stats.len() * 120);
+        for s in stats {
+            if let Err(err) = self.encode(s, &mut records) {
+                warn!("Unable to write stat {} to file due to error {}", s, err)
+            }
+        }
+
+        //Compressed data of the records written so far is flushed, so that the file is readable even if execution is aborted
+        let bytes = match self.encoder.as_mut() {
+            Some(encoder) => match encoder.write_all(&records).and_then(|_| encoder.flush()) {
+                Ok(_) => std::mem::take(encoder.get_mut()),
+                Err(err) => {
+                    warn!("Unable to compress stats due to error {}", err);
+                    return
+                }
+            },
+            None => records
+        };
+
+        if let Err(err) = self.report_file.write_all(&bytes).await {
+            warn!("Unable to write stats to file due to error {}", err)
+        }
+    }
+
+    //Ends the zstd stream, nothing is to be written after this
+    pub async fn finish(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            match encoder.finish() {
+                Ok(bytes) => if let Err(err) = self.report_file.write_all(&bytes).await {
+                    warn!("Unable to write stats to file due to error {}", err)
+                },
+                Err(err) => warn!("Unable to complete compression of stats due to error {}", err)
+            }
+        }
+
+        if let Err(err) = self.report_file.flush().await {
+            warn!("Unable to flush report file due to error {}", err)
+        }
+    }
+
+    fn encode(&mut self, stats: &Stats, records: &mut Vec<u8>) -> Result<(), String> {
+        let timestamp = report::parse_timestamp(&stats.timestamp)
+            .ok_or_else(|| format!("Invalid timestamp {}", stats.timestamp))?;
+
+        let ids = [&stats.name, &stats.scenario, &stats.method, &stats.node_id].map(|value| self.intern(value, records));
+
+        let error_kind = stats.error_kind.as_ref()
+            .and_then(|kind| ERROR_KINDS.iter().position(|k| k == kind))
+            .map_or(0, |i| i as u8 + 1);
+
+        let mut payload = Vec::with_capacity(130 + stats.url.len() + stats.error_message.len());
+        payload.push(STATS_RECORD);
+        payload.extend((timestamp.timestamp() * 1_000_000 + timestamp.timestamp_subsec_micros() as i64).to_le_bytes());
+        payload.extend(timestamp.offset().local_minus_utc().to_le_bytes());
+        ids.iter().for_each(|id| payload.extend(id.to_le_bytes()));
+        payload.extend(stats.thread_count.to_le_bytes());
+        payload.extend(stats.thread_id.to_le_bytes());
+        payload.extend(stats.iteration.to_le_bytes());
+        payload.extend(stats.status.to_le_bytes());
+        payload.extend(stats.attempt.to_le_bytes());
+        payload.push(error_kind);
+
+        for value in [stats.latency, stats.ttfb, stats.download, stats.dns, stats.connect, stats.tls, stats.bytes_sent, stats.bytes_received, stats.expected_interval] {
+            payload.extend(value.to_le_bytes());
+        }
+
+        for value in [&stats.url, &stats.error_message] {
+            payload.extend((value.len() as u32).to_le_bytes());
+            payload.extend(value.as_bytes());
+        }
+
+        write_record(records, &payload);
+        Ok(())
+    }
+
+    //Id of the string, a STRING record is written the first time a string is seen
+    fn intern(&mut self, value: &str, records: &mut Vec<u8>) -> u32 {
+        if let Some(id) = self.strings.get(value) {
+            return *id
+        }
+
+        let id = self.strings.len() as u32;
+        let mut payload = vec![STRING_RECORD];
+        payload.extend(id.to_le_bytes());
+        payload.extend(value.as_bytes());
+        write_record(records, &payload);
+
+        self.strings.insert(value.to_owned(), id);
+        id
+    }
+}
+
+#[async_trait]
+impl ResultSink for BinaryWriter {
+    async fn write(&mut self, stats: &[Stats]) {
+        BinaryWriter::write(self, stats).await
+    }
+
+    async fn finish(&mut self) {
+        BinaryWriter::finish(self).await
+    }
+}
+
+fn write_record(records: &mut Vec<u8>, payload: &[u8]) {
+    records.extend((payload.len() as u32).to_le_bytes());
+    records.extend(payload);
+}
+
+pub async fn is_binary(report_file: &str) -> Result<bool, io::Error> {
+    let mut file = fs::File::open(report_file).await?;
+    let mut magic = [0u8; 4];
+
+    match tokio::io::AsyncReadExt::read_exact(&mut file, &mut magic).await {
+        Ok(_) => Ok(&magic == MAGIC),
+        Err(err) if err.kind() == IoErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err)
+    }
+}
+
+//Stats are read one at a time, so records are never loaded in memory together.
+//Reading is blocking, so it is expected to be called from a blocking task
+pub fn read<T, F>(report_file: &str, mut value: T, mut record: F) -> Result<T, io::Error>
+where F: FnMut(&mut T, Stats) {
+    let mut reader = BufReader::new(File::open(report_file)?);
+
+    let mut header = [0u8; 6];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(io::Error::new(IoErrorKind::InvalidData, format!("{} is not a binary report file", report_file)))
+    }
+
+    if header[4] != VERSION {
+        return Err(io::Error::new(IoErrorKind::InvalidData, format!("Unsupported version {} of binary report file", header[4])))
+    }
+
+    let mut reader: Box<dyn Read> = match header[5] & COMPRESSED {
+        0 => Box::new(reader),
+        _ => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?)
+    };
+
+    let mut strings: Vec<String> = Vec::new();
+    let mut payload = Vec::new();
+
+    loop {
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(_) => (),
+            Err(err) if err.kind() == IoErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err)
+        }
+
+        payload.resize(u32::from_le_bytes(len) as usize, 0);
+        reader.read_exact(&mut payload)?;
+
+        match payload.first() {
+            Some(&STRING_RECORD) => strings.push(String::from_utf8_lossy(payload.get(5..).unwrap_or_default()).to_string()),
+            Some(&STATS_RECORD) => record(&mut value, decode(&payload, &strings)?),
+            _ => continue
+        }
+    }
+
+    Ok(value)
+}
+
+fn decode(payload: &[u8], strings: &[String]) -> Result<Stats, io::Error> {
+    let mut decoder = Decoder { payload, position: 1 };
+    let invalid = |message: &str| io::Error::new(IoErrorKind::InvalidData, message.to_owned());
+
+    let micros = i64::from_le_bytes(decoder.next()?);
+    let offset = i32::from_le_bytes(decoder.next()?);
+    let timestamp = get_timestamp(micros, offset).ok_or_else(|| invalid("Invalid timestamp in binary report file"))?;
+
+    let mut string = || -> Result<String, io::Error> {
+        let id = u32::from_le_bytes(decoder.next()?) as usize;
+        strings.get(id).cloned().ok_or_else(|| invalid("Unknown string in binary report file"))
+    };
+
+    let (name, scenario, method, node_id) = (string()?, string()?, string()?, string()?);
+    let thread_count = u16::from_le_bytes(decoder.next()?);
+    let thread_id = u32::from_le_bytes(decoder.next()?);
+    let iteration = u64::from_le_bytes(decoder.next()?);
+    let status = u16::from_le_bytes(decoder.next()?);
+    let attempt = u32::from_le_bytes(decoder.next()?);
+    let error_kind = match decoder.next::<1>()?[0] {
+        0 => None,
+        kind => ERROR_KINDS.get(kind as usize - 1).cloned()
+    };
+
+    Ok(Stats {
+        timestamp: timestamp.format(report::TIMESTAMP_FORMAT).to_string(),
+        thread_count,
+        status,
+        latency: u64::from_le_bytes(decoder.next()?),
+        name,
+        attempt,
+        error_kind,
+        ttfb: u64::from_le_bytes(decoder.next()?),
+        download: u64::from_le_bytes(decoder.next()?),
+        dns: u64::from_le_bytes(decoder.next()?),
+        connect: u64::from_le_bytes(decoder.next()?),
+        tls: u64::from_le_bytes(decoder.next()?),
+        bytes_sent: u64::from_le_bytes(decoder.next()?),
+        bytes_received: u64::from_le_bytes(decoder.next()?),
+        expected_interval: u64::from_le_bytes(decoder.next()?),
+        scenario,
+        thread_id,
+        iteration,
+        method,
+        url: decoder.string()?,
+        error_message: decoder.string()?,
+        node_id
+    })
+}
+
+fn get_timestamp(micros: i64, offset: i32) -> Option<DateTime<FixedOffset>> {
+    let offset = FixedOffset::east_opt(offset)?;
+    let nanos = micros.rem_euclid(1_000_000) as u32 * 1000;
+    Some(Utc.timestamp_opt(micros.div_euclid(1_000_000), nanos).single()?.with_timezone(&offset))
+}
+
+struct Decoder<'a> {
+    payload: &'a [u8],
+    position: usize
+}
+
+impl<'a> Decoder<'a> {
+    fn next<const N: usize>(&mut self) -> Result<[u8; N], io::Error> {
+        let bytes = self.payload.get(self.position..self.position + N)
+            .ok_or_else(|| io::Error::new(IoErrorKind::UnexpectedEof, "Truncated record in binary report file"))?;
+
+        self.position += N;
+        Ok(std::convert::TryInto::try_into(bytes).unwrap())
+    }
+
+    fn string(&mut self) -> Result<String, io::Error> {
+        let len = u32::from_le_bytes(self.next()?) as usize;
+        let bytes = self.payload.get(self.position..self.position + len)
+            .ok_or_else(|| io::Error::new(IoErrorKind::UnexpectedEof, "Truncated record in binary report file"))?;
+
+        self.position += len;
+        Ok(String::from_utf8_lossy(bytes).to_string())
+    }
+}
+
+//Converts binary report file into a csv report file
+pub fn convert_to_csv(report_file: &str, output_file: &str) -> Result<usize, io::Error> {
+    let mut writer = BufWriter::new(File::create(output_file)?);
+    writer.write_all(csv::HEADER.as_bytes())?;
+
+    let mut result = Ok(());
+    let count = read(report_file, 0, |count, stats| {
+        if result.is_ok() {
+            result = write!(writer, "{}", stats);
+            *count += 1;
+        }
+    })?;
+
+    result?;
+    writer.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::report::binary::*;
+
+    fn get_stats() -> Vec<Stats> {
+        let mut timeout_stats = Stats::new("getOrders", 0, 100, 2);
+        timeout_stats.attempt = 2;
+        timeout_stats.error_kind = Some(ErrorKind::Timeout);
+        timeout_stats.bytes_sent = 150;
+        timeout_stats.expected_interval = 100_000;
+        timeout_stats.scenario = String::from("orders");
+        timeout_stats.thread_id = 2;
+        timeout_stats.iteration = 15;
+        timeout_stats.method = String::from("GET");
+        timeout_stats.url = String::from("https://example.com/orders?ids=1,2");
+        timeout_stats.error_message = String::from("operation timed out");
+        timeout_stats.node_id = String::from("10.0.0.2:50432");
+
+        let mut stats = Stats::new("postOrder", 201, 2500, 1);
+        stats.timestamp = String::from("2021-01-01 10:00:00.123456 +0530");
+        stats.ttfb = 2000;
+        stats.download = 500;
+        stats.dns = 300;
+        stats.connect = 150;
+        stats.tls = 900;
+        stats.bytes_received = 1024;
+
+        let mut ok_stats = Stats::new("getOrders", 200, 200, 1);
+        ok_stats.timestamp = String::from("2021-01-01 04:30:00.000001 +0000");
+        timeout_stats.timestamp = String::from("2021-01-01 04:30:01.500000 +0000");
+
+        vec![ok_stats, timeout_stats, stats]
+    }
+
+    async fn write_and_read(compress: bool) -> Vec<Stats> {
+        let dir = TempDir::new("test_binary").unwrap();
+        let file_path = dir.path().join("report.bin");
+        let report_file = file_path.to_str().unwrap();
+
+        let mut writer = BinaryWriter::new(report_file, compress).await.unwrap();
+        writer.write(&get_stats()[..2]).await;
+        writer.write(&get_stats()[2..]).await;
+        writer.finish().await;
+
+        assert!(is_binary(report_file).await.unwrap());
+        read(report_file, Vec::new(), |stats: &mut Vec<Stats>, s| stats.push(s)).unwrap()
+    }
+
+    fn assert_stats(stats: &[Stats]) {
+        let expected = get_stats();
+        assert_eq!(stats.len(), 3);
+
+        for (s, e) in stats.iter().zip(expected.iter()) {
+            assert_eq!(s.timestamp, e.timestamp);
+            assert_eq!((&s.name, s.status, s.latency, s.thread_count), (&e.name, e.status, e.latency, e.thread_count));
+            assert_eq!((s.attempt, &s.error_kind), (e.attempt, &e.error_kind));
+            assert_eq!((s.dns, s.connect, s.tls), (e.dns, e.connect, e.tls));
+            assert_eq!((s.ttfb, s.download, s.bytes_sent, s.bytes_received, s.expected_interval), (e.ttfb, e.download, e.bytes_sent, e.bytes_received, e.expected_interval));
+            assert_eq!((&s.scenario, s.thread_id, s.iteration, &s.method, &s.node_id), (&e.scenario, e.thread_id, e.iteration, &e.method, &e.node_id));
+            assert_eq!((&s.url, &s.error_message), (&e.url, &e.error_message));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_and_read() {
+        assert_stats(&write_and_read(false).await);
+    }
+
+    #[tokio::test]
+    async fn test_write_and_read_compressed() {
+        assert_stats(&write_and_read(true).await);
+    }
+
+    #[tokio::test]
+    async fn test_names_are_interned() {
+        let dir = TempDir::new("test_names_are_interned").unwrap();
+        let file_path = dir.path().join("report.bin");
+
+        let mut writer = BinaryWriter::new(file_path.to_str().unwrap(), false).await.unwrap();
+        let stats = (0..100).map(|_| Stats::new("some very long request name", 200, 200, 1)).collect::<Vec<Stats>>();
+        writer.write(&stats).await;
+        writer.finish().await;
+
+        //header, string records of name and empty scenario, method and node id, and 100 stats records
+        assert_eq!(std::fs::metadata(&file_path).unwrap().len(), 6 + (4 + 1 + 4 + 27) + (4 + 1 + 4) + 100 * (4 + 130));
+    }
+
+    #[tokio::test]
+    async fn test_convert_to_csv() {
+        let dir = TempDir::new("test_convert_to_csv").unwrap();
+        let report_file = dir.path().join("report.bin");
+        let output_file = dir.path().join("report.csv");
+
+        let mut writer = BinaryWriter::new(report_file.to_str().unwrap(), true).await.unwrap();
+        writer.write(&get_stats()).await;
+        writer.finish().await;
+
+        assert_eq!(convert_to_csv(report_file.to_str().unwrap(), output_file.to_str().unwrap()).unwrap(), 3);
+
+        let content = std::fs::read_to_string(output_file).unwrap();
+        let lines = content.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], csv::HEADER.trim_end());
+        assert!(lines[3].starts_with("2021-01-01 10:00:00.123456 +0530, 1, 201, 2500, postOrder"));
+    }
+
+    #[tokio::test]
+    async fn test_is_binary() {
+        let dir = TempDir::new("test_is_binary").unwrap();
+        let file_path = dir.path().join("report.csv");
+        std::fs::write(&file_path, csv::HEADER).unwrap();
+        assert!(!is_binary(file_path.to_str().unwrap()).await.unwrap());
+
+        std::fs::write(&file_path, "").unwrap();
+        assert!(!is_binary(file_path.to_str().unwrap()).await.unwrap());
+
+        std::fs::write(&file_path, b"BMBR\x09\x00").unwrap();
+        assert!(read(file_path.to_str().unwrap(), (), |_, _| ()).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use prettytable::{Table, row, cell};
+
+use crate::report::{self, RequestSummary, ReportSummary, aggregator::GroupBy, histogram};
+
+//Change of a metric between baseline and current run, latencies are in ms
+pub struct Delta {
+    pub metric: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+    pub regression: bool
+}
+
+impl Delta {
+    pub fn absolute(&self) -> f64 {
+        self.current - self.baseline
+    }
+
+    //Not available when baseline is 0
+    pub fn percentage(&self) -> Option<f64> {
+        match self.baseline == 0.0 {
+            true => None,
+            false => Some(self.absolute() * 100.0 / self.baseline)
+        }
+    }
+}
+
+pub struct RequestComparison {
+    pub name: String,
+    pub deltas: Vec<Delta>
+}
+
+pub struct Comparison {
+    pub requests: Vec<RequestComparison>,
+
+    //Requests present in only one of the runs are not compared
+    pub only_in_baseline: Vec<String>,
+    pub only_in_current: Vec<String>
+}
+
+impl Comparison {
+    pub fn has_regressions(&self) -> bool {
+        self.requests.iter().any(|r| r.deltas.iter().any(|d| d.regression))
+    }
+}
+
+//Compares the report files and prints the deltas, returns true if any metric has regressed
+pub async fn compare_reports(baseline_file: &str, current_file: &str, tolerance: f64) -> Result<bool, Box<dyn std::error::Error>> {
+    let baseline = report::get_report_summary(&report::aggregate(baseline_file, 0, &GroupBy::default()).await?);
+    let current = report::get_report_summary(&report::aggregate(current_file, 0, &GroupBy::default()).await?);
+
+    let comparison = compare(&baseline, &current, tolerance);
+    print_comparison_table(&comparison, tolerance);
+
+    Ok(comparison.has_regressions())
+}
+
+//A metric regresses when it is worse than the baseline by more than tolerance (in %) of the baseline.
+//Throughput is worse when it is lower, latencies and error rate are worse when they are higher
+pub fn compare(baseline: &ReportSummary, current: &ReportSummary, tolerance: f64) -> Comparison {
+    let requests = baseline.requests.iter()
+        .filter_map(|b| {
+            let c = current.requests.iter().find(|c| c.name == b.name)?;
+            Some(RequestComparison {
+                name: b.name.clone(),
+                deltas: get_deltas(b, c, tolerance)
+            })
+        })
+        .collect();
+
+    let missing_in = |summary: &ReportSummary, other: &ReportSummary| summary.requests.iter()
+        .filter(|s| !other.requests.iter().any(|o| o.name == s.name))
+        .map(|s| s.name.clone())
+        .collect::<Vec<String>>();
+
+    Comparison {
+        requests,
+        only_in_baseline: missing_in(baseline, current),
+        only_in_current: missing_in(current, baseline)
+    }
+}
+
+fn get_deltas(baseline: &RequestSummary, current: &RequestSummary, tolerance: f64) -> Vec<Delta> {
+    let latency = |f: fn(&RequestSummary) -> u64| (histogram::to_ms(f(baseline)), histogram::to_ms(f(current)));
+
+    let metrics = [
+        ("throughput", (baseline.throughput as f64, current.throughput as f64), false),
+        ("avg", latency(|s| s.latency.mean), true),
+        ("p50", latency(|s| s.latency.pc_50), true),
+        ("p90", latency(|s| s.latency.pc_90), true),
+        ("p95", latency(|s| s.latency.pc_95), true),
+        ("p99", latency(|s| s.latency.pc_99), true),
+        ("p99.9", latency(|s| s.latency.pc_99_9), true),
+        ("errorRate", (baseline.error_rate as f64, current.error_rate as f64), true)
+    ];
+
+    metrics.iter()
+        .map(|&(metric, (baseline, current), higher_is_worse)| {
+            let change = match higher_is_worse {
+                true => current - baseline,
+                false => baseline - current
+            };
+
+            Delta {
+                metric,
+                baseline,
+                current,
+                regression: change > baseline * tolerance / 100.0
+            }
+        })
+        .collect()
+}
+
+//Latencies are displayed with µs precision
+fn get_precision(metric: &str) -> usize {
+    match metric {
+        "throughput" | "errorRate" => 2,
+        _ => 3
+    }
+}
+
+//Regressions are highlighted in red
+fn print_comparison_table(comparison: &Comparison, tolerance: f64) {
+    let mut table = Table::new();
+    table.add_row(row![FY => "Request", "Metric", "Baseline", "Current", "Delta", "Delta %", "Regression"]);
+
+    for request in &comparison.requests {
+        for d in &request.deltas {
+            let precision = get_precision(d.metric);
+            let baseline = format!("{:.*}", precision, d.baseline);
+            let current = format!("{:.*}", precision, d.current);
+            let delta = format!("{:+.*}", precision, d.absolute());
+            let percentage = d.percentage().map_or(String::from("-"), |p| format!("{:+.2}", p));
+
+            match d.regression {
+                true => table.add_row(row![Fr => &request.name, d.metric, &baseline, &current, &delta, &percentage, "Yes"]),
+                false => table.add_row(row![&request.name, d.metric, &baseline, &current, &delta, &percentage, "No"])
+            };
+        }
+    }
+
+    table.printstd();
+
+    println!("Latencies are in ms, throughput in hits/s and error rate in %. Tolerance is {} %", tolerance);
+    if !comparison.only_in_baseline.is_empty() {
+        println!("Requests only in baseline : {}", comparison.only_in_baseline.join(", "));
+    }
+
+    if !comparison.only_in_current.is_empty() {
+        println!("Requests only in current : {}", comparison.only_in_current.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::{self, aggregator::Aggregate, compare::*, stats::Stats};
+
+    //Requests are a second apart so that throughput is computed over the execution time
+    fn get_summary(stats: &[(&str, u16, u64)]) -> ReportSummary {
+        let stats = stats.iter().enumerate()
+            .map(|(i, &(name, status, latency))| Stats {
+                timestamp: format!("2021-01-01 00:00:{:02}.000000 +0000", i + 1),
+                ..Stats::new(name, status, latency, 1)
+            })
+            .collect::<Vec<Stats>>();
+
+        report::get_report_summary(&Aggregate::from_stats(&stats, 0))
+    }
+
+    fn get_delta<'a>(comparison: &'a Comparison, name: &str, metric: &str) -> &'a Delta {
+        comparison.requests.iter()
+            .find(|r| r.name == name).unwrap()
+            .deltas.iter()
+            .find(|d| d.metric == metric).unwrap()
+    }
+
+    #[test]
+    fn test_compare() {
+        let baseline = get_summary(&[("getOrders", 200, 1000), ("getOrders", 200, 1000), ("getHealth", 200, 500), ("login", 200, 100)]);
+        let current = get_summary(&[("getOrders", 200, 1500), ("getOrders", 500, 1500), ("getHealth", 200, 520), ("logout", 200, 100)]);
+
+        let comparison = compare(&baseline, &current, 10.0);
+        assert_eq!(comparison.requests.len(), 2);
+        assert_eq!(comparison.only_in_baseline, vec!["login"]);
+        assert_eq!(comparison.only_in_current, vec!["logout"]);
+        assert!(comparison.has_regressions());
+
+        let avg = get_delta(&comparison, "getOrders", "avg");
+        assert_eq!(avg.baseline, 1.0);
+        assert_eq!(avg.current, 1.5);
+        assert_eq!(avg.absolute(), 0.5);
+        assert_eq!(avg.percentage(), Some(50.0));
+        assert!(avg.regression);
+
+        //errors regress from a baseline of 0
+        let error_rate = get_delta(&comparison, "getOrders", "errorRate");
+        assert_eq!(error_rate.percentage(), None);
+        assert!(error_rate.regression);
+
+        //within tolerance
+        let avg = get_delta(&comparison, "getHealth", "avg");
+        assert!((avg.percentage().unwrap() - 4.0).abs() < 0.001);
+        assert!(!avg.regression);
+        assert!(!get_delta(&comparison, "getHealth", "errorRate").regression);
+    }
+
+    #[test]
+    fn test_compare_throughput_and_improvements() {
+        let baseline = get_summary(&[("getOrders", 200, 2000), ("getOrders", 200, 2000)]);
+        let current = get_summary(&[("getOrders", 200, 1000), ("getHealth", 200, 1000), ("getHealth", 200, 1000), ("getOrders", 200, 1000)]);
+
+        //lower latency is an improvement, lower throughput (same hits over a longer execution) is a regression
+        let comparison = compare(&baseline, &current, 10.0);
+        assert!(!get_delta(&comparison, "getOrders", "p99").regression);
+        assert_eq!(get_delta(&comparison, "getOrders", "p99").percentage(), Some(-50.0));
+        assert!(get_delta(&comparison, "getOrders", "throughput").regression);
+
+        assert!(!compare(&baseline, &current, 70.0).has_regressions());
+        assert!(!compare(&baseline, &baseline, 0.0).has_regressions());
+    }
+}
@@ -1,8 +1,13 @@
+use async_trait::async_trait;
 use log::{info,warn};
 use tokio::{fs, io::AsyncWriteExt};
 
 use std::fmt::Display;
 
+use crate::report::{sink::ResultSink, stats::Stats};
+
+pub const HEADER: &str = "timestamp, thread_count, status, latency, name, attempt, error_kind, ttfb, download, dns, connect, tls, bytes_sent, bytes_received, expected_interval, scenario, thread_id, iteration, method, url, error_message, node_id\n";
+
 pub struct CSVWriter {
     report_file: fs::File
 }
@@ -16,7 +21,7 @@ impl CSVWriter {
         };
 
         //write header row
-        csv_writer.report_file.write_all("timestamp, thread_count, status, latency, name\n".as_bytes()).await?;
+        csv_writer.report_file.write_all(HEADER.as_bytes()).await?;
         Ok(csv_writer)
     }
 
@@ -29,6 +34,19 @@ impl CSVWriter {
     }
 }
 
+#[async_trait]
+impl ResultSink for CSVWriter {
+    async fn write(&mut self, stats: &[Stats]) {
+        CSVWriter::write(self, stats).await
+    }
+
+    async fn finish(&mut self) {
+        if let Err(err) = self.report_file.flush().await {
+            warn!("Unable to flush report file due to error {}", err)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tempdir::TempDir;
@@ -37,7 +55,7 @@ mod tests {
     use std::str;
 
     use super::CSVWriter;
-    use crate::report::stats::Stats;
+    use crate::{data::DataProvider, model::ErrorKind, report::stats::Stats};
     
     #[tokio::test]
     async fn test_write_to_csv() {
@@ -54,7 +72,44 @@ mod tests {
         file.read_to_end(&mut contents).await.unwrap();
 
         let contents = str::from_utf8(&contents).unwrap().split('\n').collect::<Vec<_>>();
-        assert!(contents[0].contains("timestamp, thread_count, status, latency, name"));
+        assert!(contents[0].contains("timestamp, thread_count, status, latency, name, attempt, error_kind, ttfb, download, dns, connect, tls, bytes_sent, bytes_received, expected_interval"));
         assert!(contents[1].contains("1, 200, 200, test"));
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_stats_written_to_csv_can_be_read() {
+        let dir = TempDir::new("test_stats_written_to_csv_can_be_read").unwrap();
+        let file_path = dir.path().join("test.csv");
+
+        let mut timeout_stats = Stats::new("test", 0, 100, 1);
+        timeout_stats.attempt = 2;
+        timeout_stats.error_kind = Some(ErrorKind::Timeout);
+        timeout_stats.bytes_sent = 150;
+        timeout_stats.connect = 250;
+        timeout_stats.expected_interval = 100_000;
+        timeout_stats.scenario = String::from("orders");
+        timeout_stats.thread_id = 3;
+        timeout_stats.iteration = 7;
+        timeout_stats.method = String::from("GET");
+        timeout_stats.url = String::from("https://example.com/orders?ids=1,2");
+        timeout_stats.error_message = String::from("error sending request: operation timed out, \"retrying\"");
+        timeout_stats.node_id = String::from("10.0.0.2:50432");
+
+        let mut csv_writer = CSVWriter::new(file_path.to_str().unwrap()).await.unwrap();
+        csv_writer.write(&[Stats::new("test", 200, 200, 1), timeout_stats]).await;
+
+        let mut data_provider = DataProvider::new(file_path.to_str().unwrap()).await.unwrap();
+        let stats = data_provider.get_records_as::<Stats>().await.unwrap();
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].attempt, stats[0].error_kind.clone()), (1, None));
+        assert_eq!((stats[1].attempt, stats[1].error_kind.clone()), (2, Some(ErrorKind::Timeout)));
+        assert_eq!((stats[1].bytes_sent, stats[1].connect), (150, 250));
+        assert_eq!((stats[0].expected_interval, stats[1].expected_interval), (0, 100_000));
+        assert_eq!((stats[0].scenario.as_str(), stats[0].thread_id, stats[0].method.as_str()), ("", 0, ""));
+        assert_eq!((stats[1].scenario.as_str(), stats[1].thread_id, stats[1].iteration, stats[1].method.as_str()), ("orders", 3, 7, "GET"));
+        assert_eq!(stats[1].url, "https://example.com/orders?ids=1%2C2");
+        assert_eq!(stats[1].error_message, "error sending request: operation timed out; 'retrying'");
+        assert_eq!(stats[1].node_id, "10.0.0.2:50432");
+    }
+}
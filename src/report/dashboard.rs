@@ -0,0 +1,285 @@
+use crossterm::{cursor, execute, terminal::{self, ClearType}};
+use hdrhistogram::Histogram;
+use log::error;
+use parking_lot::Mutex;
+use prettytable::{Table, row, cell};
+use rustc_hash::FxHashMap as HashMap;
+use tokio::{task::{self, JoinHandle}, time};
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, Write},
+    sync::Arc,
+    time::{Duration, Instant}
+};
+
+use crate::{
+    model::Config,
+    report::{self, histogram, stats::Stats}
+};
+
+//RPS, percentiles and error rate are computed over the stats received in the last few seconds
+const WINDOW_SECONDS: u64 = 10;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+//A histogram is kept for every request in every second, so they track latencies only up to a minute with 2 significant digits.
+//That keeps each of them around 10KB instead of the 180KB of the histograms of the report, anything above a minute is shown as a minute
+const HIGHEST_LATENCY: u64 = 60_000_000;
+const SIGNIFICANT_DIGITS: u8 = 2;
+
+//Live view of the execution, fed by StatsConsumer and redrawn every second till the execution finishes
+#[derive(Clone)]
+pub struct Dashboard {
+    state: Arc<Mutex<DashboardState>>
+}
+
+struct DashboardState {
+    start_time: Instant,
+    execution_time: u64,
+    iterations: u64,
+    threads: u16, //threads started so far, as threads which finished are not tracked
+    windows: VecDeque<Window>,
+    totals: BTreeMap<String, (u64, u64)> //hits and errors of every request since start
+}
+
+struct Window {
+    second: u64,
+    requests: HashMap<String, (Histogram<u32>, u64)> //latencies and errors of every request in this second
+}
+
+impl Dashboard {
+    pub fn new(config: &Config) -> Self {
+        Dashboard {
+            state: Arc::new(Mutex::new(DashboardState::new(config.execution_time, config.iterations)))
+        }
+    }
+
+    pub fn record(&self, stats: &[Stats]) {
+        let mut state = self.state.lock();
+        let second = state.start_time.elapsed().as_secs();
+        state.record(second, stats);
+    }
+
+    //Refreshes the view till the returned task is aborted
+    pub fn start(&self) -> JoinHandle<()> {
+        let dashboard = self.clone();
+        task::spawn(async move {
+            loop {
+                time::sleep(REFRESH_INTERVAL).await;
+                dashboard.draw();
+            }
+        })
+    }
+
+    //Draws the final view, the refresh task is stopped first so that nothing is drawn after this
+    pub async fn finish(&self, handle: JoinHandle<()>) {
+        handle.abort();
+        let _ = handle.await;
+        self.draw();
+    }
+
+    //View is built under the lock and drawn after releasing it, so that recording stats does not wait for the terminal
+    fn draw(&self) {
+        let (frame, table) = {
+            let mut state = self.state.lock();
+            let second = state.start_time.elapsed().as_secs();
+            (state.render(second), state.get_request_table(second))
+        };
+
+        draw(&frame, &table);
+    }
+}
+
+impl DashboardState {
+    fn new(execution_time: u64, iterations: u64) -> Self {
+        DashboardState {
+            start_time: Instant::now(),
+            execution_time,
+            iterations,
+            threads: 0,
+            windows: VecDeque::with_capacity(WINDOW_SECONDS as usize + 1),
+            totals: BTreeMap::new()
+        }
+    }
+
+    //Token requests are not shown as they are not part of the load
+    fn record(&mut self, second: u64, stats: &[Stats]) {
+        self.expire_windows(second);
+
+        if self.windows.back().is_none_or(|w| w.second != second) {
+            self.windows.push_back(Window { second, requests: HashMap::default() });
+        }
+
+        let window = self.windows.back_mut().unwrap();
+        for s in stats.iter().filter(|s| !s.is_token_request()) {
+            self.threads = s.thread_count;
+
+            let is_error = report::is_error(s) as u64;
+            let totals = self.totals.entry(s.name.clone()).or_default();
+            totals.0 += 1;
+            totals.1 += is_error;
+
+            if !window.requests.contains_key(&s.name) {
+                match new_histogram(&s.name) {
+                    Some(h) => window.requests.insert(s.name.clone(), (h, 0)),
+                    None => continue
+                };
+            }
+
+            if let Some((h, errors)) = window.requests.get_mut(&s.name) {
+                h.saturating_record(s.latency);
+                *errors += is_error;
+            }
+        }
+    }
+
+    fn expire_windows(&mut self, second: u64) {
+        while self.windows.front().is_some_and(|w| w.second + WINDOW_SECONDS <= second) {
+            self.windows.pop_front();
+        }
+    }
+
+    //Latencies and errors of a request, or of all the requests if name is not provided, over the rolling window
+    fn get_window_stats(&self, name: Option<&str>) -> Option<(Histogram<u32>, u64)> {
+        let mut merged = new_histogram(name.unwrap_or("all"))?;
+        let mut errors = 0;
+
+        for (request, (h, e)) in self.windows.iter().flat_map(|w| w.requests.iter()) {
+            if name.is_none_or(|name| name == request) {
+                if let Err(err) = merged.add(h) {
+                    error!("Unable to merge histograms of {} : {}", request, err);
+                }
+
+                errors += e;
+            }
+        }
+
+        Some((merged, errors))
+    }
+
+    fn render(&mut self, second: u64) -> String {
+        self.expire_windows(second);
+
+        let span = get_window_span(second);
+        let remaining = match (self.iterations, self.execution_time) {
+            (0, execution_time) if execution_time > 0 => format_duration(execution_time.saturating_sub(second)),
+            _ => String::from("-")
+        };
+
+        let mut frame = format!("Elapsed: {}    Remaining: {}    Threads: {}\n", format_duration(second), remaining, self.threads);
+
+        if let Some((h, errors)) = self.get_window_stats(None) {
+            let summary = histogram::get_summary(&h);
+            frame.push_str(&format!("RPS: {:.1}    p50: {} ms    p95: {} ms    p99: {} ms    Error rate: {:.2} %\n",
+                summary.count as f64 / span, histogram::format_latency(summary.pc_50), histogram::format_latency(summary.pc_95),
+                histogram::format_latency(summary.pc_99), get_error_rate(errors, summary.count)));
+        }
+
+        frame
+    }
+
+    fn get_request_table(&self, second: u64) -> Table {
+        let span = get_window_span(second);
+        let mut table = Table::new();
+        table.add_row(row![FY => "Request", "Total Hits", "Total Errors", "RPS", "p50", "p95", "p99", "Error Rate"]);
+
+        for (name, (hits, total_errors)) in self.totals.iter() {
+            if let Some((h, errors)) = self.get_window_stats(Some(name)) {
+                let summary = histogram::get_summary(&h);
+                table.add_row(row![name, &hits.to_string(), &total_errors.to_string(), &format!("{:.1}", summary.count as f64 / span),
+                                    &histogram::format_latency(summary.pc_50), &histogram::format_latency(summary.pc_95),
+                                    &histogram::format_latency(summary.pc_99), &format!("{:.2}", get_error_rate(errors, summary.count))]);
+            }
+        }
+
+        table
+    }
+}
+
+fn new_histogram(name: &str) -> Option<Histogram<u32>> {
+    match Histogram::new_with_bounds(1, HIGHEST_LATENCY, SIGNIFICANT_DIGITS) {
+        Ok(histogram) => Some(histogram),
+        Err(err) => {
+            error!("Unable to create dashboard histogram for {} : {}", name, err);
+            None
+        }
+    }
+}
+
+//Window is shorter than WINDOW_SECONDS in the beginning
+fn get_window_span(second: u64) -> f64 {
+    (second + 1).min(WINDOW_SECONDS) as f64
+}
+
+fn get_error_rate(errors: u64, hits: u64) -> f64 {
+    match hits {
+        0 => 0.0,
+        _ => errors as f64 * 100.0 / hits as f64
+    }
+}
+
+pub fn format_duration(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+fn draw(frame: &str, table: &Table) {
+    let mut stdout = io::stdout();
+    if let Err(err) = execute!(stdout, cursor::MoveTo(0, 0), terminal::Clear(ClearType::All)) {
+        error!("Unable to clear the terminal for dashboard: {}", err);
+    }
+
+    print!("{}", frame);
+    table.printstd();
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::dashboard::*;
+
+    fn get_stats(name: &str, status: u16, latency: u64) -> Stats {
+        Stats::new(name, status, latency, 5)
+    }
+
+    #[test]
+    fn test_render() {
+        let mut state = DashboardState::new(600, 0);
+        state.record(0, &[get_stats("getOrders", 200, 1000), get_stats("getOrders", 500, 2000), get_stats("oauth2:token", 200, 1000)]);
+        state.record(1, &[get_stats("postOrder", 200, 2000)]);
+
+        let frame = state.render(1);
+        assert!(frame.contains("Elapsed: 00:00:01    Remaining: 00:09:59    Threads: 5"));
+        //latencies are tracked with 2 significant digits
+        assert!(frame.contains("RPS: 1.5    p50: 2.007 ms"));
+        assert!(frame.contains("Error rate: 33.33 %"));
+
+        //header and a row for every request except token requests
+        let table = state.get_request_table(1);
+        assert_eq!(table.len(), 3);
+
+        let row = table.get_row(1).unwrap();
+        let cells = (0..8).map(|i| row.get_cell(i).unwrap().get_content()).collect::<Vec<String>>();
+        assert_eq!(cells, vec!["getOrders", "2", "1", "1.0", "1.003", "2.007", "2.007", "50.00"]);
+        assert_eq!(table.get_row(2).unwrap().get_cell(0).unwrap().get_content(), "postOrder");
+    }
+
+    #[test]
+    fn test_old_windows_are_expired() {
+        let mut state = DashboardState::new(0, 10);
+        state.record(0, &[get_stats("getOrders", 500, 1000)]);
+        state.record(WINDOW_SECONDS, &[get_stats("getOrders", 200, 1000)]);
+
+        assert_eq!(state.windows.len(), 1);
+        assert_eq!(state.totals.get("getOrders"), Some(&(2, 1)));
+
+        let frame = state.render(WINDOW_SECONDS);
+        assert!(frame.contains("Remaining: -"));
+        assert!(frame.contains("Error rate: 0.00 %"));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "00:00:00");
+        assert_eq!(format_duration(3723), "01:02:03");
+    }
+}
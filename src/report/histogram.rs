@@ -0,0 +1,272 @@
+use hdrhistogram::{Counter, Histogram};
+use log::error;
+use rustc_hash::FxHashMap as HashMap;
+use serde::{Serialize, Serializer};
+
+use std::iter::FromIterator;
+
+use crate::report::stats::Stats;
+
+//Latencies are recorded in µs, anything above an hour is recorded as an hour.
+//Histograms have a fixed size, so memory does not grow with the number of requests
+pub const HIGHEST_LATENCY: u64 = 3_600_000_000;
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+//Latencies are in µs, serialized in ms
+#[derive(Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    pub min: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    #[serde(rename = "avg")]
+    pub mean: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    pub max: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    #[serde(rename = "p50")]
+    pub pc_50: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    #[serde(rename = "p90")]
+    pub pc_90: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    #[serde(rename = "p95")]
+    pub pc_95: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    #[serde(rename = "p99")]
+    pub pc_99: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    #[serde(rename = "p99.9")]
+    pub pc_99_9: u64,
+
+    #[serde(serialize_with = "serialize_latency")]
+    #[serde(rename = "p99.99")]
+    pub pc_99_99: u64
+}
+
+fn serialize_latency<S: Serializer>(latency: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(to_ms(*latency))
+}
+
+//Corrected histograms are maintained only for stats with an expected interval, i.e. when pacing is configured.
+//Correction adds the samples which would have been recorded had the thread not waited for a delayed response
+#[derive(Default, Clone)]
+pub struct LatencyHistograms {
+    histograms: HashMap<String, Histogram<u64>>,
+    corrected_histograms: HashMap<String, Histogram<u64>>
+}
+
+impl LatencyHistograms {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn record(&mut self, stats: &Stats) {
+        if let Some(histogram) = get_histogram(&mut self.histograms, &stats.name) {
+            histogram.saturating_record(stats.latency);
+        }
+
+        if stats.expected_interval == 0 {
+            return
+        }
+
+        if let Some(histogram) = get_histogram(&mut self.corrected_histograms, &stats.name) {
+            if let Err(err) = histogram.record_correct(stats.latency.min(HIGHEST_LATENCY), stats.expected_interval) {
+                error!("Unable to record corrected latency for {} : {}", stats.name, err);
+            }
+        }
+    }
+
+    //Names are sorted so that tables are printed in the same order every time
+    pub fn get_names(&self) -> Vec<&str> {
+        let mut names = self.histograms.keys()
+            .map(|name| name.as_str())
+            .collect::<Vec<&str>>();
+
+        names.sort_unstable();
+        names
+    }
+
+    pub fn get_summary(&self, name: &str) -> Option<LatencySummary> {
+        self.histograms.get(name).map(get_summary)
+    }
+
+    pub fn get_corrected_summary(&self, name: &str) -> Option<LatencySummary> {
+        self.corrected_histograms.get(name).map(get_summary)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.histograms.is_empty()
+    }
+
+    //Histograms of report file chunks aggregated in parallel are merged into one
+    pub fn merge(&mut self, other: LatencyHistograms) {
+        merge_histograms(&mut self.histograms, other.histograms);
+        merge_histograms(&mut self.corrected_histograms, other.corrected_histograms);
+    }
+
+    pub fn is_corrected(&self) -> bool {
+        !self.corrected_histograms.is_empty()
+    }
+}
+
+fn merge_histograms(histograms: &mut HashMap<String, Histogram<u64>>, other: HashMap<String, Histogram<u64>>) {
+    for (name, histogram) in other {
+        match histograms.get_mut(&name) {
+            Some(h) => if let Err(err) = h.add(&histogram) {
+                error!("Unable to merge histogram for {} : {}", name, err);
+            },
+            None => {
+                histograms.insert(name, histogram);
+            }
+        }
+    }
+}
+
+fn get_histogram<'a>(histograms: &'a mut HashMap<String, Histogram<u64>>, name: &str) -> Option<&'a mut Histogram<u64>> {
+    if !histograms.contains_key(name) {
+        histograms.insert(name.to_owned(), new_histogram(name)?);
+    }
+
+    histograms.get_mut(name)
+}
+
+pub fn new_histogram(name: &str) -> Option<Histogram<u64>> {
+    match Histogram::new_with_bounds(1, HIGHEST_LATENCY, SIGNIFICANT_DIGITS) {
+        Ok(histogram) => Some(histogram),
+        Err(err) => {
+            error!("Unable to create histogram for {} : {}", name, err);
+            None
+        }
+    }
+}
+
+pub fn get_summary<T: Counter>(histogram: &Histogram<T>) -> LatencySummary {
+    LatencySummary {
+        count: histogram.len(),
+        min: histogram.min(),
+        mean: histogram.mean() as u64,
+        max: histogram.max(),
+        pc_50: histogram.value_at_quantile(0.50),
+        pc_90: histogram.value_at_quantile(0.90),
+        pc_95: histogram.value_at_quantile(0.95),
+        pc_99: histogram.value_at_quantile(0.99),
+        pc_99_9: histogram.value_at_quantile(0.999),
+        pc_99_99: histogram.value_at_quantile(0.9999)
+    }
+}
+
+impl<'a> FromIterator<&'a Stats> for LatencyHistograms {
+    fn from_iter<I: IntoIterator<Item = &'a Stats>>(iter: I) -> Self {
+        let mut histograms = LatencyHistograms::new();
+        for stats in iter {
+            histograms.record(stats);
+        }
+
+        histograms
+    }
+}
+
+pub fn to_ms(latency: u64) -> f64 {
+    latency as f64 / 1000.0
+}
+
+//Latencies are displayed in ms with µs precision
+pub fn format_latency(latency: u64) -> String {
+    format!("{:.3}", to_ms(latency))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::histogram::*;
+
+    #[test]
+    fn test_latency_summary() {
+        let stats = (1..=10000).map(|i| Stats::new("name1", 200, i * 10, 1)).collect::<Vec<Stats>>();
+        let histograms = stats.iter().collect::<LatencyHistograms>();
+
+        let summary = histograms.get_summary("name1").unwrap();
+        assert_eq!(summary.count, 10000);
+        assert_eq!(summary.min, 10);
+        assert!(summary.max.abs_diff(100000) <= 100);
+
+        //values are accurate to 3 significant digits
+        assert!(summary.pc_90.abs_diff(90000) <= 90);
+        assert!(summary.pc_99.abs_diff(99000) <= 99);
+        assert!(summary.pc_99_9.abs_diff(99900) <= 100);
+        assert!(summary.pc_99_99.abs_diff(99990) <= 100);
+        assert!(summary.mean.abs_diff(50005) <= 50);
+
+        assert!(histograms.get_summary("name2").is_none());
+    }
+
+    #[test]
+    fn test_record_sub_millisecond_and_saturated_latencies() {
+        let stats = [Stats::new("b", 200, 250, 1), Stats::new("a", 200, 0, 1), Stats::new("a", 200, u64::MAX, 1)];
+        let histograms = stats.iter().collect::<LatencyHistograms>();
+
+        assert_eq!(histograms.get_names(), vec!["a", "b"]);
+        assert_eq!(histograms.get_summary("b").unwrap().max, 250);
+
+        let summary = histograms.get_summary("a").unwrap();
+        assert_eq!(summary.min, 0);
+        assert!(summary.max >= HIGHEST_LATENCY);
+    }
+
+    #[test]
+    fn test_corrected_latencies() {
+        //A stall of 1 sec with an expected interval of 100 ms hides 9 requests which would have been sent during the stall
+        let mut stats = (0..90).map(|_| Stats::new("name1", 200, 1000, 1)).collect::<Vec<Stats>>();
+        stats.push(Stats::new("name1", 200, 1_000_000, 1));
+        stats.iter_mut().for_each(|s| s.expected_interval = 100_000);
+
+        let histograms = stats.iter().collect::<LatencyHistograms>();
+        assert!(histograms.is_corrected());
+
+        let raw = histograms.get_summary("name1").unwrap();
+        assert_eq!(raw.count, 91);
+        assert!(raw.pc_90 < 2000);
+
+        let corrected = histograms.get_corrected_summary("name1").unwrap();
+        assert_eq!(corrected.count, 100);
+        assert!(corrected.pc_95 >= 500_000);
+        assert_eq!(corrected.max, raw.max);
+    }
+
+    #[test]
+    fn test_no_correction_without_expected_interval() {
+        let stats = [Stats::new("name1", 200, 1_000_000, 1)];
+        let histograms = stats.iter().collect::<LatencyHistograms>();
+
+        assert!(!histograms.is_corrected());
+        assert!(histograms.get_corrected_summary("name1").is_none());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut histograms = [Stats::new("a", 200, 1000, 1)].iter().collect::<LatencyHistograms>();
+        let mut stats = [Stats::new("a", 200, 1500, 1), Stats::new("b", 200, 500, 1)];
+        stats[0].expected_interval = 1_000_000;
+        histograms.merge(stats.iter().collect());
+
+        assert_eq!(histograms.get_names(), vec!["a", "b"]);
+        let summary = histograms.get_summary("a").unwrap();
+        assert_eq!((summary.count, summary.min, summary.max), (2, 1000, 1500));
+        assert_eq!(histograms.get_corrected_summary("a").unwrap().count, 1);
+    }
+
+    #[test]
+    fn test_format_latency() {
+        assert_eq!(format_latency(1234), "1.234");
+        assert_eq!(format_latency(5), "0.005");
+        assert!(LatencyHistograms::new().is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::report::{
+    self,
+    aggregator::Aggregate,
+    dashboard::format_duration,
+    histogram,
+    timeline::TimeBucket
+};
+
+//Charts have at most these many points, interval of time buckets is chosen accordingly
+const MAX_POINTS: u64 = 300;
+
+const CHART_WIDTH: f64 = 900.0;
+const CHART_HEIGHT: f64 = 260.0;
+const MARGIN_LEFT: f64 = 60.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 30.0;
+const MARGIN_BOTTOM: f64 = 30.0;
+
+const STYLE: &str = "body{font-family:Helvetica,Arial,sans-serif;margin:24px;color:#222}\
+table{border-collapse:collapse;margin-bottom:24px}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:right}\
+th{background:#f3f3f3}td:first-child,th:first-child{text-align:left}\
+svg{display:block;margin-bottom:24px}\
+svg text{font-size:11px;fill:#555}";
+
+//Interval of charts when it is not provided
+pub fn get_interval(et: i64) -> u64 {
+    (et.max(0) as u64).div_ceil(MAX_POINTS).max(1)
+}
+
+//Report is a single html file with inline css and svg charts, so that it can be shared as is.
+//Charts are rendered from the interval stats of the aggregate
+pub fn render(aggregate: &Aggregate) -> String {
+    let summary = report::get_report_summary(aggregate);
+    let et = summary.execution_time;
+    let interval = aggregate.timeline.as_ref().map_or(1, |timeline| timeline.get_interval());
+
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Bombardier Report</title>\n<style>{}</style>\n</head>\n<body>\n", STYLE);
+    html.push_str("<h1>Bombardier Report</h1>\n");
+
+    html.push_str("<h2>Summary</h2>\n<table>\n<tr><th>Total Execution Time (in secs)</th><th>Total Hits</th><th>Hits/s</th><th>Total Errors</th><th>Error Rate</th></tr>\n");
+    html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td><td>{:.2}</td></tr>\n</table>\n",
+        et, summary.total_hits, summary.throughput, summary.total_errors, summary.error_rate));
+
+    html.push_str("<h2>Requests</h2>\n<p>Latencies are in ms</p>\n<table>\n<tr><th>Request</th><th>Total Hits</th><th>Hits/s</th><th>Min</th><th>Avg</th><th>Max</th>\
+        <th>90%</th><th>95%</th><th>99%</th><th>99.9%</th><th>99.99%</th><th>Errors</th><th>Error Rate</th></tr>\n");
+    for s in &summary.requests {
+        html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td></tr>\n",
+            escape(&s.name), s.hits, s.throughput, histogram::format_latency(s.latency.min), histogram::format_latency(s.latency.mean),
+            histogram::format_latency(s.latency.max), histogram::format_latency(s.latency.pc_90), histogram::format_latency(s.latency.pc_95),
+            histogram::format_latency(s.latency.pc_99), histogram::format_latency(s.latency.pc_99_9), histogram::format_latency(s.latency.pc_99_99),
+            s.errors, s.error_rate));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(&format!("<h2>Over time</h2>\n<p>Every point is an interval of {} sec(s)</p>\n", interval));
+    html.push_str(&render_charts(&summary.intervals, interval));
+
+    html.push_str("<h2>Status codes</h2>\n<p>Status 0 is recorded when no response was received</p>\n");
+    html.push_str(&render_status_distribution(aggregate));
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn render_charts(buckets: &[TimeBucket], interval: u64) -> String {
+    let x = buckets.iter().map(|b| b.start).collect::<Vec<u64>>();
+    let latency = |f: fn(&histogram::LatencySummary) -> u64| buckets.iter()
+        .map(|b| b.latency.as_ref().map(|l| f(l) as f64 / 1000.0))
+        .collect::<Vec<Option<f64>>>();
+
+    let rps = buckets.iter().map(|b| Some(b.hits as f64 / interval as f64)).collect();
+    let error_rate = buckets.iter()
+        .map(|b| match b.hits {
+            0 => None,
+            hits => Some(b.errors as f64 * 100.0 / hits as f64)
+        })
+        .collect();
+    let threads = buckets.iter().map(|b| Some(b.threads as f64)).collect();
+
+    let mut charts = line_chart("Latency (ms)", &x, &[("50%", "#2b8a3e", latency(|l| l.pc_50)),
+        ("90%", "#e67700", latency(|l| l.pc_90)), ("99%", "#c92a2a", latency(|l| l.pc_99))]);
+    charts.push_str(&line_chart("Hits/s", &x, &[("Hits/s", "#1864ab", rps)]));
+    charts.push_str(&line_chart("Error Rate (%)", &x, &[("Error Rate", "#c92a2a", error_rate)]));
+    charts.push_str(&line_chart("Active Threads", &x, &[("Threads", "#5f3dc4", threads)]));
+    charts
+}
+
+//Line chart of series of values at x (in secs), missing values are shown as gaps
+fn line_chart(title: &str, x: &[u64], series: &[(&str, &str, Vec<Option<f64>>)]) -> String {
+    let plot_width = CHART_WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = CHART_HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+
+    let max_y = series.iter()
+        .flat_map(|(_, _, values)| values.iter().flatten())
+        .fold(0.0_f64, |max, v| max.max(*v));
+    let max_y = match max_y > 0.0 {
+        true => max_y * 1.1,
+        false => 1.0
+    };
+
+    let x_start = x.first().copied().unwrap_or_default();
+    let x_span = (x.last().copied().unwrap_or_default() - x_start).max(1) as f64;
+    let px = |x: u64| MARGIN_LEFT + (x - x_start) as f64 / x_span * plot_width;
+    let py = |y: f64| MARGIN_TOP + plot_height - y / max_y * plot_height;
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+        <text x=\"{}\" y=\"16\" style=\"font-size:14px;fill:#222\">{}</text>\n", MARGIN_LEFT, escape(title), w = CHART_WIDTH, h = CHART_HEIGHT);
+
+    //horizontal grid lines with y axis labels
+    for i in 0..=4 {
+        let y = max_y * i as f64 / 4.0;
+        svg.push_str(&format!("<line x1=\"{l}\" x2=\"{r}\" y1=\"{y:.1}\" y2=\"{y:.1}\" stroke=\"#e5e5e5\"/>\
+            <text x=\"{tx}\" y=\"{ty:.1}\" text-anchor=\"end\">{}</text>\n",
+            format_value(y, max_y), l = MARGIN_LEFT, r = CHART_WIDTH - MARGIN_RIGHT, y = py(y), tx = MARGIN_LEFT - 6.0, ty = py(y) + 4.0));
+    }
+
+    //x axis labels as time since start
+    for i in 0..=5 {
+        let secs = x_start + (x_span * i as f64 / 5.0) as u64;
+        svg.push_str(&format!("<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n", 
+            px(secs), CHART_HEIGHT - 10.0, format_duration(secs)));
+    }
+
+    for (i, (name, color, values)) in series.iter().enumerate() {
+        let mut segment: Vec<String> = Vec::new();
+        for (x, value) in x.iter().zip(values) {
+            match value {
+                Some(value) => segment.push(format!("{:.1},{:.1}", px(*x), py(*value))),
+                None => {
+                    svg.push_str(&polyline(&segment, color));
+                    segment.clear();
+                }
+            }
+        }
+
+        svg.push_str(&polyline(&segment, color));
+
+        //legend
+        let lx = CHART_WIDTH - MARGIN_RIGHT - 90.0 * (series.len() - i) as f64;
+        svg.push_str(&format!("<rect x=\"{:.1}\" y=\"8\" width=\"10\" height=\"10\" fill=\"{}\"/><text x=\"{:.1}\" y=\"17\">{}</text>\n",
+            lx, color, lx + 14.0, escape(name)));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn polyline(points: &[String], color: &str) -> String {
+    match points.len() {
+        0 => String::new(),
+        1 => { //a single point is not visible as a line
+            let (x, y) = points[0].split_once(',').unwrap_or_default();
+            format!("<circle cx=\"{}\" cy=\"{}\" r=\"2\" fill=\"{}\"/>\n", x, y, color)
+        },
+        _ => format!("<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>\n", color, points.join(" "))
+    }
+}
+
+fn format_value(value: f64, max: f64) -> String {
+    match max >= 10.0 {
+        true => format!("{:.0}", value),
+        false => format!("{:.2}", value)
+    }
+}
+
+fn render_status_distribution(aggregate: &Aggregate) -> String {
+    let distribution = aggregate.get_requests().into_iter()
+        .map(|(name, r)| (name, &r.statuses))
+        .collect::<Vec<(&str, &BTreeMap<u16, usize>)>>();
+
+    let codes = distribution.iter()
+        .flat_map(|(_, counts)| counts.keys().copied())
+        .collect::<BTreeSet<u16>>();
+
+    let mut html = String::from("<table>\n<tr><th>Request</th>");
+    for code in &codes {
+        html.push_str(&format!("<th>{}</th>", code));
+    }
+    html.push_str("<th>Distribution</th></tr>\n");
+
+    for (name, counts) in distribution {
+        html.push_str(&format!("<tr><td>{}</td>", escape(name)));
+        for code in &codes {
+            html.push_str(&format!("<td>{}</td>", counts.get(code).copied().unwrap_or_default()));
+        }
+
+        html.push_str(&format!("<td>{}</td></tr>\n", status_bar(counts)));
+    }
+
+    html.push_str("</table>\n");
+    html
+}
+
+//Horizontal bar with a segment for every status code, proportional to its count
+fn status_bar(counts: &BTreeMap<u16, usize>) -> String {
+    const WIDTH: f64 = 300.0;
+    let total = counts.values().sum::<usize>().max(1) as f64;
+
+    let mut x = 0.0;
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"14\" viewBox=\"0 0 {w} 14\" style=\"margin:0\">", w = WIDTH);
+    for (code, count) in counts {
+        let width = *count as f64 / total * WIDTH;
+        svg.push_str(&format!("<rect x=\"{:.1}\" y=\"0\" width=\"{:.1}\" height=\"14\" fill=\"{}\"><title>{}: {}</title></rect>", 
+            x, width, get_status_color(*code), code, count));
+        x += width;
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+fn get_status_color(status: u16) -> &'static str {
+    match status {
+        200..=299 => "#2b8a3e",
+        300..=399 => "#1864ab",
+        400..=499 => "#e67700",
+        500..=599 => "#c92a2a",
+        _ => "#868e96"
+    }
+}
+
+//Used for xml too
+pub fn escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+
+    use crate::report::{html::*, stats::Stats};
+
+    fn get_stats() -> Vec<Stats> {
+        let now = Local::now();
+        [(0, "getOrders", 200), (1, "getOrders", 503), (2, "<script>", 200), (4, "getOrders", 0)].iter()
+            .map(|(offset, name, status)| {
+                let mut stats = Stats::new(name, *status, 1500, 2);
+                stats.timestamp = (now + Duration::seconds(*offset)).to_string();
+                stats
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_render() {
+        let html = render(&Aggregate::from_stats(&get_stats(), 1));
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.ends_with("</html>\n"));
+        assert!(html.contains("<td>getOrders</td><td>3</td>"));
+        assert!(html.contains("<td>&lt;script&gt;</td>"));
+        assert!(!html.contains("<script>"));
+        assert_eq!(html.matches("<polyline").count(), 6); //3 latency percentiles, hits, error rate and threads
+        assert!(html.contains("<th>0</th><th>200</th><th>503</th>"));
+        assert!(html.contains("<title>503: 1</title>"));
+    }
+
+    #[test]
+    fn test_line_chart_with_gaps() {
+        let chart = line_chart("Test", &[0, 1, 2, 3, 4], &[("series", "#000", vec![Some(1.0), Some(2.0), None, Some(3.0), Some(1.0)])]);
+        assert_eq!(chart.matches("<polyline").count(), 2);
+
+        let chart = line_chart("Test", &[0, 1, 2], &[("series", "#000", vec![Some(1.0), None, Some(3.0)])]);
+        assert_eq!(chart.matches("<circle").count(), 2);
+        assert!(!chart.contains("<polyline"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("<a href=\"x\">'&'</a>"), "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;");
+    }
+}
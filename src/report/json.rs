@@ -0,0 +1,24 @@
+use crate::report::ReportSummary;
+
+//Latencies are in ms
+pub fn render(summary: &ReportSummary) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::{self, aggregator::Aggregate, json::*, stats::Stats};
+
+    #[test]
+    fn test_render() {
+        let stats = vec![Stats::new("getOrders", 200, 1500, 1), Stats::new("getOrders", 500, 2500, 1)];
+        let json: serde_json::Value = serde_json::from_str(&render(&report::get_report_summary(&Aggregate::from_stats(&stats, 0))).unwrap()).unwrap();
+
+        assert_eq!(json["totalHits"], 2);
+        assert_eq!(json["totalErrors"], 1);
+        assert_eq!(json["errorRate"], 50.0);
+        assert_eq!(json["requests"][0]["name"], "getOrders");
+        assert_eq!(json["requests"][0]["latency"]["min"], 1.5);
+        assert_eq!(json["requests"][0]["latency"]["p99.9"], 2.501);
+    }
+}
@@ -0,0 +1,72 @@
+use async_trait::async_trait;
+use log::{info, warn};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::report::{sink::ResultSink, stats::Stats};
+
+//Writes every stats as a json object on a line of its own, so that the file can be tailed by log shippers
+pub struct JsonlWriter {
+    file: fs::File
+}
+
+impl JsonlWriter {
+    pub async fn new(file: &str) -> Result<JsonlWriter, std::io::Error> {
+        info!("Initiating JsonlWriter for {}", file);
+        Ok(JsonlWriter { file: fs::File::create(file).await? })
+    }
+}
+
+#[async_trait]
+impl ResultSink for JsonlWriter {
+    async fn write(&mut self, stats: &[Stats]) {
+        let mut lines = String::with_capacity(stats.len() * 300);
+        for s in stats {
+            match serde_json::to_string(s) {
+                Ok(line) => {
+                    lines.push_str(&line);
+                    lines.push('\n');
+                },
+                Err(err) => warn!("Unable to serialize stat {} due to error {}", s, err)
+            }
+        }
+
+        if let Err(err) = self.file.write_all(lines.as_bytes()).await {
+            warn!("Unable to write stats to file due to error {}", err)
+        }
+    }
+
+    async fn finish(&mut self) {
+        if let Err(err) = self.file.flush().await {
+            warn!("Unable to flush jsonl file due to error {}", err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::{model::ErrorKind, report::jsonl::*};
+
+    #[tokio::test]
+    async fn test_write() {
+        let dir = TempDir::new("test_write_jsonl").unwrap();
+        let file_path = dir.path().join("stats.jsonl");
+
+        let mut timeout_stats = Stats::new("get, \"orders\"", 0, 60_000_000, 2);
+        timeout_stats.error_kind = Some(ErrorKind::Timeout);
+        timeout_stats.error_message = String::from("operation timed out\nafter 60s");
+
+        let mut writer = JsonlWriter::new(file_path.to_str().unwrap()).await.unwrap();
+        ResultSink::write(&mut writer, &[Stats::new("health", 200, 1000, 2)]).await;
+        ResultSink::write(&mut writer, &[timeout_stats]).await;
+        writer.finish().await;
+
+        let content = fs::read_to_string(file_path).await.unwrap();
+        let stats = content.lines().map(|line| serde_json::from_str::<Stats>(line).unwrap()).collect::<Vec<Stats>>();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].name.as_str(), stats[0].status), ("health", 200));
+        assert_eq!((stats[1].name.as_str(), stats[1].error_kind.as_ref()), ("get, \"orders\"", Some(&ErrorKind::Timeout)));
+        assert_eq!(stats[1].error_message, "operation timed out\nafter 60s");
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    model::Thresholds,
+    report::{ReportSummary, histogram, html::escape, threshold}
+};
+
+//Every request is a testcase which fails if any of its thresholds is breached
+pub fn render(summary: &ReportSummary, thresholds: &Thresholds) -> String {
+    let testcases = summary.requests.iter()
+        .map(|request| (request, threshold::get_breaches(request, thresholds)))
+        .collect::<Vec<_>>();
+
+    let failures = testcases.iter().filter(|(_, breaches)| !breaches.is_empty()).count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<testsuites name=\"bombardier\" tests=\"{tests}\" failures=\"{failures}\" time=\"{time}\">\n\
+        <testsuite name=\"bombardier\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" time=\"{time}\">\n",
+        tests = testcases.len(), failures = failures, time = summary.execution_time));
+
+    //time of a testcase is its average latency in secs
+    for (request, breaches) in &testcases {
+        xml.push_str(&format!("<testcase name=\"{}\" classname=\"bombardier\" time=\"{:.6}\"", 
+            escape(&request.name), histogram::to_ms(request.latency.mean) / 1000.0));
+
+        match breaches.is_empty() {
+            true => xml.push_str("/>\n"),
+            false => xml.push_str(&format!(">\n<failure type=\"threshold\" message=\"{}\">{}</failure>\n</testcase>\n",
+                escape(&breaches[0]), escape(&breaches.join("\n"))))
+        }
+    }
+
+    xml.push_str("</testsuite>\n</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::{self, aggregator::Aggregate, junit::*, stats::Stats};
+
+    #[test]
+    fn test_render() {
+        let stats = vec![Stats::new("getOrders", 200, 1500, 1), Stats::new("getHealth", 200, 500, 1)];
+        let thresholds: Thresholds = serde_yaml::from_str("p99: 1").unwrap();
+        let xml = render(&report::get_report_summary(&Aggregate::from_stats(&stats, 0)), &thresholds);
+
+        assert!(xml.contains("<testsuites name=\"bombardier\" tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("<testcase name=\"getHealth\" classname=\"bombardier\" time=\"0.000500\"/>"));
+        assert!(xml.contains("<testcase name=\"getOrders\" classname=\"bombardier\" time=\"0.001500\">\n\
+            <failure type=\"threshold\" message=\"p99 1.500 ms is above threshold of 1 ms\">"));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{
+    model::Thresholds,
+    report::{self, ReportSummary, dashboard::format_duration, histogram::{self, LatencySummary}, threshold}
+};
+
+//Tables are same as the text report, latencies are in ms
+pub fn render(summary: &ReportSummary, thresholds: &Thresholds) -> String {
+    let mut md = match summary.run.as_ref() {
+        Some(run) => format!("{}\n\n", escape(&report::get_run_description(run))),
+        None => String::new()
+    };
+
+    md.push_str("| Request | Total Hits | Hits/s | Min | Avg | Max | 90% | 95% | 99% | 99.9% | 99.99% | Errors | Error Rate |\n");
+    md.push_str("|---|---|---|---|---|---|---|---|---|---|---|---|---|\n");
+
+    for s in &summary.requests {
+        md.push_str(&format!("| {} | {} | {:.2} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {:.2} |\n",
+            escape(&s.name), s.hits, s.throughput, histogram::format_latency(s.latency.min), histogram::format_latency(s.latency.mean),
+            histogram::format_latency(s.latency.max), histogram::format_latency(s.latency.pc_90), histogram::format_latency(s.latency.pc_95),
+            histogram::format_latency(s.latency.pc_99), histogram::format_latency(s.latency.pc_99_9), histogram::format_latency(s.latency.pc_99_99),
+            s.errors, s.error_rate));
+    }
+
+    md.push_str("\n| Total Execution Time (in secs) | Total Hits | Hits/s | Total Errors | Error Rate |\n|---|---|---|---|---|\n");
+    md.push_str(&format!("| {} | {} | {:.2} | {} | {:.2} |\n", summary.execution_time, summary.total_hits, 
+        summary.throughput, summary.total_errors, summary.error_rate));
+
+    if !summary.intervals.is_empty() {
+        md.push_str("\n| Interval | Threads | Hits | Hits/s | Avg | 50% | 90% | 95% | 99% | Errors | Error Rate |\n|---|---|---|---|---|---|---|---|---|---|---|\n");
+        for b in &summary.intervals {
+            let latency = |f: fn(&LatencySummary) -> u64| b.latency.as_ref().map_or(String::from("-"), |l| histogram::format_latency(f(l)));
+            md.push_str(&format!("| {} - {} | {} | {} | {:.2} | {} | {} | {} | {} | {} | {} | {:.2} |\n", format_duration(b.start), 
+                format_duration(b.end), b.threads, b.hits, b.throughput, latency(|l| l.mean), latency(|l| l.pc_50), 
+                latency(|l| l.pc_90), latency(|l| l.pc_95), latency(|l| l.pc_99), b.errors, b.error_rate));
+        }
+    }
+
+    let breaches = summary.requests.iter()
+        .flat_map(|s| threshold::get_breaches(s, thresholds).into_iter().map(move |b| format!("- **{}**: {}\n", escape(&s.name), b)))
+        .collect::<String>();
+
+    if !breaches.is_empty() {
+        md.push_str("\n**Threshold breaches**\n\n");
+        md.push_str(&breaches);
+    }
+
+    md
+}
+
+//Pipes would break the table
+fn escape(value: &str) -> String {
+    value.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::{self, aggregator::Aggregate, markdown::*, run::Run, stats::Stats};
+
+    #[test]
+    fn test_render() {
+        let stats = vec![Stats::new("get|Orders", 200, 1500, 1), Stats::new("get|Orders", 503, 2500, 1)];
+        let thresholds: Thresholds = serde_yaml::from_str("errorRate: 1").unwrap();
+        let md = render(&report::get_report_summary(&Aggregate::from_stats(&stats, 0)), &thresholds);
+
+        assert!(md.contains("| get\\|Orders | 2 |"));
+        assert!(md.contains("| 1.500 | 2.000 | 2.501 |"));
+        assert!(md.contains("- **get\\|Orders**: errorRate 50.00 % is above threshold of 1 %\n"));
+        assert!(!render(&report::get_report_summary(&Aggregate::from_stats(&stats, 0)), &Thresholds::default()).contains("Threshold breaches"));
+        assert!(!md.contains("| Interval |"));
+
+        let md = render(&report::get_report_summary(&Aggregate::from_stats(&stats, 10)), &Thresholds::default());
+        assert!(md.contains("| 00:00:00 - 00:00:10 | 1 | 2 | 0.20 |"));
+        assert!(md.starts_with("| Request |"));
+    }
+
+    #[test]
+    fn test_render_with_run() {
+        let mut run = Run { id: String::from("6f1c"), started_at: String::from("2021-01-01T10:00:00+05:30"), ..Default::default() };
+        run.tags.insert(String::from("env"), String::from("qa"));
+
+        let stats = vec![Stats::new("getOrders", 200, 1500, 1)];
+        let summary = ReportSummary { run: Some(run), ..report::get_report_summary(&Aggregate::from_stats(&stats, 0)) };
+        assert!(render(&summary, &Thresholds::default()).starts_with("Run 6f1c from 2021-01-01T10:00:00+05:30 to - (env=qa)\n\n| Request |"));
+    }
+}
@@ -0,0 +1,311 @@
+use log::{error, info};
+use parking_lot::Mutex;
+use warp::{Filter, Rejection, Reply};
+
+use std::{collections::BTreeMap, fmt::Write, net::SocketAddr, sync::Arc};
+
+use crate::report::{self, stats::Stats};
+
+//Upper bounds (in seconds) of the buckets of latency histograms
+pub const BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+//Metric families as name, type and help
+const FAMILIES: [(&str, &str, &str); 5] = [
+    ("bombardier_requests_total", "counter", "Requests sent including retries"),
+    ("bombardier_errors_total", "counter", "Failed requests by kind of error"),
+    ("bombardier_request_duration_seconds", "histogram", "Latency of requests"),
+    ("bombardier_stats_queued", "gauge", "Stats waiting in the queue of a sink"),
+    ("bombardier_stats_dropped_total", "counter", "Stats not written to a sink as its queue was full")
+];
+
+//Request name, node id (empty when not distributed) and status
+type Labels = (String, String, u16);
+
+#[derive(Default)]
+struct LatencySeries {
+    buckets: [u64; BUCKETS.len()],
+    sum: u64,
+    count: u64
+}
+
+#[derive(Default)]
+struct Registry {
+    latencies: BTreeMap<Labels, LatencySeries>,
+
+    //Request name, node id and kind of error
+    errors: BTreeMap<(String, String, String), u64>,
+
+    //Queued and dropped stats by sink
+    sinks: BTreeMap<String, (usize, u64)>
+}
+
+//Latency histogram of a request and status, buckets are not cumulative and the last one is of latencies beyond the last bound
+pub struct RequestSeries {
+    pub name: String,
+    pub node: String,
+    pub status: u16,
+    pub buckets: Vec<u64>,
+    pub sum: f64,
+    pub count: u64
+}
+
+pub struct ErrorSeries {
+    pub name: String,
+    pub node: String,
+    pub kind: String,
+    pub count: u64
+}
+
+//Value of a series in Prometheus data model, labels are in the order they are rendered
+pub struct Sample {
+    pub family: &'static str,
+    pub name: String,
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64
+}
+
+//Counters and latency histograms of all the requests since start, exposed in Prometheus text format.
+//Shared by the stats consumer which records the stats and the server which renders them on every scrape
+#[derive(Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn record(&self, stats: &[Stats]) {
+        let mut registry = self.registry.lock();
+        for s in stats {
+            let series = registry.latencies.entry((s.name.clone(), s.node_id.clone(), s.status)).or_default();
+            if let Some(index) = BUCKETS.iter().position(|bucket| s.latency as f64 <= bucket * 1_000_000.0) {
+                series.buckets[index] += 1;
+            }
+
+            series.sum += s.latency;
+            series.count += 1;
+
+            if let Some(kind) = report::get_error_category(s) {
+                *registry.errors.entry((s.name.clone(), s.node_id.clone(), kind)).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn set_queued(&self, sink: &str, queued: usize) {
+        self.registry.lock().sinks.entry(sink.to_owned()).or_default().0 = queued;
+    }
+
+    pub fn add_dropped(&self, sink: &str, dropped: u64) {
+        self.registry.lock().sinks.entry(sink.to_owned()).or_default().1 += dropped;
+    }
+
+    pub fn get_series(&self) -> (Vec<RequestSeries>, Vec<ErrorSeries>) {
+        let registry = self.registry.lock();
+        let requests = registry.latencies.iter()
+            .map(|((name, node, status), series)| {
+                let mut buckets = series.buckets.to_vec();
+                buckets.push(series.count - series.buckets.iter().sum::<u64>());
+
+                RequestSeries {
+                    name: name.clone(),
+                    node: node.clone(),
+                    status: *status,
+                    buckets,
+                    sum: series.sum as f64 / 1_000_000.0,
+                    count: series.count
+                }
+            })
+            .collect();
+
+        let errors = registry.errors.iter()
+            .map(|((name, node, kind), count)| ErrorSeries { name: name.clone(), node: node.clone(), kind: kind.clone(), count: *count })
+            .collect();
+
+        (requests, errors)
+    }
+
+    //Samples in the order of families, histogram buckets are cumulative
+    pub fn get_samples(&self) -> Vec<Sample> {
+        let (requests, errors) = self.get_series();
+        let mut samples = Vec::new();
+
+        for r in &requests {
+            samples.push(get_sample(0, "", get_labels(&r.name, &r.node, ("status", r.status.to_string())), r.count as f64));
+        }
+
+        for e in &errors {
+            samples.push(get_sample(1, "", get_labels(&e.name, &e.node, ("kind", e.kind.clone())), e.count as f64));
+        }
+
+        for r in &requests {
+            let labels = get_labels(&r.name, &r.node, ("status", r.status.to_string()));
+
+            let mut count = 0;
+            for (bucket, bucket_count) in BUCKETS.iter().zip(&r.buckets) {
+                count += bucket_count;
+                let mut labels = labels.clone();
+                labels.push(("le", bucket.to_string()));
+                samples.push(get_sample(2, "_bucket", labels, count as f64));
+            }
+
+            let mut inf_labels = labels.clone();
+            inf_labels.push(("le", String::from("+Inf")));
+            samples.push(get_sample(2, "_bucket", inf_labels, r.count as f64));
+            samples.push(get_sample(2, "_sum", labels.clone(), r.sum));
+            samples.push(get_sample(2, "_count", labels, r.count as f64));
+        }
+
+        let sinks = self.registry.lock().sinks.clone();
+        for (sink, (queued, _)) in &sinks {
+            samples.push(get_sample(3, "", vec![("sink", sink.clone())], *queued as f64));
+        }
+
+        for (sink, (_, dropped)) in &sinks {
+            samples.push(get_sample(4, "", vec![("sink", sink.clone())], *dropped as f64));
+        }
+
+        samples
+    }
+
+    pub fn render(&self) -> String {
+        let samples = self.get_samples();
+        let mut content = String::new();
+
+        for (family, metric_type, help) in FAMILIES {
+            writeln!(content, "# HELP {} {}", family, help).unwrap();
+            writeln!(content, "# TYPE {} {}", family, metric_type).unwrap();
+
+            for sample in samples.iter().filter(|s| s.family == family) {
+                let labels = sample.labels.iter()
+                    .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                writeln!(content, "{}{{{}}} {}", sample.name, labels, sample.value).unwrap();
+            }
+        }
+
+        content
+    }
+}
+
+fn get_sample(family: usize, suffix: &str, labels: Vec<(&'static str, String)>, value: f64) -> Sample {
+    let family = FAMILIES[family].0;
+    Sample { family, name: format!("{}{}", family, suffix), labels, value }
+}
+
+//Node label is added only for the stats of distributed tests
+fn get_labels(name: &str, node: &str, label: (&'static str, String)) -> Vec<(&'static str, String)> {
+    let mut labels = vec![("request", name.to_owned())];
+    if !node.is_empty() {
+        labels.push(("node", node.to_owned()));
+    }
+
+    labels.push(label);
+    labels
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub fn route(metrics: Arc<Metrics>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("metrics")
+        .and(warp::get())
+        .map(move || warp::reply::with_header(metrics.render(), "content-type", "text/plain; version=0.0.4"))
+}
+
+//Serves the metrics on /metrics till the process exits
+pub fn serve(port: u16, metrics: Arc<Metrics>) -> Result<(), Box<dyn std::error::Error>> {
+    let address: SocketAddr = ([0, 0, 0, 0], port).into();
+    let (address, server) = match warp::serve(route(metrics)).try_bind_ephemeral(address) {
+        Ok(server) => server,
+        Err(err) => {
+            error!("Error starting metrics server on port {} : {}", port, err);
+            return Err(err.into())
+        }
+    };
+
+    info!("Serving metrics on http://{}/metrics", address);
+    tokio::spawn(server);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{model::ErrorKind, report::{metrics::*, stats::Stats}};
+
+    fn get_stats() -> Vec<Stats> {
+        let mut timeout_stats = Stats::new("getOrders", 0, 30_000_000, 1);
+        timeout_stats.error_kind = Some(ErrorKind::Timeout);
+
+        vec![Stats::new("getOrders", 200, 4000, 1), Stats::new("getOrders", 200, 200_000, 1), Stats::new("getOrders", 503, 7000, 1),
+            timeout_stats, Stats::new("get \"health\"", 200, 60_000_000, 1)]
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.record(&get_stats());
+        let content = metrics.render();
+
+        assert!(content.contains("bombardier_requests_total{request=\"getOrders\",status=\"200\"} 2\n"));
+        assert!(content.contains("bombardier_requests_total{request=\"getOrders\",status=\"503\"} 1\n"));
+        assert!(content.contains("bombardier_errors_total{request=\"getOrders\",kind=\"5xx\"} 1\n"));
+        assert!(content.contains("bombardier_errors_total{request=\"getOrders\",kind=\"timeout\"} 1\n"));
+
+        assert!(content.contains("bombardier_request_duration_seconds_bucket{request=\"getOrders\",status=\"200\",le=\"0.005\"} 1\n"));
+        assert!(content.contains("bombardier_request_duration_seconds_bucket{request=\"getOrders\",status=\"200\",le=\"0.1\"} 1\n"));
+        assert!(content.contains("bombardier_request_duration_seconds_bucket{request=\"getOrders\",status=\"200\",le=\"0.25\"} 2\n"));
+        assert!(content.contains("bombardier_request_duration_seconds_bucket{request=\"getOrders\",status=\"200\",le=\"+Inf\"} 2\n"));
+        assert!(content.contains("bombardier_request_duration_seconds_sum{request=\"getOrders\",status=\"200\"} 0.204\n"));
+        assert!(content.contains("bombardier_request_duration_seconds_count{request=\"getOrders\",status=\"200\"} 2\n"));
+
+        //beyond the last bucket
+        assert!(content.contains("bombardier_request_duration_seconds_bucket{request=\"get \\\"health\\\"\",status=\"200\",le=\"30\"} 0\n"));
+        assert!(content.contains("bombardier_request_duration_seconds_bucket{request=\"get \\\"health\\\"\",status=\"200\",le=\"+Inf\"} 1\n"));
+    }
+
+    #[test]
+    fn test_render_with_node() {
+        let metrics = Metrics::new();
+        let stats = get_stats().into_iter()
+            .map(|s| Stats { node_id: String::from("10.0.0.2:5000"), ..s })
+            .collect::<Vec<Stats>>();
+
+        metrics.record(&stats);
+        metrics.record(&stats);
+
+        let content = metrics.render();
+        assert!(content.contains("bombardier_requests_total{request=\"getOrders\",node=\"10.0.0.2:5000\",status=\"200\"} 4\n"));
+        assert!(content.contains("bombardier_errors_total{request=\"getOrders\",node=\"10.0.0.2:5000\",kind=\"timeout\"} 2\n"));
+    }
+
+    #[test]
+    fn test_render_sinks() {
+        let metrics = Metrics::new();
+        metrics.set_queued("report", 20);
+        metrics.set_queued("database", 300);
+        metrics.add_dropped("database", 100);
+        metrics.add_dropped("database", 50);
+        metrics.set_queued("report", 0);
+
+        let content = metrics.render();
+        assert!(content.contains("# TYPE bombardier_stats_queued gauge\nbombardier_stats_queued{sink=\"database\"} 300\nbombardier_stats_queued{sink=\"report\"} 0\n"));
+        assert!(content.contains("bombardier_stats_dropped_total{sink=\"database\"} 150\n"));
+        assert!(content.contains("bombardier_stats_dropped_total{sink=\"report\"} 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_route() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.record(&get_stats());
+
+        let response = warp::test::request().method("GET").path("/metrics").reply(&route(metrics)).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "text/plain; version=0.0.4");
+        assert!(String::from_utf8_lossy(response.body()).contains("bombardier_requests_total{request=\"getOrders\",status=\"200\"} 2"));
+    }
+}
@@ -0,0 +1,283 @@
+use async_trait::async_trait;
+use log::{error, info, warn};
+use tokio::{net::TcpStream, sync::Mutex};
+use tokio_tungstenite::MaybeTlsStream;
+
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    model::{Config, Output},
+    protocol::socket::WebSocketSink,
+    report::{jsonl::JsonlWriter, run::{self, Run}, stats::Stats, summary::SummaryWriter, writer},
+    storage::{influxdb, otlp, postgres, prometheus}
+};
+
+pub type Websocket = Arc<Mutex<Option<WebSocketSink<MaybeTlsStream<TcpStream>>>>>;
+
+//Outputs written to files or stdout of the machine running the test, nodes send their stats to the hub instead
+const LOCAL_OUTPUTS: [&str; 5] = ["csv", "binary", "binary-zstd", "jsonl", "stdout-summary"];
+
+//Destination of the stats of a run, written by a task of its own
+#[async_trait]
+pub trait ResultSink {
+    async fn write(&mut self, stats: &[Stats]);
+
+    //Called once all the stats are written, for sinks which buffer stats or record the end of the run
+    async fn finish(&mut self) {}
+
+    //Sinks like databases drop the stats they cannot keep up with, instead of slowing down the run
+    fn drops_when_full(&self) -> bool {
+        false
+    }
+}
+
+//Report file with its run file, end of the run is written once all the stats are written
+struct ReportSink {
+    writer: Box<dyn ResultSink + Send>,
+    report_file: String,
+    run: Run
+}
+
+#[async_trait]
+impl ResultSink for ReportSink {
+    async fn write(&mut self, stats: &[Stats]) {
+        self.writer.write(stats).await
+    }
+
+    async fn finish(&mut self) {
+        self.writer.finish().await;
+
+        self.run.end();
+        if let Err(err) = run::write(&self.report_file, &self.run).await {
+            error!("Error writing end of run {} : {}", self.run.id, err);
+        }
+    }
+}
+
+//Stats of distributed tests are sent to the hub, followed by done
+struct HubSink(Websocket);
+
+#[async_trait]
+impl ResultSink for HubSink {
+    async fn write(&mut self, stats: &[Stats]) {
+        self.0.lock().await.as_mut().unwrap().write_stats(stats).await
+    }
+
+    async fn finish(&mut self) {
+        self.0.lock().await.as_mut().unwrap().write(String::from("done")).await
+    }
+}
+
+//Report file in the report format and the database are the outputs when outputs are not configured
+pub fn get_outputs(config: &Config) -> Vec<Output> {
+    if !config.outputs.is_empty() {
+        return config.outputs.clone();
+    }
+
+    let report_format = match config.report_format.is_empty() {
+        true => "csv",
+        false => &config.report_format
+    };
+
+    let mut outputs = vec![Output::new(report_format)];
+    if !config.database.db_type.is_empty() {
+        outputs.push(Output { file: String::new(), database: config.database.clone() });
+    }
+
+    outputs
+}
+
+//Sinks are named by the type of their output, with the position of the output when the type is repeated
+pub async fn get_sinks(config: &Config, run: &Run, websocket: Websocket) -> Result<Vec<(String, Box<dyn ResultSink + Send>)>, String> {
+    let mut sinks: Vec<(String, Box<dyn ResultSink + Send>)> = Vec::new();
+    if config.distributed {
+        sinks.push((String::from("hub"), Box::new(HubSink(websocket))));
+    }
+
+    let outputs = get_outputs(config);
+    for (i, output) in outputs.iter().enumerate() {
+        let output_type = output.get_type();
+        if config.distributed && LOCAL_OUTPUTS.contains(&output_type.as_str()) {
+            info!("Output {} is not written by nodes, stats are sent to the hub", output_type);
+            continue;
+        }
+
+        let sink = match output_type.as_str() {
+            "csv" | "binary" | "binary-zstd" => get_report_sink(output, config, run).await?,
+            "jsonl" => {
+                let file = get_jsonl_file(output, config);
+                match JsonlWriter::new(&file).await {
+                    Ok(writer) => Box::new(writer),
+                    Err(err) => return Err(format!("Error creating {} : {}", file, err))
+                }
+            },
+            "stdout-summary" => Box::new(SummaryWriter::new()),
+            "influxdb" | "prometheus" | "otlp" | "postgres" => match get_db_writer(output, config, run).await {
+                Some(writer) => writer,
+                None => continue
+            },
+            _ => return Err(format!("Invalid output type: {}", output.database.db_type))
+        };
+
+        let name = match outputs.iter().filter(|o| o.get_type() == output_type).count() {
+            1 => output_type,
+            _ => format!("{}:{}", output_type, i + 1)
+        };
+
+        sinks.push((name, sink));
+    }
+
+    if sinks.is_empty() {
+        warn!("No outputs to write the stats to");
+    }
+
+    Ok(sinks)
+}
+
+//File of the output, else report file when set, else default report file of the type
+async fn get_report_sink(output: &Output, config: &Config, run: &Run) -> Result<Box<dyn ResultSink + Send>, String> {
+    let output_type = output.get_type();
+    let report_file = match (output.file.is_empty(), config.report_file.is_empty()) {
+        (false, _) => output.file.clone(),
+        (true, false) => config.report_file.clone(),
+        (true, true) => writer::get_default_report_file(&output_type).to_owned()
+    };
+
+    let writer = match writer::get_report_writer(&report_file, &output_type).await {
+        Ok(writer) => writer,
+        Err(err) => return Err(format!("Error creating {} : {}", report_file, err))
+    };
+
+    if let Err(err) = run::write(&report_file, run).await {
+        return Err(format!("Error writing run file: {}", err))
+    }
+
+    Ok(Box::new(ReportSink { writer, report_file, run: run.clone() }))
+}
+
+//File of the output, else report file with jsonl extension
+fn get_jsonl_file(output: &Output, config: &Config) -> String {
+    if !output.file.is_empty() {
+        return output.file.clone();
+    }
+
+    let report_file = match config.report_file.is_empty() {
+        true => "report",
+        false => &config.report_file
+    };
+
+    Path::new(report_file).with_extension("jsonl").to_string_lossy().to_string()
+}
+
+//Database is skipped when it cannot be initialized, so that the test can still run
+async fn get_db_writer(output: &Output, config: &Config, run: &Run) -> Option<Box<dyn ResultSink + Send>> {
+    let db = &output.database;
+    let output_type = output.get_type();
+    info!("Initiating {} writer", output_type);
+
+    let writer: Option<Box<dyn ResultSink + Send>> = match output_type.as_str() {
+        "influxdb" => influxdb::InfluxDBWriter::new(db, run).map(|writer| Box::new(writer) as _),
+        "prometheus" => prometheus::PrometheusWriter::new(db, run).map(|writer| Box::new(writer) as _),
+        "otlp" => otlp::OtlpWriter::new(db, run).map(|writer| Box::new(writer) as _),
+        "postgres" => postgres::PostgresWriter::new(db, config.node_count, run).await.map(|writer| Box::new(writer) as _),
+        _ => None
+    };
+
+    if writer.is_none() {
+        error!("{} initialization failed", output_type);
+    }
+
+    writer
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::report::sink::*;
+
+    fn get_config(yaml: &str, report_file: &str) -> Config {
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.report_file = report_file.to_owned();
+        config.report_format = String::from("csv");
+        config
+    }
+
+    #[test]
+    fn test_parse_outputs() {
+        let config = get_config("
+            outputs:
+              - csv
+              - type: jsonl
+                file: stats.jsonl
+              - type: influxdb
+                url: http://localhost:8086
+                name: bombardier", "");
+
+        let outputs = config.outputs.iter().map(|o| (o.get_type(), o.file.as_str(), o.database.url.as_str())).collect::<Vec<_>>();
+        assert_eq!(outputs, vec![(String::from("csv"), "", ""), (String::from("jsonl"), "stats.jsonl", ""),
+            (String::from("influxdb"), "", "http://localhost:8086")]);
+        assert_eq!(config.outputs[2].database.name, "bombardier");
+
+        let value = serde_json::to_value(&config.outputs[1]).unwrap();
+        assert_eq!((&value["type"], &value["file"]), (&serde_json::json!("jsonl"), &serde_json::json!("stats.jsonl")));
+    }
+
+    #[test]
+    fn test_get_outputs() {
+        let config = get_config("threadCount: 1", "");
+        assert_eq!(get_outputs(&config).iter().map(|o| o.get_type()).collect::<Vec<_>>(), vec!["csv"]);
+
+        let mut config = get_config("database:\n  type: influxDB\n  url: http://localhost:8086", "");
+        config.report_format = String::from("binary-zstd");
+        let outputs = get_outputs(&config);
+        assert_eq!(outputs.iter().map(|o| o.get_type()).collect::<Vec<_>>(), vec!["binary-zstd", "influxdb"]);
+        assert_eq!(outputs[1].database.url, "http://localhost:8086");
+
+        let config = get_config("outputs: [stdout-summary]\ndatabase:\n  type: influxdb", "");
+        assert_eq!(get_outputs(&config).iter().map(|o| o.get_type()).collect::<Vec<_>>(), vec!["stdout-summary"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_sinks() {
+        let dir = TempDir::new("test_get_sinks").unwrap();
+        let report_file = dir.path().join("report.csv");
+        let config = get_config(&format!("outputs: [csv, jsonl, stdout-summary, {{type: csv, file: {}}}]", dir.path().join("copy.csv").display()),
+            report_file.to_str().unwrap());
+        let run = Run::new(&config, "", Default::default());
+
+        let mut sinks = get_sinks(&config, &run, Arc::new(Mutex::new(None))).await.unwrap();
+        assert_eq!(sinks.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["csv:1", "jsonl", "stdout-summary", "csv:4"]);
+        assert!(sinks.iter().all(|(_, sink)| !sink.drops_when_full()));
+
+        for (_, sink) in sinks.iter_mut() {
+            sink.write(&[Stats::new("getOrders", 200, 1000, 1)]).await;
+            sink.finish().await;
+        }
+
+        for file in ["report.csv", "copy.csv"] {
+            let content = tokio::fs::read_to_string(dir.path().join(file)).await.unwrap();
+            assert_eq!(content.lines().count(), 2);
+
+            let run = run::read(dir.path().join(file).to_str().unwrap()).await.unwrap();
+            assert!(!run.ended_at.is_empty());
+        }
+
+        let content = tokio::fs::read_to_string(dir.path().join("report.jsonl")).await.unwrap();
+        assert!(content.starts_with("{\"timestamp\""));
+    }
+
+    #[tokio::test]
+    async fn test_get_sinks_of_node() {
+        let mut config = get_config("outputs: [csv, jsonl, {type: influxdb, url: http://localhost:8086, name: db}, {type: influxdb}]", "");
+        config.distributed = true;
+
+        //influxdb without url is skipped
+        let sinks = get_sinks(&config, &Run::default(), Arc::new(Mutex::new(None))).await.unwrap();
+        assert_eq!(sinks.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["hub", "influxdb:3"]);
+        assert!(sinks[1].1.drops_when_full());
+
+        config.outputs.push(Output::new("xml"));
+        assert_eq!(get_sinks(&config, &Run::default(), Arc::new(Mutex::new(None))).await.err(), Some(String::from("Invalid output type: xml")));
+    }
+}
@@ -1,227 +1,451 @@
 use chrono::Local;
-use crossbeam::channel::{self, Sender, Receiver};
 use log::{error, info, warn};
 use serde::{Serialize, Deserialize};
-use tokio::{net::TcpStream, sync::Mutex, task, task::JoinHandle};
-use tokio_tungstenite::MaybeTlsStream;
+use tokio::{sync::mpsc, task, task::JoinHandle, time::{self, Duration, MissedTickBehavior}};
 
-use std::{fmt, option::Option, sync::Arc};
+use std::{fmt, option::Option, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
 
 use crate::{
-    model::{Database, Config},
-    report::csv,
-    protocol::socket::WebSocketSink, 
-    storage::{self, DBWriter, influxdb}
+    model::{Config, ErrorKind, StatsSettings},
+    report::{dashboard::Dashboard, histogram::LatencyHistograms, metrics::Metrics, run::Run, sink::{self, ResultSink}}
 };
 
-use super::csv::CSVWriter;
+//Stats of token requests made by bombardier itself are named with this prefix
+pub const TOKEN_REQUEST_PREFIX: &str = "oauth2:";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Stats {
     pub timestamp: String,
     pub thread_count: u16,
     pub status: u16,
-    pub latency: u32,
+    pub latency: u64,
     pub name: String,
+
+    #[serde(default = "default_attempt")]
+    pub attempt: u32,
+
+    #[serde(default)]
+    pub error_kind: Option<ErrorKind>,
+
+    #[serde(default)]
+    pub ttfb: u64,
+
+    #[serde(default)]
+    pub download: u64,
+
+    //Time (in µs) taken for DNS lookup, TCP connect and TLS handshake, 0 when an open connection is reused
+    #[serde(default)]
+    pub dns: u64,
+
+    #[serde(default)]
+    pub connect: u64,
+
+    #[serde(default)]
+    pub tls: u64,
+
+    #[serde(default)]
+    pub bytes_sent: u64,
+
+    #[serde(default)]
+    pub bytes_received: u64,
+
+    //Interval (in µs) at which requests were intended to be sent, 0 if pacing is not configured
+    #[serde(default)]
+    pub expected_interval: u64,
+
+    #[serde(default)]
+    pub scenario: String,
+
+    //Thread and iteration of the thread which sent the request, starting from 1
+    #[serde(default)]
+    pub thread_id: u32,
+
+    #[serde(default)]
+    pub iteration: u64,
+
+    #[serde(default)]
+    pub method: String,
+
+    //Url after substitution of variables
+    #[serde(default)]
+    pub url: String,
+
+    #[serde(default)]
+    pub error_message: String,
+
+    //Address of the node which sent the request in distributed tests, empty otherwise
+    #[serde(default)]
+    pub node_id: String
+}
+
+fn default_attempt() -> u32 {
+    1
 }
 
 impl Stats {
-    pub fn new(name: &str, status: u16, latency: u32, thread_count: u16) -> Stats {
+    pub fn new(name: &str, status: u16, latency: u64, thread_count: u16) -> Stats {
         Stats {
             timestamp: Local::now().to_string(),
             name: String::from(name),
             status,
             latency,
-            thread_count
+            thread_count,
+            attempt: 1,
+            error_kind: None,
+            ttfb: 0,
+            download: 0,
+            dns: 0,
+            connect: 0,
+            tls: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            expected_interval: 0,
+            scenario: String::new(),
+            thread_id: 0,
+            iteration: 0,
+            method: String::new(),
+            url: String::new(),
+            error_message: String::new(),
+            node_id: String::new()
         }
     }
+
+    pub fn is_token_request(&self) -> bool {
+        self.name.starts_with(TOKEN_REQUEST_PREFIX)
+    }
 }
 
+//Commas are escaped in url and replaced in error message, as fields are written unquoted
 impl fmt::Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "{}, {}, {}, {}, {:width$}", self.timestamp, self.thread_count, self.status, self.latency, self.name, width = 35)
+        let error_kind = self.error_kind.as_ref().map(|e| e.to_string()).unwrap_or_default();
+        let url = self.url.replace(',', "%2C");
+        let error_message = self.error_message.replace(',', ";").replace('"', "'").replace(['\r', '\n'], " ");
+        writeln!(f, "{}, {}, {}, {}, {:width$}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}", self.timestamp, self.thread_count, self.status, self.latency, self.name, 
+            self.attempt, error_kind, self.ttfb, self.download, self.dns, self.connect, self.tls, self.bytes_sent, self.bytes_received, self.expected_interval,
+            self.scenario, self.thread_id, self.iteration, self.method, url, error_message, self.node_id, width = 35)
     }
 }
 
-pub struct StatsConsumer {
-    is_distributed: bool,
-    is_db_configured: bool,
-    receiver: Receiver<Vec<Stats>>,
-    db_writer: Arc<Mutex<Option<Box<dyn DBWriter + Send>>>>,
-    csv_writer: Arc<Mutex<Option<CSVWriter>>>,
-    websocket: Arc<Mutex<Option<WebSocketSink<MaybeTlsStream<TcpStream>>>>>
+//Stats are sent by the bombarding threads to the consumer over a bounded channel, so they wait when the consumer falls behind
+pub type StatsSender = mpsc::Sender<Vec<Stats>>;
+
+//Queue of batches of a sink and the task writing them, pushing to it never waits for the sink.
+//Once queue size batches are queued, sinks which drop when full drop the batch and the others buffer it in memory
+struct SinkQueue {
+    name: String,
+    drops_when_full: bool,
+    queue_size: usize,
+    sender: mpsc::UnboundedSender<Arc<Vec<Stats>>>,
+    handle: JoinHandle<()>,
+    queued: Arc<AtomicUsize>,
+    batches: Arc<AtomicUsize>,
+    dropped: u64,
+    overflowed: bool,
+    metrics: Option<Arc<Metrics>>
 }
 
-impl StatsConsumer {
-    pub async fn new(config: &Config, websocket: Arc<Mutex<Option<WebSocketSink<MaybeTlsStream<TcpStream>>>>>) 
-    -> Result<(Self,  Sender<Vec<Stats>>) ,String> {
-        info!("Initiate StatsConsumer");
-        let (sender, receiver) = channel::unbounded::<Vec<Stats>>();
-
-        //Initialize DB writer is DB is configured
-        let db_writer = get_db_writer(&config.database);
-        let is_db_configured = db_writer.is_some();
-        
-        //Initialize CSV Writer if execution is not distributed
-        let report_file;
-        if config.report_file.is_empty() {
-            report_file = "report.csv";
-        } else {
-            report_file = &config.report_file;
+impl SinkQueue {
+    fn start(name: String, mut sink: Box<dyn ResultSink + Send>, queue_size: usize, metrics: Option<Arc<Metrics>>) -> SinkQueue {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Arc<Vec<Stats>>>();
+        let drops_when_full = sink.drops_when_full();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let batches = Arc::new(AtomicUsize::new(0));
+
+        let task_name = name.clone();
+        let task_queued = queued.clone();
+        let task_batches = batches.clone();
+        let task_metrics = metrics.clone();
+        let handle = task::spawn(async move {
+            while let Some(stats) = receiver.recv().await {
+                sink.write(&stats).await;
+
+                task_batches.fetch_sub(1, Ordering::SeqCst);
+                let queued = task_queued.fetch_sub(stats.len(), Ordering::SeqCst) - stats.len();
+                if let Some(metrics) = task_metrics.as_ref() {
+                    metrics.set_queued(&task_name, queued);
+                }
+            }
+
+            sink.finish().await;
+        });
+
+        SinkQueue { name, drops_when_full, queue_size: queue_size.max(1), sender, handle, queued, batches, dropped: 0, overflowed: false, metrics }
+    }
+
+    fn push(&mut self, stats: Arc<Vec<Stats>>) {
+        let count = stats.len();
+        if self.batches.load(Ordering::SeqCst) >= self.queue_size {
+            if self.drops_when_full {
+                if self.dropped == 0 {
+                    warn!("Queue of {} is full, dropping stats till it catches up", self.name);
+                }
+
+                self.dropped += count as u64;
+                if let Some(metrics) = self.metrics.as_ref() {
+                    metrics.add_dropped(&self.name, count as u64);
+                }
+
+                return
+            }
+
+            if !self.overflowed {
+                warn!("Queue of {} is full, buffering stats in memory till it catches up", self.name);
+                self.overflowed = true;
+            }
+        }
+
+        self.add_queued(count);
+        self.batches.fetch_add(1, Ordering::SeqCst);
+        if self.sender.send(stats).is_err() {
+            error!("Writer of {} has stopped, stats are not written", self.name);
         }
+    }
+
+    fn add_queued(&self, count: usize) {
+        let queued = self.queued.fetch_add(count, Ordering::SeqCst) + count;
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.set_queued(&self.name, queued);
+        }
+    }
 
-        let mut csv_writer = None;
-        let is_distributed = config.distributed;
-        if !is_distributed {
-            csv_writer = match csv::CSVWriter::new(report_file).await {
-                Ok(w) => Some(w),
-                Err(err) => return Err(err.to_string())
-            };
+    //Waits till the queued stats are written and the sink is finished
+    async fn close(self) -> u64 {
+        drop(self.sender);
+        if let Err(err) = self.handle.await {
+            error!("Writer of {} failed: {}", self.name, err);
         }
 
+        if self.dropped > 0 {
+            warn!("{} stats were not written to {} as its queue was full", self.dropped, self.name);
+        }
+
+        self.dropped
+    }
+}
+
+pub struct StatsConsumer {
+    receiver: mpsc::Receiver<Vec<Stats>>,
+    sinks: Vec<(String, Box<dyn ResultSink + Send>)>,
+    settings: StatsSettings,
+    dashboard: Option<Dashboard>,
+    metrics: Option<Arc<Metrics>>
+}
+
+impl StatsConsumer {
+    pub async fn new(config: &Config, run: &Run, websocket: sink::Websocket, dashboard: Option<Dashboard>,
+        metrics: Option<Arc<Metrics>>) -> Result<(Self, StatsSender), String> {
+        info!("Initiate StatsConsumer");
+        let (sender, receiver) = mpsc::channel::<Vec<Stats>>(config.stats.queue_size.max(1));
+        let sinks = sink::get_sinks(config, run, websocket).await?;
+
         Ok((StatsConsumer {
-            is_distributed,
-            is_db_configured,
             receiver,
-            db_writer: Arc::new(Mutex::new(db_writer)),
-            csv_writer: Arc::new(Mutex::new(csv_writer)),
-            websocket
+            sinks,
+            settings: config.stats.clone(),
+            dashboard,
+            metrics
         }, sender))
     }
 }
 
 impl StatsConsumer {
-    pub async fn consume(self) -> JoinHandle<()> {
-        let is_db_configured = self.is_db_configured;
-        let is_distributed = self.is_distributed;
+    //Latencies of every request are recorded in HDR histograms which are returned once all the stats are consumed.
+    //Stats are written to the sinks when batch size is reached, or at every flush interval
+    pub async fn consume(self) -> JoinHandle<LatencyHistograms> {
+        let StatsConsumer { mut receiver, sinks, settings, dashboard, metrics } = self;
 
-        let db_writer = self.db_writer.clone();
-        let csv_writer = self.csv_writer.clone();
-        let websocket = self.websocket.clone();
-
-        let stats_batch: Vec<Stats> = Vec::with_capacity(100);
-        let stats_batch_arc = Arc::new(Mutex::new(stats_batch));
+        //Dashboard is drawn every second till all the stats are consumed
+        let dashboard_handle = dashboard.as_ref().map(|dashboard| dashboard.start());
 
         task::spawn(async move {
+            let mut histograms = LatencyHistograms::new();
+            let mut queues = sinks.into_iter()
+                .map(|(name, sink)| SinkQueue::start(name, sink, settings.queue_size, metrics.clone()))
+                .collect::<Vec<SinkQueue>>();
+
+            let batch_size = settings.batch_size.max(1);
+            let mut batch = Vec::with_capacity(batch_size);
+            let mut flush_interval = time::interval(Duration::from_millis(settings.flush_interval.max(1)));
+            flush_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
             loop {
-                match self.receiver.recv() {
-                    Ok(stats) => {
-                        //Add stats to batch till batch size is full
-                        let mut stats_batch = stats_batch_arc.lock().await;
-                        stats_batch.extend(stats);
-
-                        //Currently batch size is hardcoded to 100
-                        if stats_batch.len() < 50 {
-                            continue;
+                tokio::select! {
+                    received = receiver.recv() => {
+                        //Channel is closed once all the bombarding threads are done
+                        let stats = match received {
+                            Some(stats) => stats,
+                            None => break
+                        };
+
+                        stats.iter().for_each(|s| histograms.record(s));
+                        if let Some(dashboard) = dashboard.as_ref() {
+                            dashboard.record(&stats);
                         }
 
-                        //need to drop this guard else below two async tasks cannot acquire a lock
-                        drop(stats_batch); 
-
-                        let csv_writer_clone = csv_writer.clone();
-                        let websocket_clone = websocket.clone();
-                        let stats_clone = stats_batch_arc.clone();
-
-                        //Spawn a task for writing to CSV or socket
-                        let handle1 = task::spawn(async move {
-                            if is_distributed {
-                                write_to_socket(websocket_clone, stats_clone).await;
-                            } else {
-                                write_to_csv(csv_writer_clone, stats_clone).await;
-                            }
-                        });
-
-                        let db_writer_clone = db_writer.clone();
-                        let stats_clone = stats_batch_arc.clone();
-
-                        //Spawn a task to write to DB
-                        let handle2 = task::spawn(async move {
-                            if is_db_configured {
-                                write_to_db(db_writer_clone, stats_clone).await;
-                            }
-                        });
-
-                        futures::future::join_all([handle1, handle2]).await;
-
-                        //clear the batch
-                        let mut stats_batch = stats_batch_arc.lock().await;
-                        stats_batch.clear();
-                    }
-                    Err(err) => {       
-                        if err.to_string().contains("receiving on an empty and disconnected channel") {
-                            if is_distributed {
-                                write_to_socket(websocket.clone(), stats_batch_arc.clone()).await;
-                                send_done_to_websocket(websocket).await;
-                            } else {
-                                write_to_csv(csv_writer, stats_batch_arc.clone()).await;
-                            }
-
-                            if is_db_configured {
-                                write_to_db(db_writer, stats_batch_arc).await;
-                            }
-                            
-                            break;
+                        if let Some(metrics) = metrics.as_ref() {
+                            metrics.record(&stats);
                         }
 
-                        //Log any other error apart from disconnected channel
-                        error!("Error receiving msg on StatsConsumer channel: {}", err); 
-                    }
+                        batch.extend(stats);
+                        if batch.len() >= batch_size {
+                            write_batch(&mut queues, &mut batch);
+                        }
+                    },
+                    _ = flush_interval.tick() => write_batch(&mut queues, &mut batch)
                 }
             }
+
+            write_batch(&mut queues, &mut batch);
+            for queue in queues {
+                queue.close().await;
+            }
+
+            if let (Some(dashboard), Some(handle)) = (dashboard.as_ref(), dashboard_handle) {
+                dashboard.finish(handle).await;
+            }
+
+            histograms
         })
     }
 }
 
-async fn send_done_to_websocket(websocket: 
-    Arc<Mutex<Option<WebSocketSink<MaybeTlsStream<TcpStream>>>>>) {
-    let mut websocket = websocket.lock().await;
-    websocket.as_mut().unwrap().write(String::from("done")).await;
-}
+//Batch is shared by the queues of all the sinks
+fn write_batch(queues: &mut [SinkQueue], batch: &mut Vec<Stats>) {
+    if batch.is_empty() {
+        return
+    }
 
-async fn write_to_csv(csv_writer: Arc<Mutex<Option<csv::CSVWriter>>>, stats: Arc<Mutex<Vec<Stats>>>) {
-    let stats = stats.lock().await;
-    let mut csv_writer = csv_writer.lock().await;
-    csv_writer.as_mut().unwrap().write(&stats[..]).await;  
+    let stats = Arc::new(std::mem::replace(batch, Vec::with_capacity(batch.capacity())));
+    for queue in queues.iter_mut() {
+        queue.push(stats.clone());
+    }
 }
 
-async fn write_to_socket(websocket: 
-    Arc<Mutex<Option<WebSocketSink<MaybeTlsStream<TcpStream>>>>>, stats: Arc<Mutex<Vec<Stats>>>) {
-    let stats = stats.lock().await;
-    let mut websocket = websocket.lock().await;
-    websocket.as_mut().unwrap().write_stats(&stats[..]).await;
-}
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use tokio::sync::Semaphore;
 
-async fn write_to_db(db_writer: Arc<Mutex<Option<Box<dyn DBWriter + Send>>>>, stats: Arc<Mutex<Vec<Stats>>>) {
-    let stats = stats.lock().await;
-    let mut db_writer = db_writer.lock().await;
-    db_writer.as_mut().unwrap().write_stats(&stats[..]).await;
-}
+    use crate::report::stats::*;
 
-fn get_db_writer(db_config: &Database) -> Option<Box<dyn storage::DBWriter + Send>> {
-    let db_writer;
-
-    match db_config.db_type.to_lowercase().as_str() {
-        "influxdb" => {
-            info!("Initiating influx DB");
-            match influxdb::InfluxDBWriter::new(db_config) {
-                Some(writer) =>  {
-                    db_writer = Box::new(writer) as Box<dyn DBWriter + Send>;
-                },
-                None => {
-                    error!("InfluxDB initialization failed");
-                    return None
-                }
+    //Records the size of every batch written, after waiting for a permit when gated
+    #[derive(Default)]
+    struct RecordingWriter {
+        batches: Arc<parking_lot::Mutex<Vec<usize>>>,
+        flushed: Arc<parking_lot::Mutex<bool>>,
+        gate: Option<Arc<Semaphore>>,
+        drops_when_full: bool
+    }
+
+    #[async_trait]
+    impl ResultSink for RecordingWriter {
+        async fn write(&mut self, stats: &[Stats]) {
+            if let Some(gate) = self.gate.as_ref() {
+                gate.acquire().await.unwrap().forget();
             }
-        },
-        "" => {
-            warn!("No database type defined. No DBWriter will be initialized");
-            return None;
-        },
-        _ => {
-            error!("Invalid DB type received: {}", &db_config.db_type);
-            return None
+
+            self.batches.lock().push(stats.len());
         }
+
+        async fn finish(&mut self) {
+            *self.flushed.lock() = true;
+        }
+
+        fn drops_when_full(&self) -> bool {
+            self.drops_when_full
+        }
+    }
+
+    fn get_consumer(settings: &str, writer: RecordingWriter) -> (StatsConsumer, StatsSender) {
+        let settings: StatsSettings = serde_yaml::from_str(settings).unwrap();
+        let (sender, receiver) = mpsc::channel(settings.queue_size);
+        (StatsConsumer { receiver, sinks: vec![(String::from("recording"), Box::new(writer))], settings, dashboard: None, metrics: None }, sender)
     }
 
-    Some(db_writer)
-}
\ No newline at end of file
+    fn get_stats(count: usize) -> Vec<Stats> {
+        (0..count).map(|_| Stats::new("getOrders", 200, 1000, 1)).collect()
+    }
+
+    #[test]
+    fn test_default_settings() {
+        let config: Config = serde_yaml::from_str("threadCount: 1").unwrap();
+        assert_eq!((config.stats.batch_size, config.stats.flush_interval, config.stats.queue_size), (100, 1000, 100));
+    }
+
+    #[tokio::test]
+    async fn test_consume_in_batches() {
+        let writer = RecordingWriter::default();
+        let (batches, flushed) = (writer.batches.clone(), writer.flushed.clone());
+        let (consumer, sender) = get_consumer("batchSize: 3\nflushInterval: 60000", writer);
+
+        for _ in 0..4 {
+            sender.send(get_stats(2)).await.unwrap();
+        }
+
+        sender.send(get_stats(1)).await.unwrap();
+        drop(sender);
+        consumer.consume().await.await.unwrap();
+
+        //remaining stats are written once the channel is closed
+        assert_eq!(*batches.lock(), vec![4, 4, 1]);
+        assert!(*flushed.lock());
+    }
+
+    #[tokio::test]
+    async fn test_consume_on_flush_interval() {
+        let writer = RecordingWriter::default();
+        let batches = writer.batches.clone();
+        let (consumer, sender) = get_consumer("batchSize: 100\nflushInterval: 20", writer);
+        let handle = consumer.consume().await;
+
+        sender.send(get_stats(2)).await.unwrap();
+        time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(*batches.lock(), vec![2]);
+
+        drop(sender);
+        handle.await.unwrap();
+        assert_eq!(*batches.lock(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_queue_drops_when_full() {
+        let gate = Arc::new(Semaphore::new(0));
+        let writer = RecordingWriter { gate: Some(gate.clone()), drops_when_full: true, ..Default::default() };
+        let batches = writer.batches.clone();
+        let metrics = Arc::new(Metrics::new());
+
+        //Writer task does not get to run till the test awaits, so only the first batch fits in the queue
+        let mut queue = SinkQueue::start(String::from("influxdb"), Box::new(writer), 1, Some(metrics.clone()));
+        for count in [2, 3, 4] {
+            queue.push(Arc::new(get_stats(count)));
+        }
+
+        assert_eq!(queue.queued.load(Ordering::SeqCst), 2);
+        assert!(metrics.render().contains("bombardier_stats_dropped_total{sink=\"influxdb\"} 7\n"));
+
+        gate.add_permits(1);
+        assert_eq!(queue.close().await, 7);
+        assert_eq!(*batches.lock(), vec![2]);
+        assert!(metrics.render().contains("bombardier_stats_queued{sink=\"influxdb\"} 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_queue_buffers_when_full() {
+        let gate = Arc::new(Semaphore::new(0));
+        let writer = RecordingWriter { gate: Some(gate.clone()), ..Default::default() };
+        let batches = writer.batches.clone();
+
+        //Batches beyond the queue size are buffered without waiting for the writer
+        let mut queue = SinkQueue::start(String::from("report"), Box::new(writer), 1, None);
+        for count in [2, 3, 4] {
+            queue.push(Arc::new(get_stats(count)));
+        }
+
+        assert_eq!(queue.queued.load(Ordering::SeqCst), 9);
+
+        gate.add_permits(3);
+        assert_eq!(queue.close().await, 0);
+        assert_eq!(*batches.lock(), vec![2, 3, 4]);
+    }
+}
@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset};
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::report::{self, histogram, sink::ResultSink, stats::Stats};
+
+//Hits are executions of the request, retries are counted only in attempts
+#[derive(Default)]
+struct RequestCounts {
+    hits: u64,
+    attempts: u64,
+    successes: u64,
+
+    //Sum and max of latencies of every attempt in µs
+    latency: u64,
+    max_latency: u64
+}
+
+//Prints hits, errors, throughput and latencies of every request once all the stats are written.
+//Throughput is over the time between the first and the last stats
+#[derive(Default)]
+pub struct SummaryWriter {
+    requests: BTreeMap<String, RequestCounts>,
+    first: Option<DateTime<FixedOffset>>,
+    last: Option<DateTime<FixedOffset>>
+}
+
+impl SummaryWriter {
+    pub fn new() -> Self {
+        SummaryWriter::default()
+    }
+
+    fn record(&mut self, stats: &[Stats]) {
+        for s in stats {
+            let counts = self.requests.entry(s.name.clone()).or_default();
+            counts.hits += (s.attempt == 1) as u64;
+            counts.attempts += 1;
+            counts.successes += !report::is_error(s) as u64;
+            counts.latency += s.latency;
+            counts.max_latency = counts.max_latency.max(s.latency);
+
+            if let Some(timestamp) = report::parse_timestamp(&s.timestamp) {
+                self.first = Some(self.first.map_or(timestamp, |first| first.min(timestamp)));
+                self.last = Some(self.last.map_or(timestamp, |last| last.max(timestamp)));
+            }
+        }
+    }
+
+    fn get_rows(&self) -> Vec<Vec<String>> {
+        let secs = match (self.first, self.last) {
+            (Some(first), Some(last)) => (last - first).num_milliseconds() as f64 / 1000.0,
+            _ => 0.0
+        };
+
+        let mut rows = vec![["Request", "Total Hits", "Errors", "Error Rate", "Hits/s", "Avg", "Max"].map(String::from).to_vec()];
+        let mut total = RequestCounts::default();
+        for (name, counts) in &self.requests {
+            rows.push(get_row(name, counts, secs));
+            total.hits += counts.hits;
+            total.attempts += counts.attempts;
+            total.successes += counts.successes;
+            total.latency += counts.latency;
+            total.max_latency = total.max_latency.max(counts.max_latency);
+        }
+
+        rows.push(get_row("Total", &total, secs));
+        rows
+    }
+}
+
+//Requests are retried only on errors, so an execution fails when none of its attempts succeeds
+fn get_row(name: &str, counts: &RequestCounts, secs: f64) -> Vec<String> {
+    let errors = counts.hits.saturating_sub(counts.successes);
+    let error_rate = errors as f64 * 100.0 / counts.hits.max(1) as f64;
+    let throughput = match secs > 0.0 {
+        true => format!("{:.2}", counts.hits as f64 / secs),
+        false => String::from("-")
+    };
+
+    vec![name.to_owned(), counts.hits.to_string(), errors.to_string(), format!("{:.2}%", error_rate), throughput,
+        histogram::format_latency(counts.latency / counts.attempts.max(1)), histogram::format_latency(counts.max_latency)]
+}
+
+//Columns are padded to the widest cell, request names are left aligned and numbers right aligned
+fn render(rows: &[Vec<String>]) -> String {
+    let widths = (0..rows[0].len())
+        .map(|i| rows.iter().map(|row| row[i].chars().count()).max().unwrap_or_default())
+        .collect::<Vec<usize>>();
+
+    let mut content = String::new();
+    for row in rows {
+        let cells = row.iter().zip(&widths).enumerate()
+            .map(|(i, (cell, width))| match i {
+                0 => format!("{:<width$}", cell, width = width),
+                _ => format!("{:>width$}", cell, width = width)
+            })
+            .collect::<Vec<String>>();
+
+        writeln!(content, "{}", cells.join("  ").trim_end()).unwrap();
+    }
+
+    content
+}
+
+#[async_trait]
+impl ResultSink for SummaryWriter {
+    async fn write(&mut self, stats: &[Stats]) {
+        self.record(stats);
+    }
+
+    async fn finish(&mut self) {
+        if !self.requests.is_empty() {
+            print!("{}", render(&self.get_rows()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::summary::*;
+
+    #[test]
+    fn test_get_rows() {
+        let mut stats = [Stats::new("getOrders", 200, 4000, 1), Stats::new("getOrders", 503, 8000, 1), Stats::new("health", 200, 1000, 1)];
+        stats[0].timestamp = String::from("2021-01-01 10:00:00.000000 +0530");
+        stats[1].timestamp = String::from("2021-01-01 10:00:01.000000 +0530");
+        stats[2].timestamp = String::from("2021-01-01 10:00:02.000000 +0530");
+
+        let mut writer = SummaryWriter::new();
+        writer.record(&stats[..2]);
+        writer.record(&stats[2..]);
+
+        let rows = writer.get_rows();
+        assert_eq!(rows[1], vec!["getOrders", "2", "1", "50.00%", "1.00", "6.000", "8.000"]);
+        assert_eq!(rows[2], vec!["health", "1", "0", "0.00%", "0.50", "1.000", "1.000"]);
+        assert_eq!(rows[3], vec!["Total", "3", "1", "33.33%", "1.50", "4.333", "8.000"]);
+    }
+
+    #[test]
+    fn test_get_rows_with_retries() {
+        let mut stats = [Stats::new("getOrders", 503, 4000, 1), Stats::new("getOrders", 200, 2000, 1), Stats::new("getOrders", 503, 3000, 1)];
+        stats[1].attempt = 2;
+
+        let mut writer = SummaryWriter::new();
+        writer.record(&stats);
+
+        //Retried attempt is not a hit or an error
+        assert_eq!(writer.get_rows()[1], vec!["getOrders", "2", "1", "50.00%", "-", "3.000", "4.000"]);
+    }
+
+    #[test]
+    fn test_render() {
+        let rows = [vec!["Request", "Hits"], vec!["getOrders", "20"], vec!["health", "3"]]
+            .map(|row| row.into_iter().map(String::from).collect::<Vec<String>>());
+
+        assert_eq!(render(&rows), "Request    Hits\ngetOrders    20\nhealth        3\n");
+    }
+}
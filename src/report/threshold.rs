@@ -0,0 +1,65 @@
+use crate::{
+    model::{Threshold, Thresholds},
+    report::{RequestSummary, histogram}
+};
+
+//Breaches of thresholds by a request as messages, thresholds of the request take precedence over the default ones
+pub fn get_breaches(summary: &RequestSummary, thresholds: &Thresholds) -> Vec<String> {
+    let request = thresholds.requests.get(&summary.name);
+    let get = |f: fn(&Threshold) -> Option<f64>| request.and_then(f).or_else(|| f(&thresholds.default));
+
+    let latencies = [
+        ("avg", get(|t| t.avg), summary.latency.mean),
+        ("max", get(|t| t.max), summary.latency.max),
+        ("p90", get(|t| t.p90), summary.latency.pc_90),
+        ("p95", get(|t| t.p95), summary.latency.pc_95),
+        ("p99", get(|t| t.p99), summary.latency.pc_99),
+        ("p99.9", get(|t| t.p99_9), summary.latency.pc_99_9),
+        ("p99.99", get(|t| t.p99_99), summary.latency.pc_99_99)
+    ];
+
+    let mut breaches = latencies.iter()
+        .filter_map(|(metric, threshold, latency)| match threshold {
+            Some(threshold) if histogram::to_ms(*latency) > *threshold => 
+                Some(format!("{} {} ms is above threshold of {} ms", metric, histogram::format_latency(*latency), threshold)),
+            _ => None
+        })
+        .collect::<Vec<String>>();
+
+    if let Some(threshold) = get(|t| t.error_rate) {
+        if summary.error_rate as f64 > threshold {
+            breaches.push(format!("errorRate {:.2} % is above threshold of {} %", summary.error_rate, threshold));
+        }
+    }
+
+    if let Some(threshold) = get(|t| t.min_throughput) {
+        if (summary.throughput as f64) < threshold {
+            breaches.push(format!("throughput {:.2} hits/s is below threshold of {} hits/s", summary.throughput, threshold));
+        }
+    }
+
+    breaches
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{model::Thresholds, report::{self, aggregator::Aggregate, stats::Stats, threshold::*}};
+
+    #[test]
+    fn test_get_breaches() {
+        let stats = vec![Stats::new("getOrders", 200, 1500, 1), Stats::new("getOrders", 500, 2500, 1), Stats::new("getHealth", 200, 500, 1)];
+        let summary = report::get_report_summary(&Aggregate::from_stats(&stats, 0));
+
+        let thresholds: Thresholds = serde_yaml::from_str("p99: 2\nerrorRate: 10\nrequests:\n  getHealth:\n    p99: 0.1\n").unwrap();
+
+        //requests are sorted by name
+        let breaches = get_breaches(&summary.requests[1], &thresholds);
+        assert_eq!(breaches, vec!["p99 2.501 ms is above threshold of 2 ms", "errorRate 50.00 % is above threshold of 10 %"]);
+
+        //request threshold overrides the default one
+        let breaches = get_breaches(&summary.requests[0], &thresholds);
+        assert_eq!(breaches, vec!["p99 0.500 ms is above threshold of 0.1 ms"]);
+
+        assert!(get_breaches(&summary.requests[0], &Thresholds::default()).is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+use chrono::{DateTime, FixedOffset};
+use hdrhistogram::Histogram;
+use log::error;
+use serde::Serialize;
+
+use crate::report::{self, histogram::{self, LatencySummary}, stats::Stats};
+
+//Precision of 2 significant digits is enough for charts and keeps the histogram of every bucket small
+const SIGNIFICANT_DIGITS: u8 = 2;
+
+//Stats of all the requests sent in an interval, start and end are in secs since start of execution.
+//Threads is the maximum number of threads running in the interval
+#[derive(Serialize)]
+pub struct TimeBucket {
+    pub start: u64,
+    pub end: u64,
+    pub hits: u64,
+    pub throughput: f32,
+    pub errors: u64,
+
+    #[serde(rename = "errorRate")]
+    pub error_rate: f32,
+
+    pub threads: u16,
+    pub latency: Option<LatencySummary>
+}
+
+//Latency histogram, errors and maximum threads of every interval since start of execution.
+//Start time has to be known upfront as stats are recorded in any order
+#[derive(Clone)]
+pub struct Timeline {
+    start_time: DateTime<FixedOffset>,
+    interval: u64,
+    buckets: Vec<(Histogram<u32>, u64, u16)>
+}
+
+impl Timeline {
+    pub fn new(start_time: DateTime<FixedOffset>, interval: u64) -> Self {
+        Timeline {
+            start_time,
+            interval: interval.max(1),
+            buckets: Vec::new()
+        }
+    }
+
+    pub fn record(&mut self, stats: &Stats) {
+        let timestamp = match report::parse_timestamp(&stats.timestamp) {
+            Some(timestamp) => timestamp,
+            None => {
+                error!("Invalid timestamp {} for {}", stats.timestamp, stats.name);
+                return
+            }
+        };
+
+        let index = (timestamp - self.start_time).num_seconds().max(0) as usize / self.interval as usize;
+        if !self.grow(index + 1) {
+            return
+        }
+
+        let (h, errors, threads) = &mut self.buckets[index];
+        h.saturating_record(stats.latency);
+        *errors += report::is_error(stats) as u64;
+        *threads = (*threads).max(stats.thread_count);
+    }
+
+    pub fn get_interval(&self) -> u64 {
+        self.interval
+    }
+
+    //Timelines of report file chunks aggregated in parallel are merged into one
+    pub fn merge(&mut self, other: Timeline) {
+        if !self.grow(other.buckets.len()) {
+            return
+        }
+
+        for ((h, errors, threads), (other_h, other_errors, other_threads)) in self.buckets.iter_mut().zip(other.buckets) {
+            if let Err(err) = h.add(&other_h) {
+                error!("Unable to merge histogram of time bucket : {}", err);
+            }
+
+            *errors += other_errors;
+            *threads = (*threads).max(other_threads);
+        }
+    }
+
+    fn grow(&mut self, len: usize) -> bool {
+        while self.buckets.len() < len {
+            match Histogram::new_with_bounds(1, histogram::HIGHEST_LATENCY, SIGNIFICANT_DIGITS) {
+                Ok(h) => self.buckets.push((h, 0, 0)),
+                Err(err) => {
+                    error!("Unable to create histogram for time bucket : {}", err);
+                    return false
+                }
+            }
+        }
+
+        true
+    }
+
+    //Intervals without any stats are returned as empty buckets
+    pub fn get_buckets(&self) -> Vec<TimeBucket> {
+        let interval = self.interval;
+        self.buckets.iter().enumerate()
+            .map(|(i, (h, errors, threads))| TimeBucket {
+                start: i as u64 * interval,
+                end: (i as u64 + 1) * interval,
+                hits: h.len(),
+                throughput: h.len() as f32 / interval as f32,
+                errors: *errors,
+                error_rate: match h.len() {
+                    0 => 0.0,
+                    hits => *errors as f32 * 100.0 / hits as f32
+                },
+                threads: *threads,
+                latency: match h.is_empty() {
+                    true => None,
+                    false => Some(histogram::get_summary(h))
+                }
+            })
+            .collect()
+    }
+}
+
+//Interval is either secs or a duration like 30s, 5m or 1h
+pub fn parse_interval(interval: &str) -> Result<u64, String> {
+    let interval = interval.trim();
+    let (value, multiplier) = match interval.chars().last() {
+        Some('s') => (&interval[..interval.len() - 1], 1),
+        Some('m') => (&interval[..interval.len() - 1], 60),
+        Some('h') => (&interval[..interval.len() - 1], 3600),
+        _ => (interval, 1)
+    };
+
+    match value.parse::<u64>() {
+        Ok(value) if value > 0 => Ok(value * multiplier),
+        _ => Err(format!("Invalid interval {}, should be a duration like 30s, 5m or 1h", interval))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Local};
+
+    use crate::report::timeline::*;
+
+    fn get_stats(now: DateTime<Local>, offset_secs: i64, status: u16, latency: u64, thread_count: u16) -> Stats {
+        let mut stats = Stats::new("name1", status, latency, thread_count);
+        stats.timestamp = (now + Duration::seconds(offset_secs)).to_string();
+        stats
+    }
+
+    //Execution starts when the first request is sent
+    fn get_timeline(now: DateTime<Local>, interval: u64) -> Timeline {
+        Timeline::new((now - Duration::milliseconds(1)).into(), interval)
+    }
+
+    #[test]
+    fn test_get_time_buckets() {
+        let now = Local::now();
+        let stats = [get_stats(now, 0, 200, 1000, 1), get_stats(now, 1, 500, 3000, 2), get_stats(now, 5, 200, 2000, 4)];
+
+        let mut timeline = get_timeline(now, 2);
+        stats.iter().rev().for_each(|s| timeline.record(s));
+
+        let buckets = timeline.get_buckets();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets.iter().map(|b| b.start).collect::<Vec<u64>>(), vec![0, 2, 4]);
+
+        assert_eq!((buckets[0].hits, buckets[0].errors, buckets[0].threads), (2, 1, 2));
+        assert_eq!((buckets[0].throughput, buckets[0].error_rate), (1.0, 50.0));
+        assert!(buckets[0].latency.as_ref().unwrap().max.abs_diff(3000) <= 30);
+
+        //no requests were sent in 2nd bucket
+        assert_eq!((buckets[1].hits, buckets[1].errors, buckets[1].threads), (0, 0, 0));
+        assert_eq!((buckets[1].throughput, buckets[1].error_rate), (0.0, 0.0));
+        assert!(buckets[1].latency.is_none());
+
+        assert_eq!((buckets[2].hits, buckets[2].threads), (1, 4));
+    }
+
+    #[test]
+    fn test_get_time_buckets_for_empty_stats() {
+        assert!(get_timeline(Local::now(), 1).get_buckets().is_empty());
+    }
+
+    #[test]
+    fn test_merge() {
+        let now = Local::now();
+        let mut timeline = get_timeline(now, 1);
+        timeline.record(&get_stats(now, 0, 200, 1000, 1));
+
+        let mut other = get_timeline(now, 1);
+        other.record(&get_stats(now, 0, 500, 1000, 3));
+        other.record(&get_stats(now, 2, 200, 1000, 2));
+        timeline.merge(other);
+
+        let buckets = timeline.get_buckets();
+        assert_eq!(buckets.iter().map(|b| (b.hits, b.errors, b.threads)).collect::<Vec<(u64, u64, u16)>>(), vec![(2, 1, 3), (0, 0, 0), (1, 0, 2)]);
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("10"), Ok(10));
+        assert_eq!(parse_interval("10s"), Ok(10));
+        assert_eq!(parse_interval("5m"), Ok(300));
+        assert_eq!(parse_interval("1h"), Ok(3600));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("ms").is_err());
+        assert!(parse_interval("").is_err());
+    }
+}
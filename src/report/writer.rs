@@ -0,0 +1,17 @@
+use crate::report::{binary::BinaryWriter, csv::CSVWriter, sink::ResultSink};
+
+//Writes stats to the report file in the format requested with --report-format or the type of output
+pub async fn get_report_writer(report_file: &str, format: &str) -> Result<Box<dyn ResultSink + Send>, std::io::Error> {
+    match format {
+        "binary" => Ok(Box::new(BinaryWriter::new(report_file, false).await?)),
+        "binary-zstd" => Ok(Box::new(BinaryWriter::new(report_file, true).await?)),
+        _ => Ok(Box::new(CSVWriter::new(report_file).await?))
+    }
+}
+
+pub fn get_default_report_file(format: &str) -> &'static str {
+    match format {
+        "binary" | "binary-zstd" => "report.bin",
+        _ => "report.csv"
+    }
+}
@@ -0,0 +1,310 @@
+use log::{debug, info};
+use reqwest::{Request as Reqwest, header::HeaderMap};
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+use rustc_hash::FxHashMap as HashMap;
+
+use std::error::Error;
+
+use crate::{
+    auth::{hmac_sha256, sha256_hex},
+    model::Request
+};
+
+const ENV_VAR: &str = "env";
+const REQUEST_VAR: &str = "request";
+const RESPONSE_VAR: &str = "response";
+const RETRY_VAR: &str = "retry";
+
+//Upper limit on operations a script can execute, guards the execution against infinite loops
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Default)]
+struct RequestScripts {
+    pre_request: Option<AST>,
+    post_response: Option<AST>
+}
+
+//Response details made available to post response scripts
+pub struct ScriptResponse<'a> {
+    pub status: u16,
+    pub headers: &'a HeaderMap,
+    pub body: &'a str,
+    pub latency: u64
+}
+
+pub struct ScriptEngine {
+    engine: Engine,
+    scripts: HashMap<uuid::Uuid, RequestScripts>
+}
+
+impl ScriptEngine {
+    //Compiles scripts of all the requests upfront so that syntax errors are reported before execution starts
+    pub fn new(requests: &[Request]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let engine = get_engine();
+        let mut scripts = HashMap::default();
+
+        for request in requests {
+            if !request.has_scripts() {
+                continue;
+            }
+
+            info!("Compiling scripts for request {}", request.name);
+            let request_scripts = RequestScripts {
+                pre_request: compile(&engine, &request.name, "preRequest", &request.scripts.pre_request)?,
+                post_response: compile(&engine, &request.name, "postResponse", &request.scripts.post_response)?
+            };
+
+            scripts.insert(request.id, request_scripts);
+        }
+
+        Ok(ScriptEngine { engine, scripts })
+    }
+
+    pub fn has_pre_request_script(&self, id: &uuid::Uuid) -> bool {
+        self.scripts.get(id).is_some_and(|s| s.pre_request.is_some())
+    }
+
+    pub fn has_post_response_script(&self, id: &uuid::Uuid) -> bool {
+        self.scripts.get(id).is_some_and(|s| s.post_response.is_some())
+    }
+
+    //Runs the pre request script, script can update the request and the env map
+    pub fn pre_request(&self, id: &uuid::Uuid, request: &mut Request, env_map: &mut HashMap<String, String>)
+    -> Result<(), Box<dyn Error + Send + Sync>> {
+        let ast = match self.scripts.get(id).and_then(|s| s.pre_request.as_ref()) {
+            Some(ast) => ast,
+            None => return Ok(())
+        };
+
+        let mut scope = Scope::new();
+        scope.push(ENV_VAR, env_to_map(env_map));
+        scope.push(REQUEST_VAR, request_to_map(request));
+
+        debug!("Running preRequest script for request {}", request.name);
+        self.engine.run_ast_with_scope(&mut scope, ast)
+            .map_err(|err| format!("preRequest script failed for request {}: {}", request.name, err))?;
+
+        update_env_map(&scope, env_map);
+        if let Some(map) = scope.get_value::<Map>(REQUEST_VAR) {
+            update_request(request, &map);
+        }
+
+        Ok(())
+    }
+
+    //Runs the post response script, returns true if script has asked for the request to be retried
+    pub fn post_response(&self, id: &uuid::Uuid, request: &Map, response: ScriptResponse, env_map: &mut HashMap<String, String>)
+    -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let ast = match self.scripts.get(id).and_then(|s| s.post_response.as_ref()) {
+            Some(ast) => ast,
+            None => return Ok(false)
+        };
+
+        let mut scope = Scope::new();
+        scope.push(ENV_VAR, env_to_map(env_map));
+        scope.push(REQUEST_VAR, request.clone());
+        scope.push(RESPONSE_VAR, response_to_map(&response));
+        scope.push(RETRY_VAR, false);
+
+        debug!("Running postResponse script for request {}", get_string(request, "name"));
+        self.engine.run_ast_with_scope(&mut scope, ast)
+            .map_err(|err| format!("postResponse script failed for request {}: {}", get_string(request, "name"), err))?;
+
+        update_env_map(&scope, env_map);
+        Ok(scope.get_value::<bool>(RETRY_VAR).unwrap_or(false))
+    }
+}
+
+fn get_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    engine.register_fn("hmac_sha256", |key: &str, message: &str| hex::encode(hmac_sha256(key.as_bytes(), message.as_bytes())));
+    engine.register_fn("hmac_sha256_base64", |key: &str, message: &str| base64::encode(hmac_sha256(key.as_bytes(), message.as_bytes())));
+    engine.register_fn("sha256", |message: &str| sha256_hex(message.as_bytes()));
+    engine.register_fn("base64_encode", |message: &str| base64::encode(message));
+    engine.register_fn("base64_decode", |message: &str| {
+        base64::decode(message).ok()
+            .and_then(|v| String::from_utf8(v).ok())
+            .unwrap_or_default()
+    });
+    engine.register_fn("timestamp", || chrono::Utc::now().timestamp());
+    engine.register_fn("timestamp_millis", || chrono::Utc::now().timestamp_millis());
+    engine.register_fn("uuid", || uuid::Uuid::new_v4().to_string());
+
+    engine
+}
+
+fn compile(engine: &Engine, name: &str, hook: &str, script: &str) -> Result<Option<AST>, Box<dyn Error + Send + Sync>> {
+    if script.trim().is_empty() {
+        return Ok(None)
+    }
+
+    match engine.compile(script) {
+        Ok(ast) => Ok(Some(ast)),
+        Err(err) => Err(format!("Compiling {} script for request {} failed: {}", hook, name, err).into())
+    }
+}
+
+fn env_to_map(env_map: &HashMap<String, String>) -> Map {
+    env_map.iter()
+        .map(|(k, v)| (k.into(), Dynamic::from(v.to_owned())))
+        .collect()
+}
+
+fn update_env_map(scope: &Scope, env_map: &mut HashMap<String, String>) {
+    if let Some(map) = scope.get_value::<Map>(ENV_VAR) {
+        *env_map = map.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+    }
+}
+
+fn headers_to_map<'a>(headers: impl Iterator<Item = (&'a str, String)>) -> Map {
+    headers
+        .map(|(k, v)| (k.into(), Dynamic::from(v)))
+        .collect()
+}
+
+fn request_to_map(request: &Request) -> Map {
+    let mut map = Map::new();
+    map.insert("name".into(), Dynamic::from(request.name.to_owned()));
+    map.insert("method".into(), Dynamic::from(request.method.to_owned()));
+    map.insert("url".into(), Dynamic::from(request.url.to_owned()));
+    map.insert("headers".into(), Dynamic::from(headers_to_map(request.headers.iter().map(|(k, v)| (k.as_str(), v.to_owned())))));
+    map.insert("body".into(), Dynamic::from(request.body.raw.to_owned()));
+    map
+}
+
+//Builds the map of the final request sent, as made available to post response scripts
+pub fn reqwest_to_map(name: &str, reqwest: &Reqwest) -> Map {
+    let body = reqwest.body()
+        .and_then(|b| b.as_bytes())
+        .map(|b| String::from_utf8_lossy(b).to_string())
+        .unwrap_or_default();
+
+    let mut map = Map::new();
+    map.insert("name".into(), Dynamic::from(name.to_owned()));
+    map.insert("method".into(), Dynamic::from(reqwest.method().to_string()));
+    map.insert("url".into(), Dynamic::from(reqwest.url().to_string()));
+    map.insert("headers".into(), Dynamic::from(headers_to_map(reqwest.headers().iter()
+        .map(|(k, v)| (k.as_str(), v.to_str().unwrap_or_default().to_owned())))));
+    map.insert("body".into(), Dynamic::from(body));
+    map
+}
+
+fn response_to_map(response: &ScriptResponse) -> Map {
+    let mut map = Map::new();
+    map.insert("status".into(), Dynamic::from(response.status as i64));
+    map.insert("headers".into(), Dynamic::from(headers_to_map(response.headers.iter()
+        .map(|(k, v)| (k.as_str(), v.to_str().unwrap_or_default().to_owned())))));
+    map.insert("body".into(), Dynamic::from(response.body.to_owned()));
+    map.insert("latency".into(), Dynamic::from(response.latency as i64));
+    map
+}
+
+fn update_request(request: &mut Request, map: &Map) {
+    request.method = get_string(map, "method");
+    request.url = get_string(map, "url");
+    request.body.raw = get_string(map, "body");
+
+    if let Some(headers) = map.get("headers").and_then(|h| h.read_lock::<Map>()) {
+        request.headers = headers.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+    }
+}
+
+fn get_string(map: &Map, key: &str) -> String {
+    map.get(key).map(|v| v.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::*;
+
+    fn get_request(scripts: &str) -> Request {
+        let request_yaml = format!(r"
+        name: echoPost
+        method: POST
+        url: 'https://google.com/'
+        headers:
+          accept: application/json
+        body:
+          raw: 'some body'
+        scripts:
+{}", scripts);
+
+        let mut request = serde_yaml::from_str::<Request>(&request_yaml).unwrap();
+        request.id = uuid::Uuid::new_v4();
+        request
+    }
+
+    #[test]
+    fn test_compile_error_is_reported() {
+        let request = get_request("          preRequest: 'let x = ;'");
+        let result = ScriptEngine::new(&[request]);
+        assert!(result.err().unwrap().to_string().contains("Compiling preRequest script for request echoPost failed"));
+    }
+
+    #[test]
+    fn test_pre_request_script() {
+        let request = get_request(r#"          preRequest: |
+            request.headers["x-signature"] = hmac_sha256(env.secret, request.body);
+            request.url = request.url + "path";
+            env.signed = true;"#);
+
+        let engine = ScriptEngine::new(std::slice::from_ref(&request)).unwrap();
+        assert!(engine.has_pre_request_script(&request.id));
+        assert!(!engine.has_post_response_script(&request.id));
+
+        let mut env_map = HashMap::default();
+        env_map.insert(String::from("secret"), String::from("key"));
+
+        let mut processed_request = request.clone();
+        engine.pre_request(&request.id, &mut processed_request, &mut env_map).unwrap();
+
+        assert_eq!(processed_request.url, "https://google.com/path");
+        assert_eq!(processed_request.headers.get("accept").unwrap(), "application/json");
+        assert_eq!(processed_request.headers.get("x-signature").unwrap(), &hex::encode(hmac_sha256(b"key", b"some body")));
+        assert_eq!(env_map.get("signed").unwrap(), "true");
+        assert_eq!(env_map.get("secret").unwrap(), "key");
+    }
+
+    #[test]
+    fn test_post_response_script() {
+        let request = get_request(r#"          postResponse: |
+            env.status = response.status;
+            env.server = response.headers["server"];
+            retry = response.status >= 500;"#);
+
+        let engine = ScriptEngine::new(std::slice::from_ref(&request)).unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("server", "gunicorn".parse().unwrap());
+
+        let mut env_map = HashMap::default();
+        let request_map = request_to_map(&request);
+
+        let response = ScriptResponse { status: 503, headers: &headers, body: "", latency: 10 };
+        let retry = engine.post_response(&request.id, &request_map, response, &mut env_map).unwrap();
+
+        assert!(retry);
+        assert_eq!(env_map.get("status").unwrap(), "503");
+        assert_eq!(env_map.get("server").unwrap(), "gunicorn");
+
+        let response = ScriptResponse { status: 200, headers: &headers, body: "", latency: 10 };
+        assert!(!engine.post_response(&request.id, &request_map, response, &mut env_map).unwrap());
+    }
+
+    #[test]
+    fn test_script_runtime_error() {
+        let request = get_request("          postResponse: 'throw \"failed\";'");
+        let engine = ScriptEngine::new(std::slice::from_ref(&request)).unwrap();
+
+        let headers = HeaderMap::new();
+        let response = ScriptResponse { status: 200, headers: &headers, body: "", latency: 10 };
+        let result = engine.post_response(&request.id, &request_to_map(&request), response, &mut HashMap::default());
+        assert!(result.err().unwrap().to_string().contains("postResponse script failed for request echoPost"));
+    }
+}
@@ -25,6 +25,7 @@ use std::{
 use crate::{
     bombardier::Bombardier, 
     parse::parser, 
+    report::run::{self, Run},
     server::servers
 };
 
@@ -48,7 +49,10 @@ struct ErrorResponse {
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 struct SuccessResponse {
-    message: String
+    message: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<Run>
 }
 
 impl ErrorResponse {
@@ -115,9 +119,10 @@ pub async fn start(ctx: Arc<servers::Context>, form_data: FormData, ) -> Result<
     let mut scenarios_content = String::new();
     let mut environments_content = String::new();
     let mut data_file_path = String::new();
+    let mut tags = vec![];
 
     //Check if all files received
-    for mut p in parts {
+    for p in parts {
         if p.filename().is_some() {
             info!("Reading {} param", p.name());
             match p.name() {
@@ -146,23 +151,21 @@ pub async fn start(ctx: Arc<servers::Context>, form_data: FormData, ) -> Result<
                 }
             }
         } else if p.name() == "data" {
-            data_file_path = match p.data().await {
-                Some(path) => match path {
-                    Ok(mut b) => {
-                        let mut v = Vec::new();
-                        while b.has_remaining() {
-                            v.push(b.get_u8());
-                        };
-
-                        from_utf8(&v).unwrap().to_owned()
-                    },
-                    Err(err) => {
-                        error!("Error occured while reading the data: {}", err.to_string());
-                        errors.push(ErrorResponse::new(400, "Error occured while reading the data"));
-                        String::with_capacity(0)
-                    }
-                },
-                None => String::with_capacity(0)
+            data_file_path = match get_value(p).await {
+                Ok(path) => path,
+                Err(err) => {
+                    error!("Error occured while reading the data: {}", err.to_string());
+                    errors.push(ErrorResponse::new(400, "Error occured while reading the data"));
+                    String::with_capacity(0)
+                }
+            };
+        } else if p.name() == "tag" {
+            match get_value(p).await {
+                Ok(tag) => tags.push(tag),
+                Err(err) => {
+                    error!("Error occured while reading the tag: {}", err.to_string());
+                    errors.push(ErrorResponse::new(400, "Error occured while reading the tag"));
+                }
             };
         }
     }
@@ -173,6 +176,14 @@ pub async fn start(ctx: Arc<servers::Context>, form_data: FormData, ) -> Result<
         errors.push(ErrorResponse::new(400, "config and scenarios file parameters are mandatory"));
     }
 
+    let tags = match run::parse_tags(&tags) {
+        Ok(tags) => tags,
+        Err(err) => {
+            errors.push(ErrorResponse::new(400, &err));
+            Default::default()
+        }
+    };
+
     //Check error vector
     if !errors.is_empty() {
         return Ok(reply::with_status(reply::json(&errors), StatusCode::BAD_REQUEST));
@@ -188,11 +199,12 @@ pub async fn start(ctx: Arc<servers::Context>, form_data: FormData, ) -> Result<
     //set distributed to true
     config.distributed = true;
     config.data_file = data_file_path;
+    config.node_count = ctx.get_total_nodes().await;
 
     //Prepare bombardier message
     info!("Preparing bombardier message");
     let bombardier = 
-    match Bombardier::new(config, environments_content, scenarios_content) {
+    match Bombardier::new(config, environments_content, scenarios_content, tags) {
         Ok(bombardier) => bombardier,
         Err(err) => return ErrorResponse::new(400, &err.to_string()).get_warp_reply()
     };
@@ -214,7 +226,8 @@ pub async fn start(ctx: Arc<servers::Context>, form_data: FormData, ) -> Result<
 
     Ok(reply::with_status(
         reply::json(&SuccessResponse{
-            message: String::from("execution started successfully")
+            message: String::from("execution started successfully"),
+            run: Some(bombardier.run)
         }), StatusCode::CREATED))
 }
 
@@ -227,6 +240,19 @@ pub async fn nodes(ctx: Arc<servers::Context>) -> Result<impl Reply, Rejection>
         ctx.get_currently_bombarding_nodes().await).get_warp_reply()
 }
 
+//Value of a form field which is not a file
+async fn get_value(mut p: Part) -> Result<String, Box<dyn std::error::Error>> {
+    let mut v = Vec::new();
+    if let Some(data) = p.data().await {
+        let mut b = data?;
+        while b.has_remaining() {
+            v.push(b.get_u8());
+        }
+    }
+
+    Ok(from_utf8(&v)?.to_owned())
+}
+
 async fn get_stream(p: Part) -> String {
     let v= p.stream()
         .try_fold(Vec::new(), |mut v, mut data| {
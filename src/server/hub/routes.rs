@@ -2,15 +2,16 @@ use warp::Filter;
 
 use std::sync::Arc;
 
-use crate::server::{
-    hub::api,
-    servers
+use crate::{
+    report::metrics,
+    server::{hub::api, servers}
 };
 
 pub fn bombardier_filters(ctx: Arc<servers::Context>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     start_execution(ctx.clone())
         .or(stop_execution(ctx.clone()))
-        .or(get_available_nodes(ctx))
+        .or(get_available_nodes(ctx.clone()))
+        .or(metrics::route(ctx.metrics.clone()))
 }
 
 pub fn start_execution(ctx: Arc<servers::Context>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -23,6 +23,7 @@ use crate::{
         WebSocketSink, 
         WebSocketStream
     }, 
+    report::stats::Stats,
     server::servers
 };
 
@@ -62,6 +63,7 @@ pub async fn serve(
         let tansmitter_arc_clone = ctx.transmitters_map.clone();
         let bombardiers_arc_clone = ctx.bombardiers_map.clone();
         let bombardiers_arc_clone_2 = ctx.bombardiers_map.clone();
+        let metrics = ctx.metrics.clone();
 
         let mut handles = vec![];
 
@@ -96,8 +98,10 @@ pub async fn serve(
                                 info!("Received done from {}, updating bombarding to false", &conn_uuid);
                                 add_to_map(bombardiers_arc_clone.clone(), &conn_uuid, false).await;
                             },
-                            _ => {
-                                error!("Unexpected message received from node {} : {}", &conn_uuid, &text);
+                            //Every other message from the node is a batch of stats
+                            _ => match serde_json::from_str::<Vec<Stats>>(&text) {
+                                Ok(stats) => metrics.record(&stats),
+                                Err(_) => error!("Unexpected message received from node {} : {}", &conn_uuid, &text)
                             }
                         }
                     }
@@ -7,18 +7,20 @@ use tokio::sync::Mutex;
 use crate::{
     bombardier::Bombardier,
     protocol::socket::{self, WebSocketSink, WebSocketStream}, 
-    report::stats::StatsConsumer
+    report::{metrics::Metrics, stats::StatsConsumer}
 };
 
-pub async fn start(hub_address: String) -> Result<(), Box<dyn std::error::Error + 'static>> {
+//Metrics, if served, are of all the executions since the node started
+pub async fn start(hub_address: String, metrics: Option<Arc<Metrics>>) -> Result<(), Box<dyn std::error::Error + 'static>> {
     let hub_address = format!("ws://{}/ws", &hub_address);
     let websocket = socket::connect(hub_address.clone()).await?;
+    let node_id = socket::get_local_address(&websocket).unwrap_or_default();
     let (sink, stream) = websocket.split();
 
     let mut websocket_stream = WebSocketStream::new(stream);
     let websocket_sink = Arc::new(Mutex::new(Some(WebSocketSink::new(sink))));
 
-    info!("Connected to {} successfully", &hub_address);
+    info!("Connected to {} successfully as node {}", &hub_address, &node_id);
 
     loop {
         let msg = match websocket_stream.read().await {
@@ -38,12 +40,13 @@ pub async fn start(hub_address: String) -> Result<(), Box<dyn std::error::Error
         if msg.is_text() { //Handle only text messages
             let text_msg = msg.to_text()?;
 
-            let b = match is_bombard_message(text_msg)  {
+            let mut b = match is_bombard_message(text_msg)  {
                 Some(b) => b,
                 None =>  return Err("Bombarding message not received".into())
             };
+            b.config.node_id = node_id.clone();
 
-            let (stats_consumer, stats_sender) = match StatsConsumer::new(&b.config,websocket_sink.clone()).await {
+            let (stats_consumer, stats_sender) = match StatsConsumer::new(&b.config, &b.run, websocket_sink.clone(), None, metrics.clone()).await {
                 Ok(stats_consumer) => stats_consumer,
                 Err(err) => {
                     error!("Error while initializing stats consumer {}", err);
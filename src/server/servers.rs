@@ -12,12 +12,16 @@ use std::sync::Arc
 
 use crate::{
     bombardier::Bombardier,
+    report::metrics::Metrics,
     server::hub::{rest, websocket}
 };
 
 pub struct Context {
     pub bombardiers_map: Arc<Mutex<HashMap<String, bool>>>,
-    pub transmitters_map: Arc<Mutex<HashMap<String, Sender<Bombardier>>>>
+    pub transmitters_map: Arc<Mutex<HashMap<String, Sender<Bombardier>>>>,
+
+    //Stats sent by all the nodes, served on /metrics of rest server
+    pub metrics: Arc<Metrics>
 }
 
 impl Context {
@@ -39,7 +43,8 @@ impl Context {
 pub async fn serve(port: u16, ws_port: u16) -> Result<(), Box<dyn std::error::Error + 'static>> {
     let context_arc = Arc::new(Context {
         bombardiers_map :  Arc::new(Mutex::new(HashMap::default())),
-        transmitters_map: Arc::new(Mutex::new(HashMap::default()))
+        transmitters_map: Arc::new(Mutex::new(HashMap::default())),
+        metrics: Arc::new(Metrics::new())
     });
 
     let context_clone = context_arc.clone();
@@ -1,51 +1,65 @@
 use async_trait::async_trait;
-use chrono::DateTime;
-use log::error;
-use rustc_hash::FxHashMap as HashMap;
+use chrono::{DateTime, FixedOffset};
+use flate2::{write::GzEncoder, Compression};
+use log::{error, warn};
+use reqwest::header::{AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE};
+use url::Url;
 
-use crate::{converter, model, protocol::http::HttpClient, report::stats, storage};
+use std::io::Write;
 
+use crate::{
+    model::{self, Backoff, BackoffType, ErrorKind, Retry},
+    protocol::http::HttpClient,
+    report::{run::Run, sink::ResultSink, stats}
+};
+
+const MEASUREMENT: &str = "stats";
+
+//Tags of points which can not be overridden by tags of the run
+const RESERVED_TAGS: [&str; 5] = ["node", "request", "run", "scenario", "statusClass"];
+
+//Writes every batch of stats as points of line protocol, to v1 api with basic auth or to v2 api with token auth
 pub struct InfluxDBWriter {
     pub client: HttpClient,
-    request: model::Request,
+    url: Url,
+    authorization: Option<String>,
+    retry: Retry,
+    timestamps: TimestampParser,
+
+    //Run id and tags of the run, added to every point
+    run_tags: String
 }
 
 impl InfluxDBWriter {
-    pub fn new(db: &model::Database) -> Option<InfluxDBWriter> {
+    pub fn new(db: &model::Database, run: &Run) -> Option<InfluxDBWriter> {
         //check if url is set
         if db.url.is_empty() {
             error!("InfluxDB url or host is not set, not initializing the InfluxDBWriter");
             return None;
         }
 
-        let url= format!("{}/write?db={}&precision=ms", db.url, db.name);
-
-        //Setting headers
-        let mut headers = HashMap::default();
-        headers.insert(String::from("content-type"), String::from("application/octet-stream"));
+        let url = match get_write_url(db) {
+            Ok(url) => url,
+            Err(err) => {
+                error!("Invalid InfluxDB url {} : {}", db.url, err);
+                return None;
+            }
+        };
 
-         //Setting URL
-         if !db.user.is_empty() {
-            headers.insert(String::from("authorization"), format!("Basic {}", base64::encode(format!("{}:{}",db.user,db.password))));
-        }
+        let authorization = match (db.token.is_empty(), db.user.is_empty()) {
+            (false, _) => Some(format!("Token {}", db.token)),
+            (true, false) => Some(format!("Basic {}", base64::encode(format!("{}:{}", db.user, db.password)))),
+            (true, true) => None
+        };
 
         match HttpClient::get_default_async_client() {
-            Ok(http_client) =>  Some(InfluxDBWriter {
+            Ok(http_client) => Some(InfluxDBWriter {
                 client: http_client,
-                request: model::Request {
-                    id: uuid::Uuid::new_v4(),
-                    name: String::from("postToInfluxDB"),
-                    url,
-                    method: String::from("POST"),
-                    body: model::Body {
-                        formdata: vec![],
-                        urlencoded: HashMap::default(),
-                        raw: String::from(""),
-                    },
-                    headers,
-                    extractors: vec![],
-                    requires_preprocessing: false
-                }
+                url,
+                authorization,
+                retry: get_retry(),
+                timestamps: TimestampParser::default(),
+                run_tags: get_run_tags(run)
             }),
             Err(err) => {
                 error!("Error while initiating new InfluxDB Client {}", err.to_string());
@@ -54,25 +68,291 @@ impl InfluxDBWriter {
         }
     }
 
-    fn set_body_from_stats(&mut self, stats: &[stats::Stats]) {
-        self.request.body.raw = stats.iter()
-            .map(|s| {format!("stats,request={} latency={},status={} {}",
-                s.name, s.latency, s.status, DateTime::parse_from_rfc3339(&s.timestamp).unwrap().timestamp_millis())})
-            .collect::<Vec<String>>().join("\n")
+    fn get_body(&mut self, stats: &[stats::Stats]) -> String {
+        let mut body = String::new();
+        for s in stats {
+            match self.timestamps.get_millis(&s.timestamp) {
+                Some(timestamp) => {
+                    body.push_str(&get_line(s, &self.run_tags, timestamp));
+                    body.push('\n');
+                },
+                None => error!("Invalid timestamp {} for {}, not writing it to influxdb", s.timestamp, s.name)
+            }
+        }
+
+        body
     }
 }
 
 #[async_trait]
-impl storage::DBWriter for InfluxDBWriter {
-    async fn write_stats(&mut self, stats: &[stats::Stats]) {
-        //Setting Body
-        self.set_body_from_stats(stats);
+impl ResultSink for InfluxDBWriter {
+    async fn write(&mut self, stats: &[stats::Stats]) {
+        let body = self.get_body(stats);
+        if body.is_empty() {
+            return
+        }
+
+        let body = match gzip(body.as_bytes()) {
+            Ok(body) => body,
+            Err(err) => {
+                error!("Error compressing stats for influxdb: {}", err);
+                return
+            }
+        };
+
+        let mut request = self.client.get_client().post(self.url.clone())
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .header(CONTENT_ENCODING, "gzip")
+            .body(body);
+
+        if let Some(authorization) = self.authorization.as_ref() {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        let request = match request.build() {
+            Ok(request) => request,
+            Err(err) => {
+                error!("Error building request for influxdb: {}", err);
+                return
+            }
+        };
 
-        let reqwest = converter::convert_request(&self.client, &self.request).await.unwrap();
+        let execution = self.client.execute_with_retry(request, Some(&self.retry), |execution| {
+            warn!("Writing to influxdb failed with status {} / error {:?}, retrying", execution.status(), execution.error_kind())
+        }).await;
 
-        match self.client.execute(reqwest).await {
-            Ok(_res) => (),
+        match execution.result {
+            Ok(response) if response.status().is_success() => (),
+            Ok(response) => error!("Error writing to influxdb: status {} {}", response.status(), response.text().await.unwrap_or_default()),
             Err(err) => error!("Error writing to influxdb: {}", err)
         };
     }
-}
\ No newline at end of file
+
+    fn drops_when_full(&self) -> bool {
+        true
+    }
+}
+
+//v2 api is used when org is set, with bucket defaulting to database name
+fn get_write_url(db: &model::Database) -> Result<Url, url::ParseError> {
+    let base_url = db.url.trim_end_matches('/');
+    let mut url = match db.org.is_empty() {
+        true => Url::parse(&format!("{}/write", base_url))?,
+        false => Url::parse(&format!("{}/api/v2/write", base_url))?
+    };
+
+    let bucket = if db.bucket.is_empty() { &db.name } else { &db.bucket };
+    let mut query = url.query_pairs_mut();
+    match db.org.is_empty() {
+        true => query.append_pair("db", &db.name),
+        false => query.append_pair("org", &db.org).append_pair("bucket", bucket)
+    };
+
+    query.append_pair("precision", "ms");
+    drop(query);
+
+    Ok(url)
+}
+
+//Writes are retried on throttling, server errors, timeouts and connect errors
+fn get_retry() -> Retry {
+    Retry {
+        max_attempts: 3,
+        status_codes: vec![429, 500, 502, 503, 504],
+        errors: vec![ErrorKind::Timeout, ErrorKind::Connect],
+        backoff: Backoff { backoff_type: BackoffType::Exponential, delay: 200, max_delay: 2000, jitter: true }
+    }
+}
+
+fn gzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+fn get_run_tags(run: &Run) -> String {
+    let mut tags = String::new();
+    if !run.id.is_empty() {
+        tags.push_str(&format!(",run={}", escape_tag(&run.id)));
+    }
+
+    for (key, value) in &run.tags {
+        match RESERVED_TAGS.contains(&key.as_str()) || value.is_empty() {
+            true => warn!("Tag {} is not added to influxdb points as it is reserved or empty", key),
+            false => tags.push_str(&format!(",{}={}", escape_tag(key), escape_tag(value)))
+        }
+    }
+
+    tags
+}
+
+//Tags with empty values are not allowed in line protocol, hence scenario and node are written only when set.
+//Fields are floats except errorKind to keep the types of fields written by earlier versions
+fn get_line(s: &stats::Stats, run_tags: &str, timestamp: i64) -> String {
+    let mut line = String::from(MEASUREMENT);
+    if !s.node_id.is_empty() {
+        line.push_str(&format!(",node={}", escape_tag(&s.node_id)));
+    }
+
+    line.push_str(&format!(",request={}", escape_tag(&s.name)));
+    if !s.scenario.is_empty() {
+        line.push_str(&format!(",scenario={}", escape_tag(&s.scenario)));
+    }
+
+    line.push_str(&format!(",statusClass={}{} latency={},status={},attempt={},ttfb={},download={},dns={},connect={},tls={},bytesSent={},bytesReceived={}",
+        get_status_class(s.status), run_tags, s.latency, s.status, s.attempt, s.ttfb, s.download, s.dns, s.connect, s.tls, s.bytes_sent, s.bytes_received));
+
+    if let Some(error_kind) = s.error_kind.as_ref() {
+        line.push_str(&format!(",errorKind=\"{}\"", escape_field(&error_kind.to_string())));
+    }
+
+    line.push_str(&format!(" {}", timestamp));
+    line
+}
+
+//Status is 0 when no response is received
+fn get_status_class(status: u16) -> String {
+    match status {
+        0 => String::from("none"),
+        status => format!("{}xx", status / 100)
+    }
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace([' ', '\n'], "\\ ")
+}
+
+fn escape_field(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+//Timestamps of stats are formatted with microseconds and utc offset, like 2021-01-01 10:00:00.123456 +05:30.
+//Stats of a batch are mostly of the same second, so the date and time till seconds is parsed once for all of them
+#[derive(Default)]
+struct TimestampParser {
+    second: String,
+    millis: i64
+}
+
+impl TimestampParser {
+    fn get_millis(&mut self, timestamp: &str) -> Option<i64> {
+        let (datetime, offset) = timestamp.rsplit_once(' ')?;
+        let (second, fraction) = datetime.split_once('.').unwrap_or((datetime, ""));
+
+        let is_parsed = self.second.len() == second.len() + offset.len() + 1 && self.second.starts_with(second) && self.second.ends_with(offset);
+        if !is_parsed {
+            let parsed: DateTime<FixedOffset> = DateTime::parse_from_str(&format!("{} {}", second, offset), "%Y-%m-%d %H:%M:%S %z").ok()?;
+            self.second = format!("{} {}", second, offset);
+            self.millis = parsed.timestamp_millis();
+        }
+
+        let fraction = &fraction[..fraction.len().min(3)];
+        let millis = match fraction.is_empty() {
+            true => 0,
+            false => format!("{:0<3}", fraction).parse::<i64>().ok()?
+        };
+
+        Some(self.millis + millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use flate2::read::GzDecoder;
+    use warp::Filter;
+
+    use std::{io::Read, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+
+    use crate::{report::{sink::ResultSink, stats::Stats}, storage::influxdb::*};
+
+    #[test]
+    fn test_get_line() {
+        let mut stats = Stats::new("get orders, v2", 200, 1500, 1);
+        stats.ttfb = 1000;
+        assert_eq!(get_line(&stats, "", 1609475400123), "stats,request=get\\ orders\\,\\ v2,statusClass=2xx latency=1500,status=200,attempt=1,ttfb=1000,download=0,dns=0,connect=0,tls=0,bytesSent=0,bytesReceived=0 1609475400123");
+
+        let mut stats = Stats::new("a=b", 0, 30_000_000, 1);
+        stats.scenario = String::from("checkout flow");
+        stats.node_id = String::from("10.0.0.2:5000");
+        stats.attempt = 2;
+        stats.error_kind = Some(ErrorKind::Timeout);
+        assert_eq!(get_line(&stats, ",run=1", 1), "stats,node=10.0.0.2:5000,request=a\\=b,scenario=checkout\\ flow,statusClass=none,run=1 latency=30000000,status=0,attempt=2,ttfb=0,download=0,dns=0,connect=0,tls=0,bytesSent=0,bytesReceived=0,errorKind=\"timeout\" 1");
+    }
+
+    #[test]
+    fn test_get_run_tags() {
+        let mut run = Run { id: String::from("6f1c3a52"), ..Default::default() };
+        run.tags.insert(String::from("build"), String::from("1.2 rc"));
+        run.tags.insert(String::from("request"), String::from("orders"));
+        run.tags.insert(String::from("empty"), String::new());
+        assert_eq!(get_run_tags(&run), ",run=6f1c3a52,build=1.2\\ rc");
+        assert_eq!(get_run_tags(&Run::default()), "");
+    }
+
+    #[test]
+    fn test_get_millis() {
+        let mut parser = TimestampParser::default();
+        assert_eq!(parser.get_millis("2021-01-01 10:00:00.123456 +0530"), Some(1609475400123));
+        assert_eq!(parser.get_millis("2021-01-01 10:00:00.9 +0530"), Some(1609475400900));
+        assert_eq!(parser.get_millis("2021-01-01 10:00:00 +0530"), Some(1609475400000));
+        assert_eq!(parser.get_millis("2021-01-01 04:30:01.000001 +0000"), Some(1609475401000));
+        assert_eq!(parser.get_millis("2021-01-01 10:00:01.5 +05:30"), Some(1609475401500));
+        assert_eq!(parser.get_millis("2021-01-01T10:00:01.5+05:30"), None);
+        assert_eq!(parser.get_millis("invalid"), None);
+
+        //timestamp of new stats
+        let now = Local::now();
+        assert_eq!(parser.get_millis(&now.to_string()), Some(now.timestamp_millis()));
+    }
+
+    #[test]
+    fn test_get_write_url() {
+        let db = model::Database { url: String::from("http://influxdb:8086/"), name: String::from("load tests"), ..Default::default() };
+        assert_eq!(get_write_url(&db).unwrap().as_str(), "http://influxdb:8086/write?db=load+tests&precision=ms");
+
+        let db = model::Database { org: String::from("perf&qa"), ..db };
+        assert_eq!(get_write_url(&db).unwrap().as_str(), "http://influxdb:8086/api/v2/write?org=perf%26qa&bucket=load+tests&precision=ms");
+
+        let db = model::Database { bucket: String::from("bombardier"), ..db };
+        assert_eq!(get_write_url(&db).unwrap().as_str(), "http://influxdb:8086/api/v2/write?org=perf%26qa&bucket=bombardier&precision=ms");
+    }
+
+    #[tokio::test]
+    async fn test_write_stats() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = attempts.clone();
+        let body = Arc::new(parking_lot::Mutex::new(String::new()));
+        let body_clone = body.clone();
+
+        //Fails the first write to check that it is retried
+        let route = warp::post()
+            .and(warp::header::<String>("authorization"))
+            .and(warp::header::<String>("content-encoding"))
+            .and(warp::body::bytes())
+            .map(move |authorization: String, encoding: String, bytes: warp::hyper::body::Bytes| {
+                assert_eq!((authorization.as_str(), encoding.as_str()), ("Token secret", "gzip"));
+                let mut decoded = String::new();
+                GzDecoder::new(&bytes[..]).read_to_string(&mut decoded).unwrap();
+                *body_clone.lock() = decoded;
+
+                match attempts_clone.fetch_add(1, Ordering::SeqCst) {
+                    0 => warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                    _ => warp::http::StatusCode::NO_CONTENT
+                }
+            });
+
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let db = model::Database { url: format!("http://{}", address), org: String::from("qa"), bucket: String::from("perf"), token: String::from("secret"), ..Default::default() };
+        let run = Run { id: String::from("6f1c3a52"), ..Default::default() };
+        let mut writer = InfluxDBWriter::new(&db, &run).unwrap();
+        let mut stats = Stats::new("get", 200, 1000, 1);
+        stats.timestamp = String::from("2021-01-01 10:00:00.123456 +0530");
+        writer.write(&[stats]).await;
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(*body.lock(), format!("{}\n", "stats,request=get,statusClass=2xx,run=6f1c3a52 latency=1000,status=200,attempt=1,ttfb=0,download=0,dns=0,connect=0,tls=0,bytesSent=0,bytesReceived=0 1609475400123"));
+    }
+}
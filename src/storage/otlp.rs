@@ -0,0 +1,274 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use log::{error, info};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde_json::{json, Value};
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    model,
+    protocol::http::HttpClient,
+    report::{metrics::{self, ErrorSeries, Metrics, RequestSeries}, run::Run, sink::ResultSink, stats}
+};
+
+//Metrics are pushed at most once in this interval, and once more when all the stats are written
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+const METRICS_PATH: &str = "/v1/metrics";
+const DEFAULT_SERVICE_NAME: &str = "bombardier";
+
+//Pushes metrics to an OpenTelemetry collector with OTLP/HTTP in json encoding.
+//Metrics are cumulative from the time the writer is created, so a failed push is made up by the next one
+pub struct OtlpWriter {
+    client: HttpClient,
+    url: String,
+    authorization: Option<String>,
+    service_name: String,
+
+    //Run id and tags of the run, added as attributes of the resource
+    run_attributes: Vec<(String, String)>,
+    start_time: i64,
+    metrics: Metrics,
+    last_push: Option<Instant>
+}
+
+impl OtlpWriter {
+    pub fn new(db: &model::Database, run: &Run) -> Option<OtlpWriter> {
+        if db.url.is_empty() {
+            error!("OTLP endpoint is not set, not initializing the OtlpWriter");
+            return None;
+        }
+
+        //Url can be the collector endpoint or the complete metrics url
+        let url = match db.url.ends_with(METRICS_PATH) {
+            true => db.url.clone(),
+            false => format!("{}{}", db.url.trim_end_matches('/'), METRICS_PATH)
+        };
+
+        let authorization = match db.user.is_empty() {
+            true => None,
+            false => Some(format!("Basic {}", base64::encode(format!("{}:{}", db.user, db.password))))
+        };
+
+        //Database name is used as service name
+        let service_name = match db.name.is_empty() {
+            true => DEFAULT_SERVICE_NAME.to_owned(),
+            false => db.name.clone()
+        };
+
+        match HttpClient::get_default_async_client() {
+            Ok(client) => Some(OtlpWriter {
+                client,
+                url,
+                authorization,
+                service_name,
+                run_attributes: get_run_attributes(run),
+                start_time: get_unix_nanos(),
+                metrics: Metrics::new(),
+                last_push: None
+            }),
+            Err(err) => {
+                error!("Error while initiating new OTLP client {}", err.to_string());
+                None
+            }
+        }
+    }
+
+    async fn push(&mut self) {
+        self.last_push = Some(Instant::now());
+
+        let (requests, errors) = self.metrics.get_series();
+        let body = get_export_request(&self.service_name, &self.run_attributes, &requests, &errors, self.start_time, get_unix_nanos());
+
+        let mut request = self.client.get_client().post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+
+        if let Some(authorization) = self.authorization.as_ref() {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => (),
+            Ok(response) => error!("Error writing to OTLP endpoint: status {} {}", response.status(), response.text().await.unwrap_or_default()),
+            Err(err) => error!("Error writing to OTLP endpoint: {}", err)
+        }
+    }
+}
+
+#[async_trait]
+impl ResultSink for OtlpWriter {
+    async fn write(&mut self, stats: &[stats::Stats]) {
+        self.metrics.record(stats);
+
+        if self.last_push.is_none_or(|last_push| last_push.elapsed() >= PUSH_INTERVAL) {
+            self.push().await;
+        }
+    }
+
+    async fn finish(&mut self) {
+        info!("Pushing final metrics to OTLP endpoint");
+        self.push().await;
+    }
+
+    fn drops_when_full(&self) -> bool {
+        true
+    }
+}
+
+fn get_unix_nanos() -> i64 {
+    Utc::now().timestamp_nanos()
+}
+
+//ExportMetricsServiceRequest with a sum for requests and errors and a histogram for latencies.
+//64 bit integers are strings in OTLP json encoding
+fn get_export_request(service_name: &str, run_attributes: &[(String, String)], requests: &[RequestSeries], errors: &[ErrorSeries], start_time: i64, time: i64) -> Value {
+    let data_point = |name: &str, node: &str, label: (&str, String)| {
+        let mut attributes = vec![get_attribute("request", name)];
+        if !node.is_empty() {
+            attributes.push(get_attribute("node", node));
+        }
+
+        attributes.push(get_attribute(label.0, &label.1));
+        json!({
+            "attributes": attributes,
+            "startTimeUnixNano": start_time.to_string(),
+            "timeUnixNano": time.to_string()
+        })
+    };
+
+    let request_points = requests.iter()
+        .map(|r| {
+            let mut point = data_point(&r.name, &r.node, ("status", r.status.to_string()));
+            point["asInt"] = json!(r.count.to_string());
+            point
+        })
+        .collect::<Vec<Value>>();
+
+    let error_points = errors.iter()
+        .map(|e| {
+            let mut point = data_point(&e.name, &e.node, ("kind", e.kind.clone()));
+            point["asInt"] = json!(e.count.to_string());
+            point
+        })
+        .collect::<Vec<Value>>();
+
+    let latency_points = requests.iter()
+        .map(|r| {
+            let mut point = data_point(&r.name, &r.node, ("status", r.status.to_string()));
+            point["count"] = json!(r.count.to_string());
+            point["sum"] = json!(r.sum);
+            point["bucketCounts"] = json!(r.buckets.iter().map(|count| count.to_string()).collect::<Vec<String>>());
+            point["explicitBounds"] = json!(metrics::BUCKETS);
+            point
+        })
+        .collect::<Vec<Value>>();
+
+    let mut resource_attributes = vec![get_attribute("service.name", service_name)];
+    resource_attributes.extend(run_attributes.iter().map(|(key, value)| get_attribute(key, value)));
+
+    json!({
+        "resourceMetrics": [{
+            "resource": {
+                "attributes": resource_attributes
+            },
+            "scopeMetrics": [{
+                "scope": { "name": "bombardier" },
+                "metrics": [
+                    {
+                        "name": "bombardier.requests",
+                        "description": "Requests sent including retries",
+                        "unit": "1",
+                        "sum": { "aggregationTemporality": 2, "isMonotonic": true, "dataPoints": request_points }
+                    },
+                    {
+                        "name": "bombardier.errors",
+                        "description": "Failed requests by kind of error",
+                        "unit": "1",
+                        "sum": { "aggregationTemporality": 2, "isMonotonic": true, "dataPoints": error_points }
+                    },
+                    {
+                        "name": "bombardier.request.duration",
+                        "description": "Latency of requests",
+                        "unit": "s",
+                        "histogram": { "aggregationTemporality": 2, "dataPoints": latency_points }
+                    }
+                ]
+            }]
+        }]
+    })
+}
+
+fn get_run_attributes(run: &Run) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    if !run.id.is_empty() {
+        attributes.push((String::from("bombardier.run.id"), run.id.clone()));
+    }
+
+    attributes.extend(run.tags.iter().filter(|(key, _)| key.as_str() != "service.name").map(|(key, value)| (key.clone(), value.clone())));
+    attributes
+}
+
+fn get_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{model::ErrorKind, report::{metrics::Metrics, stats::Stats}, storage::otlp::*};
+
+    #[test]
+    fn test_get_export_request() {
+        let mut timeout_stats = Stats::new("getOrders", 0, 60_000_000, 1);
+        timeout_stats.error_kind = Some(ErrorKind::Timeout);
+        timeout_stats.node_id = String::from("10.0.0.2:5000");
+
+        let metrics = Metrics::new();
+        metrics.record(&[Stats::new("getOrders", 200, 4000, 1), Stats::new("getOrders", 200, 20_000, 1), timeout_stats]);
+
+        let (requests, errors) = metrics.get_series();
+        let mut run = Run { id: String::from("6f1c"), ..Default::default() };
+        run.tags.insert(String::from("env"), String::from("qa"));
+
+        let request = get_export_request("ci", &get_run_attributes(&run), &requests, &errors, 1000, 2000);
+        let resource_metrics = &request["resourceMetrics"][0];
+        assert_eq!(resource_metrics["resource"]["attributes"], json!([{ "key": "service.name", "value": { "stringValue": "ci" } },
+            get_attribute("bombardier.run.id", "6f1c"), get_attribute("env", "qa")]));
+
+        let metrics = &resource_metrics["scopeMetrics"][0]["metrics"];
+        let requests = &metrics[0]["sum"]["dataPoints"];
+        assert_eq!(requests.as_array().unwrap().len(), 2);
+        assert_eq!(requests[0]["attributes"], json!([get_attribute("request", "getOrders"), get_attribute("status", "200")]));
+        assert_eq!(requests[0]["asInt"], "2");
+        assert_eq!((&requests[0]["startTimeUnixNano"], &requests[0]["timeUnixNano"]), (&json!("1000"), &json!("2000")));
+        assert_eq!(requests[1]["attributes"], json!([get_attribute("request", "getOrders"), get_attribute("node", "10.0.0.2:5000"), get_attribute("status", "0")]));
+
+        let errors = &metrics[1]["sum"]["dataPoints"];
+        assert_eq!(errors[0]["attributes"][2], get_attribute("kind", "timeout"));
+        assert_eq!(errors[0]["asInt"], "1");
+
+        //timeout is beyond the last bound
+        let latencies = &metrics[2]["histogram"]["dataPoints"];
+        assert_eq!(latencies[0]["count"], "2");
+        assert_eq!(latencies[0]["sum"], 0.024);
+        assert_eq!(latencies[0]["bucketCounts"][0], "1");
+        assert_eq!(latencies[0]["bucketCounts"][2], "1");
+        assert_eq!(latencies[0]["bucketCounts"].as_array().unwrap().len(), latencies[0]["explicitBounds"].as_array().unwrap().len() + 1);
+        assert_eq!(latencies[1]["bucketCounts"].as_array().unwrap().last().unwrap(), "1");
+    }
+
+    #[test]
+    fn test_new() {
+        let db = model::Database { db_type: String::from("otlp"), ..Default::default() };
+        assert!(OtlpWriter::new(&db, &Run::default()).is_none());
+
+        let db = model::Database { url: String::from("http://localhost:4318/"), name: String::from("checkout"), ..db };
+        let writer = OtlpWriter::new(&db, &Run::default()).unwrap();
+        assert_eq!(writer.url, "http://localhost:4318/v1/metrics");
+        assert_eq!(writer.service_name, "checkout");
+        assert!(writer.authorization.is_none());
+
+        let db = model::Database { url: String::from("http://localhost:4318/v1/metrics"), ..db };
+        assert_eq!(OtlpWriter::new(&db, &Run::default()).unwrap().url, "http://localhost:4318/v1/metrics");
+    }
+}
@@ -0,0 +1,258 @@
+use async_trait::async_trait;
+use futures::{pin_mut, SinkExt};
+use log::{error, info};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use std::{error::Error, fmt::Write, io::Cursor};
+
+use crate::{model, report::{run::Run, sink::ResultSink, stats}};
+
+const RUNS_TABLE: &str = "bombardier_runs";
+const STATS_TABLE: &str = "bombardier_stats";
+
+//Columns in the order of rows written by get_rows
+const STATS_COLUMNS: &str = "time, run_id, name, scenario, node, status, error_kind, latency, ttfb, download, dns, connect, tls, bytes_sent, \
+    bytes_received, attempt, thread_count, thread_id, iteration, expected_interval, method, url, error_message";
+
+//Writes every batch of stats to the stats table with COPY, and the config, start and end of the run to the runs table.
+//Stats table is made a hypertable when TimescaleDB extension is installed
+pub struct PostgresWriter {
+    client: Client,
+    run_id: Uuid
+}
+
+impl PostgresWriter {
+    pub async fn new(db: &model::Database, node_count: usize, run: &Run) -> Option<PostgresWriter> {
+        if db.url.is_empty() {
+            error!("Postgres url is not set, not initializing the PostgresWriter");
+            return None;
+        }
+
+        let client = match connect(db).await {
+            Ok(client) => client,
+            Err(err) => {
+                error!("Error connecting to postgres: {}", err);
+                return None;
+            }
+        };
+
+        if let Err(err) = create_tables(&client).await {
+            error!("Error creating tables in postgres: {}", err);
+            return None;
+        }
+
+        //Nodes of a distributed run share the run, so it is inserted by the first node
+        let run_id = Uuid::parse_str(&run.id).unwrap_or_else(|_| Uuid::new_v4());
+        let writer = PostgresWriter { client, run_id };
+        let statement = format!("INSERT INTO {} (run_id, started_at, node_count, scenarios_hash, tags, config) \
+            VALUES ($1, coalesce($2::text::timestamptz, now()), $3, $4, $5, $6) ON CONFLICT (run_id) DO NOTHING", RUNS_TABLE);
+
+        let started_at = Some(run.started_at.as_str()).filter(|started_at| !started_at.is_empty());
+        let node_count = node_count.max(1) as i32;
+        let tags = serde_json::to_value(&run.tags).unwrap_or_default();
+        if let Err(err) = writer.client.execute(statement.as_str(), &[&writer.run_id, &started_at, &node_count, &run.scenarios_hash, &tags, &run.config]).await {
+            error!("Error inserting run in postgres: {}", err);
+            return None;
+        }
+
+        info!("Writing stats of run {} to postgres", writer.run_id);
+        Some(writer)
+    }
+
+    async fn copy(&self, stats: &[stats::Stats]) -> Result<u64, tokio_postgres::Error> {
+        let statement = format!("COPY {} ({}) FROM STDIN WITH (FORMAT csv)", STATS_TABLE, STATS_COLUMNS);
+        let sink = self.client.copy_in::<_, Cursor<Vec<u8>>>(statement.as_str()).await?;
+        pin_mut!(sink);
+
+        sink.send(Cursor::new(get_rows(stats, &self.run_id).into_bytes())).await?;
+        sink.finish().await
+    }
+}
+
+#[async_trait]
+impl ResultSink for PostgresWriter {
+    async fn write(&mut self, stats: &[stats::Stats]) {
+        if stats.is_empty() {
+            return
+        }
+
+        if let Err(err) = self.copy(stats).await {
+            error!("Error writing to postgres: {}", err);
+        }
+    }
+
+    //Nodes of a distributed run finish at different times, so the run ends when the last one finishes
+    async fn finish(&mut self) {
+        let statement = format!("UPDATE {} SET finished_nodes = finished_nodes + 1, \
+            ended_at = CASE WHEN finished_nodes + 1 >= node_count THEN now() ELSE ended_at END WHERE run_id = $1", RUNS_TABLE);
+        if let Err(err) = self.client.execute(statement.as_str(), &[&self.run_id]).await {
+            error!("Error updating end of run in postgres: {}", err);
+        }
+    }
+
+    fn drops_when_full(&self) -> bool {
+        true
+    }
+}
+
+//Url is a connection string like postgres://host:5432/db or host=localhost dbname=db, user, password and name override it when set.
+//TLS is used as per sslmode of the url
+async fn connect(db: &model::Database) -> Result<Client, Box<dyn Error + Send + Sync>> {
+    let mut pg_config = db.url.parse::<tokio_postgres::Config>()?;
+    if !db.user.is_empty() {
+        pg_config.user(&db.user);
+    }
+
+    if !db.password.is_empty() {
+        pg_config.password(&db.password);
+    }
+
+    if !db.name.is_empty() {
+        pg_config.dbname(&db.name);
+    }
+
+    let connector = MakeTlsConnector::new(TlsConnector::new()?);
+    let (client, connection) = pg_config.connect(connector).await?;
+
+    //Connection performs the communication with the database and runs till the client is dropped
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            error!("Postgres connection error: {}", err);
+        }
+    });
+
+    Ok(client)
+}
+
+async fn create_tables(client: &Client) -> Result<(), tokio_postgres::Error> {
+    client.batch_execute(&format!("
+        CREATE TABLE IF NOT EXISTS {} (
+            run_id uuid PRIMARY KEY,
+            started_at timestamptz NOT NULL DEFAULT now(),
+            ended_at timestamptz,
+            node_count integer NOT NULL,
+            finished_nodes integer NOT NULL DEFAULT 0,
+            scenarios_hash text NOT NULL,
+            tags jsonb NOT NULL,
+            config jsonb NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS {} (
+            time timestamptz NOT NULL,
+            run_id uuid NOT NULL,
+            name text NOT NULL,
+            scenario text NOT NULL,
+            node text NOT NULL,
+            status integer NOT NULL,
+            error_kind text,
+            latency bigint NOT NULL,
+            ttfb bigint NOT NULL,
+            download bigint NOT NULL,
+            dns bigint NOT NULL,
+            connect bigint NOT NULL,
+            tls bigint NOT NULL,
+            bytes_sent bigint NOT NULL,
+            bytes_received bigint NOT NULL,
+            attempt integer NOT NULL,
+            thread_count integer NOT NULL,
+            thread_id bigint NOT NULL,
+            iteration bigint NOT NULL,
+            expected_interval bigint NOT NULL,
+            method text NOT NULL,
+            url text NOT NULL,
+            error_message text NOT NULL
+        );", RUNS_TABLE, STATS_TABLE)).await?;
+
+    let row = client.query_one("SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')", &[]).await?;
+    if row.get::<_, bool>(0) {
+        info!("Creating hypertable {} in TimescaleDB", STATS_TABLE);
+        client.execute(format!("SELECT create_hypertable('{}', 'time', if_not_exists => TRUE)", STATS_TABLE).as_str(), &[]).await?;
+    }
+
+    client.batch_execute(&format!("CREATE INDEX IF NOT EXISTS {0}_run_id_time_idx ON {0} (run_id, time)", STATS_TABLE)).await
+}
+
+//Rows of COPY in csv format, text is always quoted so that empty error kind is the only NULL.
+//Timestamps of stats are parsed by postgres as they are
+fn get_rows(stats: &[stats::Stats], run_id: &Uuid) -> String {
+    let mut rows = String::new();
+    for s in stats {
+        let error_kind = s.error_kind.as_ref().map(|kind| quote(&kind.to_string())).unwrap_or_default();
+        writeln!(rows, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}", quote(&s.timestamp), run_id, quote(&s.name), quote(&s.scenario),
+            quote(&s.node_id), s.status, error_kind, s.latency, s.ttfb, s.download, s.dns, s.connect, s.tls, s.bytes_sent, s.bytes_received,
+            s.attempt, s.thread_count, s.thread_id, s.iteration, s.expected_interval, quote(&s.method), quote(&s.url), quote(&s.error_message)).unwrap();
+    }
+
+    rows
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{model::{Config, ErrorKind}, report::{sink::ResultSink, stats::Stats}, storage::postgres::*};
+
+    #[test]
+    fn test_get_rows() {
+        let run_id = Uuid::parse_str("6f1c3a52-3d5e-4d2a-9a64-1f0f1c8a7b10").unwrap();
+        let mut stats = Stats::new("get \"orders\", v2", 200, 1500, 4);
+        stats.timestamp = String::from("2021-01-01 10:00:00.123456 +0530");
+        stats.url = String::from("http://localhost/orders?ids=1,2");
+        stats.connect = 250;
+
+        let mut error_stats = Stats { error_kind: Some(ErrorKind::Timeout), status: 0, ..stats.clone() };
+        error_stats.error_message = String::from("operation timed out");
+
+        assert_eq!(get_rows(&[stats, error_stats], &run_id),
+            "\"2021-01-01 10:00:00.123456 +0530\",6f1c3a52-3d5e-4d2a-9a64-1f0f1c8a7b10,\"get \"\"orders\"\", v2\",\"\",\"\",200,,1500,0,0,0,250,0,0,0,1,4,0,0,0,\"\",\"http://localhost/orders?ids=1,2\",\"\"\n\
+            \"2021-01-01 10:00:00.123456 +0530\",6f1c3a52-3d5e-4d2a-9a64-1f0f1c8a7b10,\"get \"\"orders\"\", v2\",\"\",\"\",0,\"timeout\",1500,0,0,0,250,0,0,0,1,4,0,0,0,\"\",\"http://localhost/orders?ids=1,2\",\"operation timed out\"\n");
+    }
+
+    //Run with --ignored and BOMBARDIER_TEST_POSTGRES_URL set to a database which can be written to,
+    //like postgres://postgres@localhost/postgres of a local container
+    #[tokio::test]
+    #[ignore = "needs a postgres database set in BOMBARDIER_TEST_POSTGRES_URL"]
+    async fn test_write_stats() {
+        let url = std::env::var("BOMBARDIER_TEST_POSTGRES_URL").unwrap();
+
+        let config: Config = serde_yaml::from_str(&format!("database:\n  type: postgres\n  url: {}", url)).unwrap();
+        let mut tags = std::collections::BTreeMap::new();
+        tags.insert(String::from("env"), String::from("qa"));
+        let run = Run::new(&config, "scenarios:", tags);
+        let mut writer = PostgresWriter::new(&config.database, config.node_count, &run).await.unwrap();
+        assert_eq!(writer.run_id.to_string(), run.id);
+
+        let mut stats = Stats::new("getOrders", 503, 2000, 1);
+        stats.timestamp = String::from("2021-01-01 10:00:00.123456 +0530");
+        writer.write(&[stats.clone(), Stats { status: 200, ..stats }]).await;
+        writer.finish().await;
+
+        let statement = format!("SELECT count(*), min(time)::text, max(status) FROM {} WHERE run_id = $1", STATS_TABLE);
+        let row = writer.client.query_one(statement.as_str(), &[&writer.run_id]).await.unwrap();
+        assert_eq!((row.get::<_, i64>(0), row.get::<_, String>(1), row.get::<_, i32>(2)), (2, String::from("2021-01-01 04:30:00.123456+00"), 503));
+
+        let statement = format!("SELECT node_count, ended_at IS NOT NULL, tags->>'env', config->'database'->>'type' FROM {} WHERE run_id = $1", RUNS_TABLE);
+        let row = writer.client.query_one(statement.as_str(), &[&writer.run_id]).await.unwrap();
+        assert_eq!((row.get::<_, i32>(0), row.get::<_, bool>(1)), (1, true));
+        assert_eq!((row.get::<_, String>(2), row.get::<_, String>(3)), (String::from("qa"), String::from("postgres")));
+
+        //Run of 2 nodes ends only when both of them finish
+        let run = Run::new(&config, "scenarios:", Default::default());
+        let mut writers = vec![];
+        for _ in 0..2 {
+            writers.push(PostgresWriter::new(&config.database, 2, &run).await.unwrap());
+        }
+
+        let statement = format!("SELECT finished_nodes, ended_at IS NOT NULL FROM {} WHERE run_id = $1", RUNS_TABLE);
+        for (i, writer) in writers.iter_mut().enumerate() {
+            writer.finish().await;
+            let row = writer.client.query_one(statement.as_str(), &[&writer.run_id]).await.unwrap();
+            assert_eq!((row.get::<_, i32>(0), row.get::<_, bool>(1)), (i as i32 + 1, i == 1));
+        }
+    }
+}
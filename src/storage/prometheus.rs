@@ -0,0 +1,264 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use log::{error, info, warn};
+use reqwest::header::{AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE};
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    model,
+    protocol::http::HttpClient,
+    report::{metrics::{Metrics, Sample}, run::Run, sink::ResultSink, stats}
+};
+
+//Series are pushed at most once in this interval, and once more when all the stats are written
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_JOB: &str = "bombardier";
+
+//Labels of series which can not be overridden by tags of the run
+const RESERVED_LABELS: [&str; 8] = ["__name__", "job", "run", "request", "node", "status", "kind", "le"];
+
+//Pushes the series of Prometheus exporter to a remote write endpoint as snappy compressed protobuf.
+//Every push has the cumulative values of all the series, so a failed push is made up by the next one
+pub struct PrometheusWriter {
+    client: HttpClient,
+    url: String,
+    authorization: Option<String>,
+
+    //Job, run id and tags of the run, added to every series
+    labels: Vec<(String, String)>,
+    metrics: Metrics,
+    last_push: Option<Instant>
+}
+
+impl PrometheusWriter {
+    pub fn new(db: &model::Database, run: &Run) -> Option<PrometheusWriter> {
+        if db.url.is_empty() {
+            error!("Prometheus remote write url is not set, not initializing the PrometheusWriter");
+            return None;
+        }
+
+        let authorization = match db.user.is_empty() {
+            true => None,
+            false => Some(format!("Basic {}", base64::encode(format!("{}:{}", db.user, db.password))))
+        };
+
+        //Database name is used as job label
+        let job = match db.name.is_empty() {
+            true => DEFAULT_JOB.to_owned(),
+            false => db.name.clone()
+        };
+
+        match HttpClient::get_default_async_client() {
+            Ok(client) => Some(PrometheusWriter {
+                client,
+                url: db.url.clone(),
+                authorization,
+                labels: get_labels(&job, run),
+                metrics: Metrics::new(),
+                last_push: None
+            }),
+            Err(err) => {
+                error!("Error while initiating new Prometheus client {}", err.to_string());
+                None
+            }
+        }
+    }
+
+    async fn push(&mut self) {
+        self.last_push = Some(Instant::now());
+
+        let body = encode_write_request(&self.metrics.get_samples(), &self.labels, Utc::now().timestamp_millis());
+        let body = match snap::raw::Encoder::new().compress_vec(&body) {
+            Ok(body) => body,
+            Err(err) => {
+                error!("Error compressing series for prometheus: {}", err);
+                return
+            }
+        };
+
+        let mut request = self.client.get_client().post(&self.url)
+            .header(CONTENT_TYPE, "application/x-protobuf")
+            .header(CONTENT_ENCODING, "snappy")
+            .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+            .body(body);
+
+        if let Some(authorization) = self.authorization.as_ref() {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => (),
+            Ok(response) => error!("Error writing to prometheus: status {} {}", response.status(), response.text().await.unwrap_or_default()),
+            Err(err) => error!("Error writing to prometheus: {}", err)
+        }
+    }
+}
+
+#[async_trait]
+impl ResultSink for PrometheusWriter {
+    async fn write(&mut self, stats: &[stats::Stats]) {
+        self.metrics.record(stats);
+
+        if self.last_push.is_none_or(|last_push| last_push.elapsed() >= PUSH_INTERVAL) {
+            self.push().await;
+        }
+    }
+
+    async fn finish(&mut self) {
+        info!("Pushing final series to prometheus");
+        self.push().await;
+    }
+
+    fn drops_when_full(&self) -> bool {
+        true
+    }
+}
+
+//Tag keys are sanitized as label names can only have letters, digits and underscores
+fn get_labels(job: &str, run: &Run) -> Vec<(String, String)> {
+    let mut labels = vec![(String::from("job"), job.to_owned())];
+    if !run.id.is_empty() {
+        labels.push((String::from("run"), run.id.clone()));
+    }
+
+    for (key, value) in &run.tags {
+        let mut name = key.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect::<String>();
+        if name.starts_with(|c: char| c.is_ascii_digit()) {
+            name.insert(0, '_');
+        }
+
+        match RESERVED_LABELS.contains(&name.as_str()) || labels.iter().any(|(label, _)| *label == name) {
+            true => warn!("Tag {} is not added to prometheus series as it is reserved or duplicate", key),
+            false => labels.push((name, value.clone()))
+        }
+    }
+
+    labels
+}
+
+//WriteRequest of remote write protocol, every sample is a time series with labels sorted by name.
+//  WriteRequest { repeated TimeSeries timeseries = 1 }
+//  TimeSeries { repeated Label labels = 1; repeated Sample samples = 2 }
+//  Label { string name = 1; string value = 2 }
+//  Sample { double value = 1; int64 timestamp = 2 }
+fn encode_write_request(samples: &[Sample], series_labels: &[(String, String)], timestamp: i64) -> Vec<u8> {
+    let mut request = Vec::new();
+
+    for sample in samples {
+        let mut labels = vec![("__name__", sample.name.as_str())];
+        labels.extend(series_labels.iter().map(|(name, value)| (name.as_str(), value.as_str())));
+        labels.extend(sample.labels.iter().map(|(name, value)| (*name, value.as_str())));
+        labels.sort();
+
+        let mut series = Vec::new();
+        for (name, value) in labels {
+            let mut label = Vec::new();
+            write_bytes(&mut label, 1, name.as_bytes());
+            write_bytes(&mut label, 2, value.as_bytes());
+            write_bytes(&mut series, 1, &label);
+        }
+
+        let mut value = Vec::new();
+        write_key(&mut value, 1, 1);
+        value.extend(sample.value.to_le_bytes());
+        write_key(&mut value, 2, 0);
+        write_varint(&mut value, timestamp as u64);
+        write_bytes(&mut series, 2, &value);
+
+        write_bytes(&mut request, 1, &series);
+    }
+
+    request
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+fn write_key(buffer: &mut Vec<u8>, field: u64, wire_type: u64) {
+    write_varint(buffer, field << 3 | wire_type);
+}
+
+fn write_bytes(buffer: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_key(buffer, field, 2);
+    write_varint(buffer, bytes.len() as u64);
+    buffer.extend(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{report::{metrics::Metrics, stats::Stats}, storage::prometheus::*};
+
+    #[test]
+    fn test_write_varint() {
+        let mut buffer = Vec::new();
+        write_varint(&mut buffer, 1);
+        write_varint(&mut buffer, 300);
+        write_varint(&mut buffer, 1_600_000_000_000);
+        assert_eq!(buffer, vec![0x01, 0xac, 0x02, 0x80, 0x80, 0xba, 0xbb, 0xc8, 0x2e]);
+    }
+
+    #[test]
+    fn test_encode_write_request() {
+        let metrics = Metrics::new();
+        metrics.record(&[Stats::new("get", 200, 1000, 1)]);
+
+        let samples = metrics.get_samples();
+        let labels = get_labels("ci", &Run { id: String::from("6f1c"), ..Default::default() });
+        let request = encode_write_request(&samples[..1], &labels, 1);
+
+        //bombardier_requests_total{job="ci",request="get",run="6f1c",status="200"} 1 @ 1ms
+        let label = |name: &str, value: &str| {
+            let mut label = vec![0x0a, name.len() as u8];
+            label.extend(name.as_bytes());
+            label.extend([0x12, value.len() as u8]);
+            label.extend(value.as_bytes());
+
+            let mut field = vec![0x0a, label.len() as u8];
+            field.extend(label);
+            field
+        };
+
+        let mut series = label("__name__", "bombardier_requests_total");
+        series.extend(label("job", "ci"));
+        series.extend(label("request", "get"));
+        series.extend(label("run", "6f1c"));
+        series.extend(label("status", "200"));
+        series.extend([0x12, 11, 0x09]);
+        series.extend(1.0f64.to_le_bytes());
+        series.extend([0x10, 0x01]);
+
+        let mut expected = vec![0x0a, series.len() as u8];
+        expected.extend(series);
+        assert_eq!(request, expected);
+    }
+
+    #[test]
+    fn test_get_labels() {
+        let mut run = Run { id: String::from("6f1c"), ..Default::default() };
+        run.tags.insert(String::from("git.sha"), String::from("a1b2"));
+        run.tags.insert(String::from("1env"), String::from("qa"));
+        run.tags.insert(String::from("status"), String::from("ok"));
+
+        let labels = get_labels("ci", &run);
+        let labels = labels.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect::<Vec<(&str, &str)>>();
+        assert_eq!(labels, vec![("job", "ci"), ("run", "6f1c"), ("_1env", "qa"), ("git_sha", "a1b2")]);
+    }
+
+    #[test]
+    fn test_new() {
+        let db = model::Database { db_type: String::from("prometheus"), ..Default::default() };
+        assert!(PrometheusWriter::new(&db, &Run::default()).is_none());
+
+        let db = model::Database { url: String::from("http://localhost:9090/api/v1/write"), user: String::from("user"), ..db };
+        let writer = PrometheusWriter::new(&db, &Run::default()).unwrap();
+        assert_eq!(writer.labels, vec![(String::from("job"), String::from("bombardier"))]);
+        assert_eq!(writer.authorization.as_deref(), Some("Basic dXNlcjo="));
+    }
+}